@@ -6,6 +6,7 @@ import {
   drilldownHtml,
   renderAlerts,
   resolveAlertSessionId,
+  isActionableAlert,
   resetAlertSelection,
   selectAlertById
 } from '../lib/renders/alerts.js';
@@ -421,3 +422,48 @@ describe('renderAlerts', () => {
     assert.ok(drilldownRoot.innerHTML.includes('sess-1'));
   });
 });
+
+// ── alert lifecycle ──
+
+describe('alert lifecycle', () => {
+  it('shows repeat count and non-open status', () => {
+    const html = alertItemHtml(makeAlert({ count: 3, status: 'snoozed' }));
+    assert.ok(html.includes('×3'));
+    assert.ok(html.includes('alert-status--snoozed'));
+  });
+
+  it('omits count badge for single occurrence', () => {
+    const html = alertItemHtml(makeAlert({ count: 1, status: 'open' }));
+    assert.ok(!html.includes('alert-count'));
+    assert.ok(!html.includes('alert-status'));
+  });
+
+  it('renders action buttons for server alerts only', () => {
+    const ctx = getAlertContext(makeAlert(), null);
+    const html = drilldownHtml(makeAlert({ status: 'open' }), ctx);
+    assert.ok(html.includes('data-alert-action="ack"'));
+    assert.ok(html.includes('data-alert-action="snooze"'));
+    assert.ok(html.includes('data-alert-action="resolve"'));
+
+    const derived = drilldownHtml(makeAlert({ id: 'derived:stuck:sess-1', status: 'open' }), ctx);
+    assert.ok(!derived.includes('data-alert-action'));
+  });
+
+  it('hides ack button once acked', () => {
+    const html = drilldownHtml(makeAlert({ status: 'acked' }), getAlertContext(makeAlert(), null));
+    assert.ok(!html.includes('data-alert-action="ack"'));
+    assert.ok(html.includes('data-alert-action="resolve"'));
+  });
+
+  it('isActionableAlert ignores legacy rows without status', () => {
+    assert.equal(isActionableAlert(makeAlert()), false);
+    assert.equal(isActionableAlert(makeAlert({ status: 'open' })), true);
+  });
+});
+
+describe('rule alerts', () => {
+  it('labels project/global rule alerts by rule id', () => {
+    const html = alertItemHtml(makeAlert({ agentId: '', sessionId: '', ruleId: 'burn-rate', status: 'open' }));
+    assert.ok(html.includes('Rule burn-rate'));
+  });
+});
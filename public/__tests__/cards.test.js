@@ -39,6 +39,13 @@ describe('buildCardData', () => {
     assert.equal(card.type, 'ok');
   });
 
+  it('counts busy sessions as active', () => {
+    const sessions = makeSessions(['busy', 'active', 'stuck']);
+    const cards = buildCardData(sessions, {}, numberFmt);
+    const card = cards.find((c) => c.label === '활성 세션');
+    assert.equal(card.value, '2');
+  });
+
   it('assigns neutral type to 활성 세션 when count is 0', () => {
     const sessions = makeSessions(['completed', 'idle']);
     const cards = buildCardData(sessions, {}, numberFmt);
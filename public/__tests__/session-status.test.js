@@ -62,6 +62,10 @@ describe('toWorkflowStatus', () => {
     assert.equal(toWorkflowStatus('active'), 'running');
   });
 
+  it('maps busy to running', () => {
+    assert.equal(toWorkflowStatus('busy'), 'running');
+  });
+
   it('maps stuck to at-risk', () => {
     assert.equal(toWorkflowStatus('stuck'), 'at-risk');
   });
@@ -220,4 +224,13 @@ describe('annotateSessionsWithState', () => {
     assert.deepEqual(rows[0].needsAttentionReasons, ['cost_spike']);
     assert.equal(rows[0].needsAttentionRank, 100);
   });
+
+  it('keeps the state computed by the server', () => {
+    const sessions = [{ sessionId: 'sess-1', sessionState: 'stuck', lastSeen: '2026-01-01T00:00:00Z', tokenTotal: 100, costUsd: 0.01, agentIds: ['a1'] }];
+    const agents = [{ agentId: 'a1', sessionId: 'sess-1', total: 2, warning: 0, error: 0, lastEvent: 'assistant_message', lastSeen: '2026-01-01T00:00:00Z' }];
+    const rows = annotateSessionsWithState(sessions, agents, new Date('2026-01-01T00:00:10Z').getTime());
+
+    assert.equal(rows[0].sessionState, 'stuck');
+    assert.deepEqual(rows[0].needsAttentionReasons, ['stuck']);
+  });
 });
@@ -1,6 +1,6 @@
 import { describe, it } from 'node:test';
 import assert from 'node:assert/strict';
-import { extractAgentMeta, applyIncrementalEvent } from '../lib/state.js';
+import { extractAgentMeta, applyIncrementalEvent, applyAlertUpdate, applySessionStateChange } from '../lib/state.js';
 
 describe('extractAgentMeta', () => {
   it('extracts top-level model, isSidechain, sessionId', () => {
@@ -145,35 +145,13 @@ describe('applyIncrementalEvent', () => {
     assert.equal(state.recent[0].event, 'new');
   });
 
-  it('creates alert for warning event', () => {
+  it('leaves alerts to server alert updates', () => {
     const state = makeState();
     applyIncrementalEvent(state, makeEvent({ status: 'warning', agentId: 'a1' }));
-    assert.equal(state.alerts.length, 1);
-    assert.equal(state.alerts[0].severity, 'warning');
-    assert.equal(state.alerts[0].agentId, 'a1');
-  });
-
-  it('creates alert for error event', () => {
-    const state = makeState();
     applyIncrementalEvent(state, makeEvent({ status: 'error' }));
-    assert.equal(state.alerts.length, 1);
-    assert.equal(state.alerts[0].severity, 'error');
-  });
-
-  it('does not create alert for ok event', () => {
-    const state = makeState();
-    applyIncrementalEvent(state, makeEvent({ status: 'ok' }));
     assert.equal(state.alerts.length, 0);
   });
 
-  it('caps alerts at 20', () => {
-    const state = makeState();
-    state.alerts = Array.from({ length: 20 }, () => ({ id: 'old' }));
-    applyIncrementalEvent(state, makeEvent({ status: 'error' }));
-    assert.equal(state.alerts.length, 20);
-    assert.equal(state.alerts[0].severity, 'error');
-  });
-
   it('sorts agents by agentId', () => {
     const state = makeState();
     applyIncrementalEvent(state, makeEvent({ agentId: 'b-agent' }));
@@ -251,3 +229,55 @@ describe('applyIncrementalEvent', () => {
     assert.equal(state.totals.sessions, 2);
   });
 });
+
+describe('applyAlertUpdate', () => {
+  it('inserts a new alert at the top', () => {
+    const state = makeState();
+    state.alerts = [{ id: 'a1', status: 'open' }];
+    applyAlertUpdate(state, { id: 'a2', status: 'open', severity: 'error' });
+    assert.deepEqual(state.alerts.map((a) => a.id), ['a2', 'a1']);
+  });
+
+  it('replaces a repeated alert in place of the old row', () => {
+    const state = makeState();
+    state.alerts = [{ id: 'a2', status: 'open' }, { id: 'a1', status: 'open', count: 1 }];
+    applyAlertUpdate(state, { id: 'a1', status: 'open', count: 2 });
+    assert.equal(state.alerts.length, 2);
+    assert.equal(state.alerts[0].id, 'a1');
+    assert.equal(state.alerts[0].count, 2);
+  });
+
+  it('drops resolved alerts', () => {
+    const state = makeState();
+    state.alerts = [{ id: 'a1', status: 'open' }];
+    applyAlertUpdate(state, { id: 'a1', status: 'resolved' });
+    assert.equal(state.alerts.length, 0);
+  });
+});
+
+describe('applySessionStateChange', () => {
+  it('copies the new state and risk onto the matching session', () => {
+    const state = makeState();
+    state.sessions = [{ sessionId: 's1', sessionState: 'active', lastSeen: '2026-01-01T00:00:00Z' }];
+    applySessionStateChange(state, {
+      sessionId: 's1',
+      previousState: 'active',
+      sessionState: 'stuck',
+      needsAttention: true,
+      needsAttentionRank: 300,
+      needsAttentionReasons: ['stuck'],
+      isCostSpike: false,
+      changedAt: '2026-01-01T00:03:00Z'
+    });
+    assert.equal(state.sessions[0].sessionState, 'stuck');
+    assert.deepEqual(state.sessions[0].needsAttentionReasons, ['stuck']);
+    assert.equal(state.sessions[0].lastSeen, '2026-01-01T00:00:00Z');
+    assert.equal(state.sessions[0].changedAt, undefined);
+  });
+
+  it('ignores changes for unknown sessions', () => {
+    const state = makeState();
+    applySessionStateChange(state, { sessionId: 'missing', sessionState: 'failed' });
+    assert.equal(state.sessions.length, 0);
+  });
+});
@@ -2,7 +2,7 @@ import { recalcWorkflow, splitWorkflow } from './lib/workflow.js';
 import { buildCardData } from './lib/cards.js';
 import { sumByRange, rangeLabel } from './lib/time-range.js';
 import { escapeHtml, statusPill, getActivityStatus, activityDotHtml } from './lib/utils.js';
-import { applyIncrementalEvent } from './lib/state.js';
+import { applyIncrementalEvent, applyAlertUpdate, applySessionStateChange } from './lib/state.js';
 import { ALERT_RULES_STORAGE_KEY } from './lib/alert-rules.js';
 import { saveFilters, loadFilters, saveToggle, loadToggle, loadAlertRules, saveAlertRules, resetAlertRules } from './lib/persistence.js';
 import { connectStream, loadSnapshot } from './lib/connection.js';
@@ -218,7 +218,7 @@ function renderSnapshot(snapshot) {
     if (empty) renderEmptyState(emptyStateEl);
   }
   const sessionRows = annotateSessionsWithState(snapshot.sessions || [], snapshot.agents || [], Date.now(), alertRules);
-  const alerts = mergeAlertsForPanel(snapshot.alerts || [], sessionRows, { generatedAt: snapshot.generatedAt });
+  const alerts = mergeAlertsForPanel((snapshot.alerts || []).filter((alert) => alert.status !== 'resolved'), sessionRows, { generatedAt: snapshot.generatedAt });
   const alertSnapshot = { ...snapshot, sessions: sessionRows };
   currentPanelAlerts = alerts;
   currentAlertSnapshot = alertSnapshot;
@@ -440,6 +440,18 @@ connectStream({
       queueRender();
     }
   },
+  onAlert(alert) {
+    if (snapshotState) {
+      applyAlertUpdate(snapshotState, alert);
+      queueRender();
+    }
+  },
+  onSessionState(change) {
+    if (snapshotState) {
+      applySessionStateChange(snapshotState, change);
+      queueRender();
+    }
+  },
   onFallback() { loadSnapshot().then((snapshot) => renderSnapshot(snapshot)).catch(console.error); }
 });
 
@@ -1,5 +1,5 @@
 export function buildCardData(sessions = [], totals = {}, numberFmt, rangeInfo = null) {
-  const activeSessions = sessions.filter((s) => s.sessionState === 'active').length;
+  const activeSessions = sessions.filter((s) => s.sessionState === 'active' || s.sessionState === 'busy').length;
   const needsAttention = sessions.filter((s) => Boolean(s.needsAttention)).length;
 
   const tokenLabel = rangeInfo ? `${rangeInfo.label} 토큰` : '전체 토큰';
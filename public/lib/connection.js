@@ -54,7 +54,7 @@ export async function loadSnapshot() {
   return res.json();
 }
 
-export function connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEvent, onFallback }) {
+export function connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEvent, onAlert, onSessionState, onFallback }) {
   if (streamRef) {
     streamRef.close();
   }
@@ -89,6 +89,14 @@ export function connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEv
         onEvent(parsed.payload);
         return;
       }
+      if (parsed.type === 'alert' && parsed.payload) {
+        onAlert?.(parsed.payload);
+        return;
+      }
+      if (parsed.type === 'session_state_changed' && parsed.payload) {
+        onSessionState?.(parsed.payload);
+        return;
+      }
     } catch {
       // fallback below
     }
@@ -104,6 +112,6 @@ export function connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEv
       onSnapshot(snapshot);
     });
     es.close();
-    setTimeout(() => connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEvent, onFallback }), 1500);
+    setTimeout(() => connectStream({ connectionEl, connectionMetaEl, onSnapshot, onEvent, onAlert, onSessionState, onFallback }), 1500);
   };
 }
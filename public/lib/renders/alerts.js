@@ -6,6 +6,7 @@ let _selectedAlertId = null;
 function alertSubjectLabel(alert = {}) {
   if (alert.agentId) return displayNameFor(alert.agentId);
   if (alert.sessionId) return `Session ${alert.sessionId}`;
+  if (alert.ruleId) return `Rule ${alert.ruleId}`;
   return 'Unknown';
 }
 
@@ -33,11 +34,41 @@ export function alertItemHtml(alert, isSelected = false, sessionId = '') {
         <span>${new Date(alert.createdAt).toLocaleTimeString()}</span>
         <span title="${escapeHtml(alertSubjectTitle(alert))}"><strong>${escapeHtml(alertSubjectLabel(alert))}</strong></span>
         <span>${escapeHtml(alert.event)}</span>
-        <span>${escapeHtml(alert.message)}</span>
-        ${statusPill(alert.severity)}
+        <span>${escapeHtml(alert.message)}${alertCountBadge(alert)}</span>
+        ${statusPill(alert.severity)}${alertLifecyclePill(alert)}
       </div>`;
 }
 
+function alertCountBadge(alert = {}) {
+  return alert.count > 1 ? ` <span class="alert-count">×${alert.count}</span>` : '';
+}
+
+function alertLifecyclePill(alert = {}) {
+  if (!alert.status || alert.status === 'open') return '';
+  return ` <span class="pill alert-status alert-status--${escapeHtml(alert.status)}">${escapeHtml(alert.status)}</span>`;
+}
+
+// Derived alerts are recomputed from session state and have no server-side lifecycle.
+export function isActionableAlert(alert = {}) {
+  return Boolean(alert.id) && !String(alert.id).startsWith('derived:') && Boolean(alert.status);
+}
+
+function alertActionsHtml(alert) {
+  if (!isActionableAlert(alert)) return '';
+  const button = (action, label) =>
+    `<button class="alert-action" data-alert-action="${action}" data-alert-action-id="${escapeHtml(alert.id)}">${label}</button>`;
+  return `
+    <div class="drilldown-section drilldown-alert-actions">
+      ${alert.status !== 'acked' ? button('ack', 'Ack') : ''}
+      ${button('snooze', 'Snooze 1h')}
+      ${button('resolve', 'Resolve')}
+    </div>`;
+}
+
+export function postAlertAction(alertId, action) {
+  return fetch(`/api/alerts/${encodeURIComponent(alertId)}/${action}`, { method: 'POST' });
+}
+
 export function getAlertContext(alertOrAgentId, snapshot) {
   if (!snapshot) return { recentEvents: [], agentState: null, linkedSessionId: '', contextLabel: '' };
 
@@ -105,6 +136,7 @@ export function drilldownHtml(alert, context) {
       <h3>${statusPill(alert.severity)} ${escapeHtml(alert.event)}</h3>
       <button class="drilldown-close" data-drilldown-close>✕</button>
     </div>
+    ${alertActionsHtml(alert)}
     <div class="drilldown-section">
       <h3>Message</h3>
       <p>${escapeHtml(alert.message)}</p>
@@ -201,6 +233,12 @@ export function renderAlerts(alerts, el, drilldownEl, snapshot, options = {}) {
         onOpenSession(openBtn.dataset.sessionOpen);
         return;
       }
+      const actionBtn = event.target.closest('[data-alert-action]');
+      if (actionBtn) {
+        // The server answers with an `alert` stream message that re-renders the panel.
+        postAlertAction(actionBtn.dataset.alertActionId, actionBtn.dataset.alertAction).catch(console.error);
+        return;
+      }
       if (event.target.closest('[data-drilldown-close]')) {
         _selectedAlertId = null;
         drilldownEl.setAttribute('hidden', '');
@@ -97,7 +97,7 @@ export function matchesSessionFilters(session = {}, filters = {}) {
     : safeNumber(session.needsAttentionRank) > 0;
 
   if (quickFilter === 'needs-attention' && !needsAttention) return false;
-  if (quickFilter === 'active' && session.sessionState !== 'active') return false;
+  if (quickFilter === 'active' && session.sessionState !== 'active' && session.sessionState !== 'busy') return false;
   if (quickFilter === 'completed' && session.sessionState !== 'completed') return false;
   if (query && !sessionSearchText(session).includes(query)) return false;
   return true;
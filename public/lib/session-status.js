@@ -120,6 +120,7 @@ export function deriveRiskSignals(row = {}, nowOrRules = Date.now(), maybeRules)
 export function toWorkflowStatus(sessionState) {
   switch (sessionState) {
     case 'active':
+    case 'busy':
       return 'running';
     case 'stuck':
       return 'at-risk';
@@ -172,7 +173,9 @@ export function annotateSessionsWithState(sessions = [], agents = [], nowOrRules
       agentLastEvents: [],
       latestAgentEvent: ''
     };
-    const derivedState = deriveSessionState(
+    // The server keeps sessionState current; deriving it here only covers
+    // rows that arrived without one.
+    const derivedState = session.sessionState || deriveSessionState(
       {
         total: totals.total,
         warning: totals.warning,
@@ -76,17 +76,8 @@ export function applyIncrementalEvent(state, evt) {
   state.recent.unshift(evt);
   state.recent = state.recent.slice(0, 200);
 
-  if (evt.status === 'warning' || evt.status === 'error') {
-    state.alerts.unshift({
-      id: `inline-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`,
-      severity: evt.status,
-      agentId: evt.agentId,
-      event: evt.event,
-      message: evt.message || 'No message',
-      createdAt: evt.receivedAt
-    });
-    state.alerts = state.alerts.slice(0, 20);
-  }
+  // Alerts arrive separately as `alert` stream messages (see applyAlertUpdate),
+  // since the server folds repeats into one alert with a count.
 
   const sessionId = evt.sessionId || evt.metadata?.sessionId || '';
   if (sessionId) {
@@ -118,3 +109,19 @@ export function applyIncrementalEvent(state, evt) {
   state.workflowProgress = recalcWorkflow(state.agents);
   state.generatedAt = new Date().toISOString();
 }
+
+export function applyAlertUpdate(state, alert) {
+  if (!alert?.id) return;
+  const alerts = (state.alerts || []).filter((row) => row.id !== alert.id);
+  if (alert.status !== 'resolved') {
+    alerts.unshift(alert);
+  }
+  state.alerts = alerts.slice(0, 120);
+}
+
+export function applySessionStateChange(state, change) {
+  const session = (state.sessions || []).find((row) => row.sessionId === change?.sessionId);
+  if (!session) return;
+  const { sessionId, previousState, changedAt, ...risk } = change;
+  Object.assign(session, risk);
+}
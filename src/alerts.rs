@@ -0,0 +1,340 @@
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::db::DbWrite;
+use crate::state::broadcast_sse;
+use crate::types::{AlertRow, App, Event, State};
+use crate::utils::now_iso;
+
+pub const MAX_ALERTS: usize = 120;
+pub const DEFAULT_SNOOZE_MINUTES: i64 = 60;
+/// Longest snooze accepted: 30 days.
+pub const MAX_SNOOZE_MINUTES: i64 = 30 * 24 * 60;
+
+pub const STATUS_OPEN: &str = "open";
+pub const STATUS_ACKED: &str = "acked";
+pub const STATUS_SNOOZED: &str = "snoozed";
+pub const STATUS_RESOLVED: &str = "resolved";
+
+/// Alerts for the same agent, session and event type are one alert.
+pub fn alert_fingerprint(agent_id: &str, session_id: &str, event: &str) -> String {
+    format!("{}|{}|{}", agent_id, session_id, event)
+}
+
+pub enum AlertAction {
+    Ack,
+    Snooze { minutes: i64 },
+    Resolve,
+}
+
+impl AlertAction {
+    /// Maps the last path segment of `POST /api/alerts/:id/<action>`.
+    pub fn from_route(action: &str, minutes: Option<i64>) -> Option<Self> {
+        match action {
+            "ack" => Some(Self::Ack),
+            "snooze" => Some(Self::Snooze {
+                minutes: minutes
+                    .unwrap_or(DEFAULT_SNOOZE_MINUTES)
+                    .clamp(1, MAX_SNOOZE_MINUTES),
+            }),
+            "resolve" => Some(Self::Resolve),
+            _ => None,
+        }
+    }
+}
+
+/// Folds a warning/error event into the alert list. Returns the alert as it is now.
+pub fn record_alert(state: &mut State, evt: &Event, new_id: impl FnOnce() -> String) -> AlertRow {
+    let message = if evt.message.is_empty() {
+        "No message".to_string()
+    } else {
+        evt.message.clone()
+    };
+    let draft = AlertRow {
+        severity: evt.status.clone(),
+        agent_id: evt.agent_id.clone(),
+        session_id: evt.session_id.clone(),
+        event: evt.event.clone(),
+        message,
+        created_at: evt.received_at.clone(),
+        fingerprint: alert_fingerprint(&evt.agent_id, &evt.session_id, &evt.event),
+        ..AlertRow::default()
+    };
+    raise_alert(state, draft, new_id)
+}
+
+/// Raises `draft` (fingerprint, subject, severity, message and `created_at`
+/// filled in): a repeat of an alert that is not resolved bumps its count and
+/// moves it to the top; anything else opens a new alert.
+pub fn raise_alert(
+    state: &mut State,
+    draft: AlertRow,
+    new_id: impl FnOnce() -> String,
+) -> AlertRow {
+    let seen_at = draft.created_at.clone();
+    let existing = state
+        .alerts
+        .iter()
+        .position(|a| a.fingerprint == draft.fingerprint && a.status != STATUS_RESOLVED);
+
+    let alert = match existing {
+        Some(index) => {
+            let mut alert = state.alerts.remove(index);
+            alert.count += 1;
+            alert.last_seen = seen_at.clone();
+            alert.severity = draft.severity;
+            alert.message = draft.message;
+            if alert.status == STATUS_SNOOZED && snooze_expired(&alert, &seen_at) {
+                alert.status = STATUS_OPEN.to_string();
+                alert.snoozed_until = None;
+            }
+            alert
+        }
+        None => AlertRow {
+            id: new_id(),
+            count: 1,
+            last_seen: seen_at,
+            status: STATUS_OPEN.to_string(),
+            snoozed_until: None,
+            ..draft
+        },
+    };
+    state.alerts.insert(0, alert.clone());
+    if state.alerts.len() > MAX_ALERTS {
+        state.alerts.truncate(MAX_ALERTS);
+    }
+    alert
+}
+
+fn snooze_expired(alert: &AlertRow, now: &str) -> bool {
+    let parse = |s: &str| OffsetDateTime::parse(s, &Rfc3339).ok();
+    match (alert.snoozed_until.as_deref().and_then(parse), parse(now)) {
+        (Some(until), Some(now)) => now >= until,
+        _ => true,
+    }
+}
+
+/// Applies an ack/snooze/resolve to the alert with `id`, then persists and
+/// broadcasts the new state. Returns `None` when no such alert is held.
+pub fn apply_alert_action(app: &App, id: &str, action: AlertAction) -> Option<AlertRow> {
+    let alert = {
+        let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+        let alert = state.alerts.iter_mut().find(|a| a.id == id)?;
+        match action {
+            AlertAction::Ack => {
+                alert.status = STATUS_ACKED.to_string();
+                alert.snoozed_until = None;
+            }
+            AlertAction::Snooze { minutes } => {
+                let until = OffsetDateTime::now_utc() + Duration::minutes(minutes);
+                alert.status = STATUS_SNOOZED.to_string();
+                alert.snoozed_until = until.format(&Rfc3339).ok();
+            }
+            AlertAction::Resolve => {
+                alert.status = STATUS_RESOLVED.to_string();
+                alert.snoozed_until = None;
+            }
+        }
+        alert.clone()
+    };
+    if let Some(writer) = &app.db_writer {
+        writer.send(DbWrite::Alert(Box::new(alert.clone())));
+    }
+    broadcast_alert(app, &alert);
+    Some(alert)
+}
+
+pub fn broadcast_alert(app: &App, alert: &AlertRow) {
+    let payload = json!({ "type": "alert", "payload": alert }).to_string();
+    broadcast_sse(app, format!("data: {}\n\n", payload));
+}
+
+/// Fills lifecycle fields on alerts stored before they existed.
+pub fn upgrade_legacy_alert(alert: &mut AlertRow) {
+    if alert.fingerprint.is_empty() {
+        alert.fingerprint = alert_fingerprint(&alert.agent_id, &alert.session_id, &alert.event);
+    }
+    if alert.count == 0 {
+        alert.count = 1;
+    }
+    if alert.last_seen.is_empty() {
+        alert.last_seen = alert.created_at.clone();
+    }
+    if alert.status.is_empty() {
+        alert.status = STATUS_OPEN.to_string();
+    }
+}
+
+/// `GET /api/alerts?status=` filter; `active` means open or snoozed past its deadline.
+pub fn alert_matches_status(alert: &AlertRow, status: &str) -> bool {
+    match status {
+        "" | "all" => true,
+        "active" => {
+            alert.status == STATUS_OPEN
+                || (alert.status == STATUS_SNOOZED && snooze_expired(alert, &now_iso()))
+        }
+        other => alert.status == other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(status: &str, name: &str, received_at: &str) -> Event {
+        Event {
+            id: "e1".to_string(),
+            agent_id: "a1".to_string(),
+            event: name.to_string(),
+            status: status.to_string(),
+            latency_ms: None,
+            message: format!("{} at {}", name, received_at),
+            metadata: json!({}),
+            timestamp: received_at.to_string(),
+            received_at: received_at.to_string(),
+            model: String::new(),
+            is_sidechain: false,
+            session_id: "s1".to_string(),
+            cwd: String::new(),
+            dedup_key: None,
+        }
+    }
+
+    #[test]
+    fn test_record_alert_dedupes_by_fingerprint() {
+        let mut state = State::default();
+        let first = record_alert(
+            &mut state,
+            &event("warning", "tool_result", "2025-01-01T00:00:00Z"),
+            || "a1".to_string(),
+        );
+        record_alert(
+            &mut state,
+            &event("warning", "api_error", "2025-01-01T00:00:30Z"),
+            || "a2".to_string(),
+        );
+        let again = record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:01:00Z"),
+            || unreachable!(),
+        );
+        assert_eq!(state.alerts.len(), 2);
+        assert_eq!(again.id, first.id);
+        assert_eq!(again.count, 2);
+        assert_eq!(again.created_at, "2025-01-01T00:00:00Z");
+        assert_eq!(again.last_seen, "2025-01-01T00:01:00Z");
+        assert_eq!(again.severity, "error");
+        assert_eq!(state.alerts[0].id, first.id);
+    }
+
+    #[test]
+    fn test_record_alert_opens_new_alert_after_resolve() {
+        let mut state = State::default();
+        record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:00:00Z"),
+            || "a1".to_string(),
+        );
+        state.alerts[0].status = STATUS_RESOLVED.to_string();
+        let reopened = record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:05:00Z"),
+            || "a2".to_string(),
+        );
+        assert_eq!(reopened.id, "a2");
+        assert_eq!(reopened.count, 1);
+        assert_eq!(state.alerts.len(), 2);
+    }
+
+    #[test]
+    fn test_record_alert_keeps_ack_and_wakes_expired_snooze() {
+        let mut state = State::default();
+        record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:00:00Z"),
+            || "a1".to_string(),
+        );
+        state.alerts[0].status = STATUS_ACKED.to_string();
+        let acked = record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:01:00Z"),
+            || unreachable!(),
+        );
+        assert_eq!(acked.status, STATUS_ACKED);
+
+        state.alerts[0].status = STATUS_SNOOZED.to_string();
+        state.alerts[0].snoozed_until = Some("2025-01-01T00:30:00Z".to_string());
+        let snoozed = record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:10:00Z"),
+            || unreachable!(),
+        );
+        assert_eq!(snoozed.status, STATUS_SNOOZED);
+        let woken = record_alert(
+            &mut state,
+            &event("error", "tool_result", "2025-01-01T00:31:00Z"),
+            || unreachable!(),
+        );
+        assert_eq!(woken.status, STATUS_OPEN);
+        assert_eq!(woken.snoozed_until, None);
+        assert_eq!(woken.count, 4);
+    }
+
+    #[test]
+    fn test_alert_action_from_route() {
+        assert!(matches!(
+            AlertAction::from_route("ack", None),
+            Some(AlertAction::Ack)
+        ));
+        assert!(matches!(
+            AlertAction::from_route("snooze", None),
+            Some(AlertAction::Snooze {
+                minutes: DEFAULT_SNOOZE_MINUTES
+            })
+        ));
+        assert!(matches!(
+            AlertAction::from_route("snooze", Some(0)),
+            Some(AlertAction::Snooze { minutes: 1 })
+        ));
+        assert!(matches!(
+            AlertAction::from_route("snooze", Some(10_000_000_000)),
+            Some(AlertAction::Snooze {
+                minutes: MAX_SNOOZE_MINUTES
+            })
+        ));
+        assert!(AlertAction::from_route("delete", None).is_none());
+    }
+
+    #[test]
+    fn test_upgrade_legacy_alert() {
+        let mut alert = AlertRow {
+            id: "a1".to_string(),
+            agent_id: "agent".to_string(),
+            session_id: "s1".to_string(),
+            event: "tool_result".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            ..AlertRow::default()
+        };
+        upgrade_legacy_alert(&mut alert);
+        assert_eq!(alert.fingerprint, "agent|s1|tool_result");
+        assert_eq!(alert.count, 1);
+        assert_eq!(alert.last_seen, "2025-01-01T00:00:00Z");
+        assert_eq!(alert.status, STATUS_OPEN);
+    }
+
+    #[test]
+    fn test_alert_matches_status() {
+        let mut alert = AlertRow {
+            status: STATUS_SNOOZED.to_string(),
+            snoozed_until: Some("2999-01-01T00:00:00Z".to_string()),
+            ..AlertRow::default()
+        };
+        assert!(alert_matches_status(&alert, ""));
+        assert!(alert_matches_status(&alert, "snoozed"));
+        assert!(!alert_matches_status(&alert, "active"));
+        alert.snoozed_until = Some("2000-01-01T00:00:00Z".to_string());
+        assert!(alert_matches_status(&alert, "active"));
+    }
+}
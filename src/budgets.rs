@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use time::format_description::well_known::Rfc3339;
+use time::{Date, Duration, Month, OffsetDateTime, Time};
+
+use crate::alerts::raise_alert;
+use crate::types::{AlertRow, HourBucket, State};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetScope {
+    Global,
+    Project,
+    Model,
+}
+
+/// Budget periods follow UTC calendar boundaries; weeks start on Monday.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetPeriod {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// One entry of `budgets` in `monitor.json`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetConfig {
+    pub id: String,
+    pub scope: BudgetScope,
+    /// Project name for `project` budgets; for `model` budgets, every model
+    /// whose name contains it counts.
+    #[serde(default)]
+    pub name: String,
+    pub period: BudgetPeriod,
+    pub cap_usd: f64,
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: f64,
+}
+
+fn default_warn_percent() -> f64 {
+    80.0
+}
+
+/// Rejects budgets that could never be evaluated sensibly.
+pub fn validate_budgets(budgets: &[BudgetConfig]) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for budget in budgets {
+        if budget.id.trim().is_empty() {
+            return Err("budget id must not be empty".to_string());
+        }
+        if !ids.insert(budget.id.as_str()) {
+            return Err(format!("duplicate budget id {:?}", budget.id));
+        }
+        if budget.scope != BudgetScope::Global && budget.name.trim().is_empty() {
+            return Err(format!(
+                "budget {:?}: name is required for project and model budgets",
+                budget.id
+            ));
+        }
+        if !budget.cap_usd.is_finite() || budget.cap_usd <= 0.0 {
+            return Err(format!("budget {:?}: capUsd must be > 0", budget.id));
+        }
+        if !(budget.warn_percent > 0.0 && budget.warn_percent <= 100.0) {
+            return Err(format!(
+                "budget {:?}: warnPercent must be in (0, 100]",
+                budget.id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Start (inclusive) and end (exclusive) of the period containing `now`.
+pub fn period_bounds(
+    period: BudgetPeriod,
+    now: OffsetDateTime,
+) -> (OffsetDateTime, OffsetDateTime) {
+    let today = now.to_offset(time::UtcOffset::UTC).date();
+    let (start, end) = match period {
+        BudgetPeriod::Daily => (today, today + Duration::days(1)),
+        BudgetPeriod::Weekly => {
+            let start = today - Duration::days(today.weekday().number_days_from_monday() as i64);
+            (start, start + Duration::days(7))
+        }
+        BudgetPeriod::Monthly => {
+            let start = today.replace_day(1).unwrap_or(today);
+            let (year, month) = match today.month() {
+                Month::December => (today.year() + 1, Month::January),
+                month => (today.year(), month.next()),
+            };
+            let end =
+                Date::from_calendar_date(year, month, 1).unwrap_or(start + Duration::days(31));
+            (start, end)
+        }
+    };
+    (
+        start.with_time(Time::MIDNIGHT).assume_utc(),
+        end.with_time(Time::MIDNIGHT).assume_utc(),
+    )
+}
+
+/// Hour key (`YYYY-MM-DDTHH`) used by the hourly buckets.
+pub(crate) fn hour_key(at: OffsetDateTime) -> String {
+    at.format(&Rfc3339).unwrap_or_default()[..13].to_string()
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub id: String,
+    pub scope: BudgetScope,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub period: BudgetPeriod,
+    pub period_start: String,
+    pub period_end: String,
+    pub cap_usd: f64,
+    pub warn_percent: f64,
+    pub spent_usd: f64,
+    pub remaining_usd: f64,
+    pub percent_used: f64,
+    /// Spend at the end of the period if the pace so far continues.
+    pub projected_usd: f64,
+    /// `ok`, `warning` or `exceeded`.
+    pub status: String,
+}
+
+/// Cost of the buckets in `[start_key, end_key)`.
+pub(crate) fn spend_between(buckets: &[HourBucket], start_key: &str, end_key: &str) -> f64 {
+    buckets
+        .iter()
+        .filter(|b| b.hour_key.as_str() >= start_key && b.hour_key.as_str() < end_key)
+        .map(|b| b.cost_usd)
+        .sum()
+}
+
+fn budget_spend(state: &State, budget: &BudgetConfig, start_key: &str, end_key: &str) -> f64 {
+    match budget.scope {
+        BudgetScope::Global => spend_between(&state.hourly_buckets, start_key, end_key),
+        BudgetScope::Project => state
+            .project_hourly
+            .get(&budget.name)
+            .map(|buckets| spend_between(buckets, start_key, end_key))
+            .unwrap_or(0.0),
+        BudgetScope::Model => state
+            .model_hourly
+            .iter()
+            .filter(|(model, _)| model.contains(&budget.name))
+            .map(|(_, buckets)| spend_between(buckets, start_key, end_key))
+            .sum(),
+    }
+}
+
+pub fn budget_status(state: &State, budget: &BudgetConfig, now: OffsetDateTime) -> BudgetStatus {
+    let (start, end) = period_bounds(budget.period, now);
+    let spent = budget_spend(state, budget, &hour_key(start), &hour_key(end));
+    let elapsed_hours = ((now - start).as_seconds_f64() / 3600.0).max(1.0);
+    let total_hours = (end - start).as_seconds_f64() / 3600.0;
+    let projected = (spent / elapsed_hours * total_hours).max(spent);
+    let percent_used = spent / budget.cap_usd * 100.0;
+    let status = if spent >= budget.cap_usd {
+        "exceeded"
+    } else if percent_used >= budget.warn_percent {
+        "warning"
+    } else {
+        "ok"
+    };
+    BudgetStatus {
+        id: budget.id.clone(),
+        scope: budget.scope,
+        name: budget.name.clone(),
+        period: budget.period,
+        period_start: start.format(&Rfc3339).unwrap_or_default(),
+        period_end: end.format(&Rfc3339).unwrap_or_default(),
+        cap_usd: budget.cap_usd,
+        warn_percent: budget.warn_percent,
+        spent_usd: spent,
+        remaining_usd: (budget.cap_usd - spent).max(0.0),
+        percent_used,
+        projected_usd: projected,
+        status: status.to_string(),
+    }
+}
+
+fn budget_label(budget: &BudgetConfig) -> String {
+    match budget.scope {
+        BudgetScope::Global => format!("{:?} budget {}", budget.period, budget.id),
+        BudgetScope::Project => format!(
+            "{:?} budget {} (project {})",
+            budget.period, budget.id, budget.name
+        ),
+        BudgetScope::Model => format!(
+            "{:?} budget {} (model {})",
+            budget.period, budget.id, budget.name
+        ),
+    }
+}
+
+/// Raises one alert per budget and period when spend first reaches the warn
+/// threshold and another when it reaches the cap. Returns the alerts raised.
+pub fn evaluate_budgets(
+    state: &mut State,
+    budgets: &[BudgetConfig],
+    now: OffsetDateTime,
+    mut new_id: impl FnMut() -> String,
+) -> Vec<AlertRow> {
+    let now_iso = now.format(&Rfc3339).unwrap_or_default();
+    let mut raised = Vec::new();
+    let mut current_periods = HashSet::new();
+    for budget in budgets {
+        let status = budget_status(state, budget, now);
+        let period_key = &status.period_start[..13];
+        current_periods.insert(format!("budget:{}|{}|", budget.id, period_key));
+        let mut crossings = Vec::new();
+        if status.percent_used >= budget.warn_percent {
+            crossings.push(("warn", "budget_warning", "warning"));
+        }
+        if status.spent_usd >= budget.cap_usd {
+            crossings.push(("cap", "budget_exceeded", "error"));
+        }
+        for (kind, event, severity) in crossings {
+            let fingerprint = format!("budget:{}|{}|{}", budget.id, period_key, kind);
+            if !state.budgets_fired.insert(fingerprint.clone()) {
+                continue;
+            }
+            let message = format!(
+                "{}: ${:.2} of ${:.2} spent ({:.0}%), projected ${:.2}",
+                budget_label(budget),
+                status.spent_usd,
+                budget.cap_usd,
+                status.percent_used,
+                status.projected_usd
+            );
+            let draft = AlertRow {
+                severity: severity.to_string(),
+                event: event.to_string(),
+                message: message.chars().take(160).collect(),
+                created_at: now_iso.clone(),
+                fingerprint,
+                rule_id: Some(format!("budget:{}", budget.id)),
+                ..AlertRow::default()
+            };
+            raised.push(raise_alert(state, draft, &mut new_id));
+        }
+    }
+    state.budgets_fired.retain(|fingerprint| {
+        fingerprint
+            .rfind('|')
+            .is_some_and(|end| current_periods.contains(&fingerprint[..=end]))
+    });
+    raised
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TokenBreakdown;
+    use serde_json::json;
+
+    fn at(iso: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(iso, &Rfc3339).unwrap()
+    }
+
+    fn budget(value: serde_json::Value) -> BudgetConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn bucket(hour_key: &str, cost_usd: f64) -> HourBucket {
+        HourBucket {
+            hour_key: hour_key.to_string(),
+            token_total: 0,
+            cost_usd,
+            usage: TokenBreakdown::default(),
+        }
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let now = at("2025-01-15T10:30:00Z");
+        let (start, end) = period_bounds(BudgetPeriod::Daily, now);
+        assert_eq!(start, at("2025-01-15T00:00:00Z"));
+        assert_eq!(end, at("2025-01-16T00:00:00Z"));
+        // 2025-01-15 is a Wednesday.
+        let (start, end) = period_bounds(BudgetPeriod::Weekly, now);
+        assert_eq!(start, at("2025-01-13T00:00:00Z"));
+        assert_eq!(end, at("2025-01-20T00:00:00Z"));
+        let (start, end) = period_bounds(BudgetPeriod::Monthly, at("2025-12-31T23:00:00Z"));
+        assert_eq!(start, at("2025-12-01T00:00:00Z"));
+        assert_eq!(end, at("2026-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_validate_budgets() {
+        let ok = budget(json!({ "id": "g", "scope": "global", "period": "daily", "capUsd": 10.0 }));
+        assert_eq!(ok.warn_percent, 80.0);
+        assert!(validate_budgets(std::slice::from_ref(&ok)).is_ok());
+        assert!(validate_budgets(&[ok.clone(), ok]).is_err());
+        let unnamed =
+            budget(json!({ "id": "p", "scope": "project", "period": "daily", "capUsd": 10.0 }));
+        assert!(validate_budgets(&[unnamed]).is_err());
+        let zero =
+            budget(json!({ "id": "z", "scope": "global", "period": "daily", "capUsd": 0.0 }));
+        assert!(validate_budgets(&[zero]).is_err());
+    }
+
+    #[test]
+    fn test_budget_status_spend_and_projection() {
+        let mut state = State::default();
+        state.project_hourly.insert(
+            "web".to_string(),
+            vec![
+                bucket("2025-01-14T23", 5.0),
+                bucket("2025-01-15T01", 2.0),
+                bucket("2025-01-15T05", 1.0),
+            ],
+        );
+        let daily = budget(json!({
+            "id": "web-daily", "scope": "project", "name": "web", "period": "daily", "capUsd": 10.0
+        }));
+        let status = budget_status(&state, &daily, at("2025-01-15T06:00:00Z"));
+        assert!((status.spent_usd - 3.0).abs() < 1e-9);
+        assert!((status.remaining_usd - 7.0).abs() < 1e-9);
+        // $3 in 6 hours -> $12 over 24 hours.
+        assert!((status.projected_usd - 12.0).abs() < 1e-9);
+        assert_eq!(status.status, "ok");
+    }
+
+    #[test]
+    fn test_budget_model_scope_matches_substring() {
+        let mut state = State::default();
+        state.model_hourly.insert(
+            "claude-opus-4".to_string(),
+            vec![bucket("2025-01-15T01", 2.0)],
+        );
+        state.model_hourly.insert(
+            "claude-opus-4-1".to_string(),
+            vec![bucket("2025-01-15T02", 1.0)],
+        );
+        state.model_hourly.insert(
+            "claude-sonnet-4".to_string(),
+            vec![bucket("2025-01-15T02", 4.0)],
+        );
+        let opus = budget(json!({
+            "id": "opus", "scope": "model", "name": "opus", "period": "daily", "capUsd": 10.0
+        }));
+        let status = budget_status(&state, &opus, at("2025-01-15T12:00:00Z"));
+        assert!((status.spent_usd - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_evaluate_budgets_alerts_once_per_threshold_and_period() {
+        let mut state = State {
+            hourly_buckets: vec![bucket("2025-01-15T01", 8.5)],
+            ..State::default()
+        };
+        let budgets = vec![budget(
+            json!({ "id": "g", "scope": "global", "period": "daily", "capUsd": 10.0 }),
+        )];
+        let mut seq = 0;
+        let mut next = || {
+            seq += 1;
+            format!("a{}", seq)
+        };
+
+        let raised = evaluate_budgets(&mut state, &budgets, at("2025-01-15T12:00:00Z"), &mut next);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].event, "budget_warning");
+        assert_eq!(raised[0].rule_id.as_deref(), Some("budget:g"));
+        assert!(
+            evaluate_budgets(&mut state, &budgets, at("2025-01-15T12:00:10Z"), &mut next)
+                .is_empty()
+        );
+        // Still quiet once the alert has been evicted from the alert list.
+        state.alerts.clear();
+        assert!(
+            evaluate_budgets(&mut state, &budgets, at("2025-01-15T12:00:20Z"), &mut next)
+                .is_empty()
+        );
+
+        state.hourly_buckets[0].cost_usd = 11.0;
+        let raised = evaluate_budgets(&mut state, &budgets, at("2025-01-15T13:00:00Z"), &mut next);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].event, "budget_exceeded");
+        assert_eq!(raised[0].severity, "error");
+
+        // A new day starts a new period.
+        state.hourly_buckets.push(bucket("2025-01-16T01", 9.0));
+        let raised = evaluate_budgets(&mut state, &budgets, at("2025-01-16T02:00:00Z"), &mut next);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].event, "budget_warning");
+        assert_eq!(
+            state.budgets_fired,
+            HashSet::from(["budget:g|2025-01-16T00|warn".to_string()])
+        );
+    }
+}
@@ -5,17 +5,33 @@ use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+use crate::db::DbWrite;
 use crate::state::append_event;
-use crate::types::{App, Event};
+use crate::types::{App, Event, FileCursor, PendingToolCall, ScanStats};
 use crate::utils::now_iso;
+use crate::watcher::FsWatcher;
+
+const MAX_PENDING_TOOL_CALLS: usize = 2_000;
+const WATCH_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+#[cfg(unix)]
+fn file_inode(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn file_inode(_meta: &std::fs::Metadata) -> u64 {
+    0
+}
 
 pub fn read_delta_lines(
     file_path: &Path,
-    cursor: &mut (u64, String),
+    cursor: &mut FileCursor,
     max_read_bytes: u64,
 ) -> Vec<String> {
     let meta = match metadata(file_path) {
@@ -23,17 +39,22 @@ pub fn read_delta_lines(
         Err(_) => return vec![],
     };
 
-    if meta.len() < cursor.0 {
-        cursor.0 = 0;
-        cursor.1.clear();
+    // a different inode means the file was replaced (e.g. rewritten via rename)
+    let inode = file_inode(&meta);
+    let replaced = cursor.inode != 0 && inode != 0 && cursor.inode != inode;
+    if replaced || meta.len() < cursor.offset {
+        cursor.offset = 0;
+        cursor.remainder.clear();
     }
+    cursor.inode = inode;
+    cursor.size = meta.len();
 
-    if meta.len() == cursor.0 {
+    if meta.len() == cursor.offset {
         return vec![];
     }
 
-    let mut start = cursor.0;
-    if meta.len().saturating_sub(cursor.0) > max_read_bytes {
+    let mut start = cursor.offset;
+    if meta.len().saturating_sub(cursor.offset) > max_read_bytes {
         start = meta.len().saturating_sub(max_read_bytes);
     }
 
@@ -51,14 +72,38 @@ pub fn read_delta_lines(
         return vec![];
     }
 
-    cursor.0 = meta.len();
+    cursor.offset = meta.len();
 
-    let text = format!("{}{}", cursor.1, String::from_utf8_lossy(&bytes));
+    let text = format!("{}{}", cursor.remainder, String::from_utf8_lossy(&bytes));
     let mut lines: Vec<String> = text.split('\n').map(|s| s.to_string()).collect();
-    cursor.1 = lines.pop().unwrap_or_default();
+    cursor.remainder = lines.pop().unwrap_or_default();
     lines.into_iter().filter(|s| !s.is_empty()).collect()
 }
 
+/// Writes the writer has dropped so far, taken before reading a file.
+fn dropped_writes(app: &App) -> u64 {
+    app.db_writer.as_ref().map_or(0, |writer| writer.dropped())
+}
+
+/// Queues the cursor for persistence. Sent after the events read with it, so
+/// the writer never commits a cursor ahead of the data it covers. If any write
+/// was dropped since `dropped_before` the cursor is held back for the rest of
+/// the run instead, and a restart resumes from the last cursor that was safe.
+fn persist_cursor(app: &App, path: &Path, cursor: &mut FileCursor, dropped_before: u64) {
+    let Some(writer) = &app.db_writer else {
+        return;
+    };
+    if writer.dropped() != dropped_before {
+        cursor.held_back = true;
+    }
+    if !cursor.held_back {
+        writer.send(DbWrite::Cursor {
+            path: path.to_path_buf(),
+            cursor: cursor.clone(),
+        });
+    }
+}
+
 pub fn parse_history_event(line: &str, app: &App) -> Option<Event> {
     let v: Value = serde_json::from_str(line).ok()?;
     let text = v
@@ -105,6 +150,7 @@ pub fn parse_history_event(line: &str, app: &App) -> Option<Event> {
         is_sidechain: false,
         session_id: String::new(),
         cwd: String::new(),
+        dedup_key: None,
     })
 }
 
@@ -142,6 +188,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
         .and_then(|c| c.as_str())
         .unwrap_or("")
         .to_string();
+    let dedup_key = |item: &str| record_dedup_key(&v, item);
 
     match msg_type {
         "user" => {
@@ -167,11 +214,13 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                 _ => String::new(),
             };
 
+            let mut events = tool_result_events(content_val, app, &v, &agent_id, &cwd);
+
             if content.is_empty() {
-                return vec![];
+                return events;
             }
 
-            vec![Event {
+            events.push(Event {
                 id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
                 agent_id: agent_id.clone(),
                 event: "user_message".to_string(),
@@ -189,7 +238,9 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                 is_sidechain,
                 session_id: session_id.clone(),
                 cwd: cwd.clone(),
-            }]
+                dedup_key: dedup_key("message"),
+            });
+            events
         }
         "assistant" => {
             let mut events = Vec::new();
@@ -201,7 +252,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                 .to_string();
 
             if let Some(content_arr) = message.get("content").and_then(|c| c.as_array()) {
-                for item in content_arr {
+                for (index, item) in content_arr.iter().enumerate() {
                     let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
                     match item_type {
                         "text" => {
@@ -229,6 +280,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                                     is_sidechain,
                                     session_id: session_id.clone(),
                                     cwd: cwd.clone(),
+                                    dedup_key: dedup_key(&index.to_string()),
                                 });
                             }
                         }
@@ -238,6 +290,20 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                                 .and_then(|n| n.as_str())
                                 .unwrap_or("unknown_tool");
                             let input = item.get("input").cloned().unwrap_or(json!({}));
+                            let tool_use_id = item.get("id").and_then(|i| i.as_str()).unwrap_or("");
+                            if !tool_use_id.is_empty() {
+                                track_pending_tool_call(
+                                    app,
+                                    tool_use_id,
+                                    PendingToolCall {
+                                        tool_name: name.to_string(),
+                                        agent_id: agent_id.clone(),
+                                        session_id: session_id.clone(),
+                                        started_at: timestamp.clone(),
+                                        subagent_type: task_subagent_type(name, &input),
+                                    },
+                                );
+                            }
                             events.push(Event {
                                 id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
                                 agent_id: agent_id.clone(),
@@ -250,6 +316,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                                     "sessionId": session_id,
                                     "model": model,
                                     "isSidechain": is_sidechain,
+                                    "toolUseId": tool_use_id,
                                     "toolInput": input,
                                 }),
                                 timestamp: timestamp.clone(),
@@ -258,6 +325,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                                 is_sidechain,
                                 session_id: session_id.clone(),
                                 cwd: cwd.clone(),
+                                dedup_key: dedup_key(&index.to_string()),
                             });
                         }
                         _ => {}
@@ -278,9 +346,22 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                     .get("cache_read_input_tokens")
                     .and_then(|v| v.as_u64())
                     .unwrap_or(0);
+                let cache_creation = usage
+                    .get("cache_creation_input_tokens")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let server_tool_use = usage.get("server_tool_use");
+                let web_search_requests = server_tool_use
+                    .and_then(|s| s.get("web_search_requests"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let web_fetch_requests = server_tool_use
+                    .and_then(|s| s.get("web_fetch_requests"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
                 let total = input_tokens + output_tokens;
 
-                if total > 0 {
+                if total > 0 || cache_read > 0 || cache_creation > 0 {
                     events.push(Event {
                         id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
                         agent_id: agent_id.clone(),
@@ -297,6 +378,9 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                                 "inputTokens": input_tokens,
                                 "outputTokens": output_tokens,
                                 "cacheReadInputTokens": cache_read,
+                                "cacheCreationInputTokens": cache_creation,
+                                "webSearchRequests": web_search_requests,
+                                "webFetchRequests": web_fetch_requests,
                                 "totalTokens": total,
                             }
                         }),
@@ -306,6 +390,7 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                         is_sidechain,
                         session_id: session_id.clone(),
                         cwd: cwd.clone(),
+                        dedup_key: dedup_key("usage"),
                     });
                 }
             }
@@ -338,66 +423,412 @@ pub fn parse_session_line(line: &str, app: &App) -> Vec<Event> {
                 is_sidechain,
                 session_id: session_id.clone(),
                 cwd: cwd.clone(),
+                dedup_key: dedup_key("activity"),
             }]
         }
         _ => vec![],
     }
 }
 
-fn walk_jsonl_recursive(dir: &Path, result: &mut Vec<PathBuf>) {
+/// `<uuid>#<item>` for session lines that carry a `uuid`; `item` tells apart
+/// the several events one line can produce (content index, `usage`, ...).
+fn record_dedup_key(line: &Value, item: &str) -> Option<String> {
+    line.get("uuid")
+        .and_then(|u| u.as_str())
+        .filter(|u| !u.is_empty())
+        .map(|uuid| format!("{}#{}", uuid, item))
+}
+
+pub fn track_pending_tool_call(app: &App, tool_use_id: &str, call: PendingToolCall) {
+    let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+    state
+        .pending_tool_calls
+        .insert(tool_use_id.to_string(), call);
+    if state.pending_tool_calls.len() > MAX_PENDING_TOOL_CALLS {
+        // Calls whose result never arrived (e.g. interrupted sessions) must not pile up forever.
+        if let Some(oldest) = state
+            .pending_tool_calls
+            .iter()
+            .min_by(|a, b| a.1.started_at.cmp(&b.1.started_at))
+            .map(|(k, _)| k.clone())
+        {
+            state.pending_tool_calls.remove(&oldest);
+        }
+    }
+}
+
+/// The `subagent_type` a Task (or, in newer versions, Agent) call starts.
+fn task_subagent_type(tool_name: &str, input: &Value) -> String {
+    if tool_name != "Task" && tool_name != "Agent" {
+        return String::new();
+    }
+    input
+        .get("subagent_type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+pub fn take_pending_tool_call(app: &App, tool_use_id: &str) -> Option<PendingToolCall> {
+    let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_tool_calls.remove(tool_use_id)
+}
+
+pub fn millis_between(start: &str, end: &str) -> Option<i64> {
+    let start = OffsetDateTime::parse(start, &Rfc3339).ok()?;
+    let end = OffsetDateTime::parse(end, &Rfc3339).ok()?;
+    let millis = (end - start).whole_milliseconds();
+    Some(millis.clamp(0, i64::MAX as i128) as i64)
+}
+
+fn tool_result_events(
+    content_val: Option<&Value>,
+    app: &App,
+    line: &Value,
+    agent_id: &str,
+    cwd: &str,
+) -> Vec<Event> {
+    let Some(Value::Array(items)) = content_val else {
+        return vec![];
+    };
+    let session_id = line.get("sessionId").and_then(|s| s.as_str()).unwrap_or("");
+    let is_sidechain = line
+        .get("isSidechain")
+        .and_then(|b| b.as_bool())
+        .unwrap_or(false);
+    let timestamp = line
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(now_iso);
+
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+        .filter_map(|(index, item)| {
+            let tool_use_id = item.get("tool_use_id").and_then(|i| i.as_str())?;
+            let is_error = item
+                .get("is_error")
+                .and_then(|b| b.as_bool())
+                .unwrap_or(false);
+            let pending = take_pending_tool_call(app, tool_use_id);
+            let latency_ms = pending
+                .as_ref()
+                .and_then(|call| millis_between(&call.started_at, &timestamp));
+            let tool_name = pending
+                .as_ref()
+                .map(|call| call.tool_name.clone())
+                .unwrap_or_else(|| "unknown_tool".to_string());
+            // Results are written on a `user` line, so attribute them to the agent that made the call.
+            let agent_id = pending
+                .as_ref()
+                .map(|call| call.agent_id.clone())
+                .unwrap_or_else(|| agent_id.to_string());
+            let session_id = pending
+                .as_ref()
+                .map(|call| call.session_id.as_str())
+                .filter(|id| session_id.is_empty() && !id.is_empty())
+                .unwrap_or(session_id)
+                .to_string();
+            let mut metadata = json!({
+                "source": "claude_session",
+                "sessionId": session_id,
+                "isSidechain": is_sidechain,
+                "toolUseId": tool_use_id,
+                "toolName": tool_name,
+                "isError": is_error,
+            });
+            // A Task result names the subagent that ran; tag it with its type.
+            let subagent_id = line
+                .get("toolUseResult")
+                .and_then(|r| r.get("agentId"))
+                .and_then(|a| a.as_str());
+            if let (Some(subagent_id), Some(call)) = (subagent_id, pending.as_ref()) {
+                if !call.subagent_type.is_empty() {
+                    metadata["subagentId"] = json!(subagent_id);
+                    metadata["subagentType"] = json!(call.subagent_type);
+                }
+            }
+
+            Some(Event {
+                id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
+                agent_id,
+                event: "tool_result".to_string(),
+                status: if is_error { "error" } else { "ok" }.to_string(),
+                latency_ms,
+                message: tool_name,
+                metadata,
+                timestamp: timestamp.clone(),
+                received_at: now_iso(),
+                model: String::new(),
+                is_sidechain,
+                session_id,
+                cwd: cwd.to_string(),
+                dedup_key: record_dedup_key(line, &index.to_string()),
+            })
+        })
+        .collect()
+}
+
+struct DirListing {
+    mtime: SystemTime,
+    files: Vec<PathBuf>,
+    subdirs: Vec<PathBuf>,
+}
+
+struct FileStamp {
+    mtime: Option<SystemTime>,
+    len: u64,
+    checked_at: Instant,
+}
+
+/// Remembers directory listings and file stamps between scans. A directory
+/// is only re-listed when its mtime changes, and session files not modified
+/// for `dormant_after` are only re-checked every `dormant_recheck`.
+pub struct ScanIndex {
+    dirs: HashMap<PathBuf, DirListing>,
+    files: HashMap<PathBuf, FileStamp>,
+    dormant_after: Duration,
+    dormant_recheck: Duration,
+    pub last_scan: ScanStats,
+}
+
+impl Default for ScanIndex {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(24 * 60 * 60),
+            Duration::from_secs(5 * 60),
+        )
+    }
+}
+
+impl ScanIndex {
+    pub fn new(dormant_after: Duration, dormant_recheck: Duration) -> Self {
+        Self {
+            dirs: HashMap::new(),
+            files: HashMap::new(),
+            dormant_after,
+            dormant_recheck,
+            last_scan: ScanStats::default(),
+        }
+    }
+
+    fn scan_dir(
+        &mut self,
+        dir: &Path,
+        top_level: bool,
+        out: &mut Vec<PathBuf>,
+        stats: &mut ScanStats,
+    ) {
+        let Some(mtime) = metadata(dir).and_then(|m| m.modified()).ok() else {
+            self.forget(dir);
+            return;
+        };
+        let (files, subdirs) = match self.dirs.get(dir) {
+            Some(listing) if listing.mtime == mtime => {
+                stats.dirs_skipped += 1;
+                (listing.files.clone(), listing.subdirs.clone())
+            }
+            _ => {
+                stats.dirs_listed += 1;
+                let (files, subdirs) = list_dir(dir, top_level);
+                if let Some(old) = self.dirs.remove(dir) {
+                    for gone in old.subdirs.iter().filter(|d| !subdirs.contains(d)) {
+                        self.forget(gone);
+                    }
+                    for gone in old.files.iter().filter(|f| !files.contains(f)) {
+                        self.files.remove(gone);
+                    }
+                }
+                self.dirs.insert(
+                    dir.to_path_buf(),
+                    DirListing {
+                        mtime,
+                        files: files.clone(),
+                        subdirs: subdirs.clone(),
+                    },
+                );
+                (files, subdirs)
+            }
+        };
+        for sub in &subdirs {
+            self.scan_dir(sub, false, out, stats);
+        }
+        for file in files {
+            self.check_file(file, out, stats);
+        }
+    }
+
+    fn check_file(&mut self, file: PathBuf, out: &mut Vec<PathBuf>, stats: &mut ScanStats) {
+        if let Some(stamp) = self.files.get(&file) {
+            let dormant = stamp
+                .mtime
+                .and_then(|m| m.elapsed().ok())
+                .is_some_and(|age| age >= self.dormant_after);
+            if dormant && stamp.checked_at.elapsed() < self.dormant_recheck {
+                stats.dormant_skipped += 1;
+                return;
+            }
+        }
+        stats.files_checked += 1;
+        let Ok(meta) = metadata(&file) else {
+            self.files.remove(&file);
+            return;
+        };
+        let mtime = meta.modified().ok();
+        let changed = self
+            .files
+            .get(&file)
+            .is_none_or(|stamp| stamp.mtime != mtime || stamp.len != meta.len());
+        self.files.insert(
+            file.clone(),
+            FileStamp {
+                mtime,
+                len: meta.len(),
+                checked_at: Instant::now(),
+            },
+        );
+        if changed {
+            out.push(file);
+        }
+    }
+
+    fn forget(&mut self, dir: &Path) {
+        self.dirs.retain(|path, _| !path.starts_with(dir));
+        self.files.retain(|path, _| !path.starts_with(dir));
+    }
+}
+
+/// `.jsonl` files and subdirectories of `dir`. Files directly in the
+/// projects root are not sessions, and symlinks below it are skipped to
+/// avoid loops.
+fn list_dir(dir: &Path, top_level: bool) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
     let Ok(entries) = std::fs::read_dir(dir) else {
-        return;
+        return (files, subdirs);
     };
     for entry in entries.flatten() {
         let path = entry.path();
+        if top_level {
+            if path.is_dir() {
+                subdirs.push(path);
+            }
+            continue;
+        }
         if path.is_symlink() {
             continue;
         }
         if path.is_dir() {
-            walk_jsonl_recursive(&path, result);
+            subdirs.push(path);
         } else if path.is_file() && path.extension().map(|e| e == "jsonl").unwrap_or(false) {
-            result.push(path);
+            files.push(path);
         }
     }
+    (files, subdirs)
 }
 
-pub fn walk_jsonl_files(dir: &Path) -> Vec<PathBuf> {
+/// Session files under `dir` that are new or changed since the previous
+/// walk with the same `index`. A fresh index returns every session file.
+pub fn walk_jsonl_files(dir: &Path, index: &mut ScanIndex) -> Vec<PathBuf> {
+    let started = Instant::now();
+    let mut stats = ScanStats::default();
     let mut result = Vec::new();
-    let Ok(entries) = std::fs::read_dir(dir) else {
-        return result;
-    };
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            walk_jsonl_recursive(&path, &mut result);
-        }
-    }
+    index.scan_dir(dir, true, &mut result, &mut stats);
+    stats.files_changed = result.len() as u64;
+    stats.scan_ms = started.elapsed().as_millis() as u64;
+    stats.scanned_at = now_iso();
+    index.last_scan = stats;
     result
 }
 
 pub fn poll_session_files(
     projects_dir: &Path,
     app: &App,
-    cursors: &mut HashMap<PathBuf, (u64, String)>,
+    cursors: &mut HashMap<PathBuf, FileCursor>,
+    index: &mut ScanIndex,
 ) {
-    let files = walk_jsonl_files(projects_dir);
+    let files = walk_jsonl_files(projects_dir, index);
     for file in files {
-        let cursor = cursors.entry(file.clone()).or_insert((0, String::new()));
-        let lines = read_delta_lines(&file, cursor, 512 * 1024);
-        for line in lines {
-            let events = parse_session_line(&line, app);
-            for evt in events {
-                append_event(app, evt);
-            }
+        poll_session_file(&file, app, cursors);
+    }
+    let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+    state.collector_scan = index.last_scan.clone();
+}
+
+pub fn poll_session_file(file: &Path, app: &App, cursors: &mut HashMap<PathBuf, FileCursor>) {
+    let cursor = cursors.entry(file.to_path_buf()).or_default();
+    let before = cursor.clone();
+    let dropped = dropped_writes(app);
+    let lines = read_delta_lines(file, cursor, 512 * 1024);
+    for line in lines {
+        let events = parse_session_line(&line, app);
+        for evt in events {
+            append_event(app, evt);
+        }
+    }
+    if *cursor != before {
+        persist_cursor(app, file, cursor, dropped);
+    }
+}
+
+/// Same selection rule as `walk_jsonl_files`: `.jsonl` files below a project directory.
+fn is_session_file(projects_dir: &Path, path: &Path) -> bool {
+    path.extension().map(|e| e == "jsonl").unwrap_or(false)
+        && path.starts_with(projects_dir)
+        && path.parent() != Some(projects_dir)
+        && !path.is_symlink()
+}
+
+fn poll_history(history: &Path, app: &App, cursor: &mut FileCursor) {
+    let before = cursor.clone();
+    let dropped = dropped_writes(app);
+    for line in read_delta_lines(history, cursor, 512 * 1024) {
+        if let Some(evt) = parse_history_event(&line, app) {
+            append_event(app, evt);
+        }
+    }
+    if *cursor != before {
+        persist_cursor(app, history, cursor, dropped);
+    }
+}
+
+fn start_watcher(claude_home: &Path, projects_dir: &Path) -> Option<FsWatcher> {
+    let mut watcher = FsWatcher::new()
+        .and_then(|mut w| {
+            w.watch(claude_home, false)?;
+            Ok(w)
+        })
+        .map_err(|e| eprintln!("[collector] file watching unavailable: {e} — polling only"))
+        .ok()?;
+    if projects_dir.is_dir() {
+        if let Err(e) = watcher.watch(projects_dir, true) {
+            eprintln!("[collector] cannot watch {}: {e}", projects_dir.display());
         }
     }
+    println!("[collector] watching {} for changes", claude_home.display());
+    Some(watcher)
+}
+
+/// `costUSD` of every model in a `stats-cache.json` document.
+fn stats_model_costs(v: &Value) -> HashMap<String, f64> {
+    v.get("modelUsage")
+        .and_then(|u| u.as_object())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|(model, m)| Some((model.clone(), m.get("costUSD")?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 pub fn poll_stats_cache(
     path: &Path,
     app: &App,
     last_mtime: &mut Option<SystemTime>,
-    last_cost: &mut f64,
+    last_costs: &mut HashMap<String, f64>,
 ) -> Option<Event> {
     let meta = metadata(path).ok()?;
     let mtime = meta.modified().ok()?;
@@ -410,32 +841,31 @@ pub fn poll_stats_cache(
     let content = std::fs::read_to_string(path).ok()?;
     let v: Value = serde_json::from_str(&content).ok()?;
 
-    let total_cost = v
-        .get("modelUsage")
-        .and_then(|u| u.as_object())
-        .map(|models| {
-            models
-                .values()
-                .filter_map(|m| m.get("costUSD").and_then(|c| c.as_f64()))
-                .sum::<f64>()
-        })
-        .unwrap_or(0.0);
+    let costs = stats_model_costs(&v);
+    let total_cost: f64 = costs.values().sum();
 
     // is_first is true only when initialization failed (stats-cache.json absent at startup)
     let is_first = last_mtime.is_none();
     *last_mtime = Some(mtime);
 
     if is_first {
-        *last_cost = total_cost;
+        *last_costs = costs;
         return None;
     }
 
-    let delta = total_cost - *last_cost;
+    let delta = total_cost - last_costs.values().sum::<f64>();
     if delta <= 0.0 {
         return None;
     }
 
-    *last_cost = total_cost;
+    let model_deltas: serde_json::Map<String, Value> = costs
+        .iter()
+        .filter_map(|(model, cost)| {
+            let model_delta = cost - last_costs.get(model).copied().unwrap_or(0.0);
+            (model_delta > 0.0).then(|| (model.clone(), json!(model_delta)))
+        })
+        .collect();
+    *last_costs = costs;
 
     Some(Event {
         id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
@@ -448,6 +878,7 @@ pub fn poll_stats_cache(
             "source": "stats_cache",
             "costDelta": delta,
             "costTotalUsd": total_cost,
+            "modelCostDeltas": model_deltas,
         }),
         timestamp: now_iso(),
         received_at: now_iso(),
@@ -455,35 +886,54 @@ pub fn poll_stats_cache(
         is_sidechain: false,
         session_id: String::new(),
         cwd: String::new(),
+        dedup_key: None,
     })
 }
 
-pub fn spawn_claude_collector(app: App, claude_home: PathBuf, poll_ms: u64, backfill_lines: usize) {
+pub struct CollectorOptions {
+    pub poll_ms: u64,
+    pub backfill_lines: usize,
+    /// Use OS file notifications when available.
+    pub watch: bool,
+    pub dormant_after: Duration,
+    pub dormant_recheck: Duration,
+}
+
+/// `saved_cursors` are the positions persisted by a previous run. History and
+/// session files with a saved cursor resume where they left off; files without
+/// one keep the first-run behaviour (history backfill, sessions from the start).
+/// With `watch`, file notifications drive collection and the `poll_ms` full
+/// scan only runs when watching is unavailable.
+pub fn spawn_claude_collector(
+    app: App,
+    claude_home: PathBuf,
+    options: CollectorOptions,
+    mut saved_cursors: HashMap<PathBuf, FileCursor>,
+) {
+    let CollectorOptions {
+        poll_ms,
+        backfill_lines,
+        watch,
+        dormant_after,
+        dormant_recheck,
+    } = options;
     thread::spawn(move || {
         let history = claude_home.join("history.jsonl");
         let projects_dir = claude_home.join("projects");
         let stats_cache = claude_home.join("stats-cache.json");
-        let mut history_cursor = (0_u64, String::new());
-        let mut session_cursors: HashMap<PathBuf, (u64, String)> = HashMap::new();
+        saved_cursors.retain(|path, _| path.exists());
+        let saved_history_cursor = saved_cursors.remove(&history);
+        let resume_history = saved_history_cursor.is_some();
+        let mut history_cursor = saved_history_cursor.unwrap_or_default();
+        let mut session_cursors = saved_cursors;
+        let mut scan_index = ScanIndex::new(dormant_after, dormant_recheck);
         let mut stats_last_mtime: Option<SystemTime> = None;
-        let mut stats_last_cost: f64 = 0.0;
+        let mut stats_last_costs: HashMap<String, f64> = HashMap::new();
 
-        // initialize cost_total_usd from stats-cache.json (no event generated)
+        // baseline the stats-cache.json total so only later diffs produce events
         if let Ok(content) = std::fs::read_to_string(&stats_cache) {
             if let Ok(v) = serde_json::from_str::<Value>(&content) {
-                let initial: f64 = v
-                    .get("modelUsage")
-                    .and_then(|u| u.as_object())
-                    .map(|m| {
-                        m.values()
-                            .filter_map(|e| e.get("costUSD").and_then(|c| c.as_f64()))
-                            .sum()
-                    })
-                    .unwrap_or(0.0);
-                stats_last_cost = initial;
-                if let Ok(mut state) = app.state.lock() {
-                    state.cost_total_usd = initial;
-                }
+                stats_last_costs = stats_model_costs(&v);
                 if let Ok(meta) = metadata(&stats_cache) {
                     if let Ok(mtime) = meta.modified() {
                         stats_last_mtime = Some(mtime);
@@ -492,45 +942,97 @@ pub fn spawn_claude_collector(app: App, claude_home: PathBuf, poll_ms: u64, back
             }
         }
 
-        // initial backfill from history.jsonl
-        if let Ok(contents) = std::fs::read_to_string(&history) {
-            for line in contents
-                .lines()
-                .rev()
-                .take(backfill_lines)
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-            {
-                if let Some(evt) = parse_history_event(line, &app) {
-                    append_event(&app, evt);
+        // initial backfill from history.jsonl, unless a previous run left a cursor
+        if !resume_history {
+            let dropped = dropped_writes(&app);
+            if let Ok(contents) = std::fs::read_to_string(&history) {
+                for line in contents
+                    .lines()
+                    .rev()
+                    .take(backfill_lines)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                {
+                    if let Some(evt) = parse_history_event(line, &app) {
+                        append_event(&app, evt);
+                    }
                 }
             }
-        }
 
-        if let Ok(meta) = metadata(&history) {
-            history_cursor.0 = meta.len();
+            if let Ok(meta) = metadata(&history) {
+                history_cursor = FileCursor {
+                    offset: meta.len(),
+                    remainder: String::new(),
+                    inode: file_inode(&meta),
+                    size: meta.len(),
+                    held_back: false,
+                };
+                persist_cursor(&app, &history, &mut history_cursor, dropped);
+            }
         }
 
+        let mut watcher = if watch {
+            start_watcher(&claude_home, &projects_dir)
+        } else {
+            None
+        };
+        let mut last_full_scan: Option<Instant> = None;
+
         loop {
-            for line in read_delta_lines(&history, &mut history_cursor, 512 * 1024) {
-                if let Some(evt) = parse_history_event(&line, &app) {
+            // with a watcher, full scans only catch what notifications missed
+            let full_scan_due = watcher.is_none()
+                || last_full_scan.is_none_or(|at| at.elapsed() >= WATCH_RESCAN_INTERVAL);
+            if full_scan_due {
+                poll_history(&history, &app, &mut history_cursor);
+                poll_session_files(&projects_dir, &app, &mut session_cursors, &mut scan_index);
+                if let Some(evt) = poll_stats_cache(
+                    &stats_cache,
+                    &app,
+                    &mut stats_last_mtime,
+                    &mut stats_last_costs,
+                ) {
                     append_event(&app, evt);
                 }
+                if let Some(w) = watcher.as_mut() {
+                    if !w.is_watching(&projects_dir) && projects_dir.is_dir() {
+                        let _ = w.watch(&projects_dir, true);
+                    }
+                }
+                last_full_scan = Some(Instant::now());
             }
 
-            poll_session_files(&projects_dir, &app, &mut session_cursors);
-
-            if let Some(evt) = poll_stats_cache(
-                &stats_cache,
-                &app,
-                &mut stats_last_mtime,
-                &mut stats_last_cost,
-            ) {
-                append_event(&app, evt);
+            let Some(w) = watcher.as_mut() else {
+                thread::sleep(Duration::from_millis(poll_ms));
+                continue;
+            };
+            match w.wait(Duration::from_millis(poll_ms)) {
+                Ok(changes) => {
+                    if changes.overflowed {
+                        last_full_scan = None;
+                    }
+                    for path in changes.paths {
+                        if path == history {
+                            poll_history(&history, &app, &mut history_cursor);
+                        } else if path == stats_cache {
+                            if let Some(evt) = poll_stats_cache(
+                                &stats_cache,
+                                &app,
+                                &mut stats_last_mtime,
+                                &mut stats_last_costs,
+                            ) {
+                                append_event(&app, evt);
+                            }
+                        } else if is_session_file(&projects_dir, &path) {
+                            poll_session_file(&path, &app, &mut session_cursors);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("[collector] file watcher failed: {e} — falling back to polling");
+                    watcher = None;
+                }
             }
-
-            thread::sleep(Duration::from_millis(poll_ms));
         }
     });
 }
@@ -538,6 +1040,7 @@ pub fn spawn_claude_collector(app: App, claude_home: PathBuf, poll_ms: u64, back
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::MonitorConfig;
     use crate::types::State;
     use std::io::Write;
     use std::sync::atomic::AtomicU64;
@@ -549,7 +1052,10 @@ mod tests {
             sse_clients: Arc::new(Mutex::new(Vec::new())),
             event_seq: Arc::new(AtomicU64::new(1)),
             public_dir: Arc::new(PathBuf::from("public")),
-            db: None,
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
         }
     }
 
@@ -565,7 +1071,7 @@ mod tests {
         let dir = unique_tmp_dir("rdl_empty");
         let path = dir.join("empty.log");
         File::create(&path).unwrap();
-        let mut cursor = (0u64, String::new());
+        let mut cursor = FileCursor::default();
         let lines = read_delta_lines(&path, &mut cursor, 1024);
         assert!(lines.is_empty());
         let _ = std::fs::remove_dir_all(&dir);
@@ -580,7 +1086,7 @@ mod tests {
             writeln!(f, "line1").unwrap();
             writeln!(f, "line2").unwrap();
         }
-        let mut cursor = (0u64, String::new());
+        let mut cursor = FileCursor::default();
         let lines = read_delta_lines(&path, &mut cursor, 1024);
         assert_eq!(lines, vec!["line1", "line2"]);
 
@@ -610,9 +1116,9 @@ mod tests {
             writeln!(f, "aaaaaa").unwrap();
             writeln!(f, "bbbbbb").unwrap();
         }
-        let mut cursor = (0u64, String::new());
+        let mut cursor = FileCursor::default();
         read_delta_lines(&path, &mut cursor, 1024);
-        assert!(cursor.0 > 0);
+        assert!(cursor.offset > 0);
 
         // shrink file
         {
@@ -635,7 +1141,7 @@ mod tests {
             }
         }
         // read with very small max_read_bytes — only tail portion
-        let mut cursor = (0u64, String::new());
+        let mut cursor = FileCursor::default();
         let lines = read_delta_lines(&path, &mut cursor, 30);
         assert!(!lines.is_empty());
         assert!(lines.len() < 100);
@@ -645,7 +1151,7 @@ mod tests {
     #[test]
     fn test_read_delta_lines_nonexistent_file() {
         let path = std::env::temp_dir().join("ccm_nonexistent_file_xyz.log");
-        let mut cursor = (0u64, String::new());
+        let mut cursor = FileCursor::default();
         let lines = read_delta_lines(&path, &mut cursor, 1024);
         assert!(lines.is_empty());
     }
@@ -659,7 +1165,7 @@ mod tests {
         File::create(sub.join("other.json")).unwrap(); // should be ignored
         File::create(sub.join("log.jsonl")).unwrap();
 
-        let files = walk_jsonl_files(&dir);
+        let files = walk_jsonl_files(&dir, &mut ScanIndex::default());
         assert_eq!(files.len(), 2);
         assert!(files.iter().all(|p| p.extension().unwrap() == "jsonl"));
         let _ = std::fs::remove_dir_all(&dir);
@@ -669,7 +1175,7 @@ mod tests {
     fn test_walk_jsonl_files_ignores_top_level_files() {
         let dir = unique_tmp_dir("walk_top");
         File::create(dir.join("top.jsonl")).unwrap(); // top-level, should be ignored
-        let files = walk_jsonl_files(&dir);
+        let files = walk_jsonl_files(&dir, &mut ScanIndex::default());
         assert!(files.is_empty());
         let _ = std::fs::remove_dir_all(&dir);
     }
@@ -685,7 +1191,7 @@ mod tests {
         File::create(subagents.join("agent-def.jsonl")).unwrap();
         File::create(subagents.join("notes.txt")).unwrap(); // should be ignored
 
-        let files = walk_jsonl_files(&dir);
+        let files = walk_jsonl_files(&dir, &mut ScanIndex::default());
         assert_eq!(files.len(), 3); // lead + 2 sub-agents
         assert!(files.iter().all(|p| p.extension().unwrap() == "jsonl"));
         let sub_files: Vec<_> = files
@@ -698,7 +1204,10 @@ mod tests {
 
     #[test]
     fn test_walk_jsonl_files_nonexistent_dir() {
-        let files = walk_jsonl_files(&std::env::temp_dir().join("ccm_nonexistent_dir_xyz"));
+        let files = walk_jsonl_files(
+            &std::env::temp_dir().join("ccm_nonexistent_dir_xyz"),
+            &mut ScanIndex::default(),
+        );
         assert!(files.is_empty());
     }
 
@@ -785,6 +1294,50 @@ mod tests {
         assert!(events[1].message.contains("150"));
     }
 
+    #[test]
+    fn test_parse_session_line_usage_full_breakdown() {
+        let app = make_test_app();
+        let line = r#"{"type":"assistant","message":{"model":"claude-3","content":[],"usage":{"input_tokens":10,"output_tokens":5,"cache_read_input_tokens":300,"cache_creation_input_tokens":40,"server_tool_use":{"web_search_requests":2,"web_fetch_requests":1}}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        let events = parse_session_line(line, &app);
+        assert_eq!(events.len(), 1);
+        let usage = &events[0].metadata["tokenUsage"];
+        assert_eq!(usage["cacheReadInputTokens"], 300);
+        assert_eq!(usage["cacheCreationInputTokens"], 40);
+        assert_eq!(usage["webSearchRequests"], 2);
+        assert_eq!(usage["webFetchRequests"], 1);
+        assert_eq!(usage["totalTokens"], 15);
+    }
+
+    #[test]
+    fn test_parse_session_line_dedup_keys_from_uuid() {
+        let app = make_test_app();
+        let line = r#"{"type":"assistant","uuid":"u-1","message":{"model":"m","content":[{"type":"text","text":"hi"},{"type":"tool_use","id":"t1","name":"Bash","input":{}}],"usage":{"input_tokens":1,"output_tokens":1}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        let keys: Vec<Option<String>> = parse_session_line(line, &app)
+            .into_iter()
+            .map(|e| e.dedup_key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                Some("u-1#0".to_string()),
+                Some("u-1#1".to_string()),
+                Some("u-1#usage".to_string())
+            ]
+        );
+
+        let no_uuid = r#"{"type":"user","message":{"content":"hi"},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        assert!(parse_session_line(no_uuid, &app)[0].dedup_key.is_none());
+    }
+
+    #[test]
+    fn test_parse_session_line_cache_only_usage_still_emitted() {
+        let app = make_test_app();
+        let line = r#"{"type":"assistant","message":{"model":"m","content":[],"usage":{"input_tokens":0,"output_tokens":0,"cache_read_input_tokens":1200}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        let events = parse_session_line(line, &app);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "token_usage");
+    }
+
     #[test]
     fn test_parse_session_line_unknown_type_ignored() {
         let app = make_test_app();
@@ -865,11 +1418,11 @@ mod tests {
         }
         let app = make_test_app();
         let mut last_mtime: Option<SystemTime> = None;
-        let mut last_cost = 0.0_f64;
+        let mut last_cost = HashMap::new();
         let result = poll_stats_cache(&path, &app, &mut last_mtime, &mut last_cost);
         assert!(result.is_none());
         assert!(last_mtime.is_some());
-        assert!((last_cost - 1.5).abs() < 1e-9);
+        assert!((last_cost["m1"] - 1.5).abs() < 1e-9);
         let _ = std::fs::remove_dir_all(&dir);
     }
 
@@ -883,7 +1436,7 @@ mod tests {
         }
         let app = make_test_app();
         let mut last_mtime: Option<SystemTime> = None;
-        let mut last_cost = 0.0_f64;
+        let mut last_cost = HashMap::new();
         poll_stats_cache(&path, &app, &mut last_mtime, &mut last_cost);
         // second call without file change
         let result = poll_stats_cache(&path, &app, &mut last_mtime, &mut last_cost);
@@ -901,7 +1454,7 @@ mod tests {
         }
         let app = make_test_app();
         let mut last_mtime: Option<SystemTime> = None;
-        let mut last_cost = 0.0_f64;
+        let mut last_cost = HashMap::new();
         poll_stats_cache(&path, &app, &mut last_mtime, &mut last_cost);
 
         // simulate file update with cost increase — sleep 1s to guarantee mtime change
@@ -914,7 +1467,8 @@ mod tests {
         assert!(result.is_some());
         let evt = result.unwrap();
         assert_eq!(evt.event, "cost_update");
-        assert!((last_cost - 2.5).abs() < 1e-9);
+        assert_eq!(evt.metadata["modelCostDeltas"]["m1"], 1.5);
+        assert!((last_cost["m1"] - 2.5).abs() < 1e-9);
         let _ = std::fs::remove_dir_all(&dir);
     }
 
@@ -922,7 +1476,7 @@ mod tests {
     fn test_poll_stats_cache_nonexistent_returns_none() {
         let app = make_test_app();
         let mut last_mtime: Option<SystemTime> = None;
-        let mut last_cost = 0.0_f64;
+        let mut last_cost = HashMap::new();
         let result = poll_stats_cache(
             &std::env::temp_dir().join("ccm_nonexistent_stats.json"),
             &app,
@@ -942,7 +1496,7 @@ mod tests {
         }
         let app = make_test_app();
         let mut last_mtime: Option<SystemTime> = None;
-        let mut last_cost = 0.0_f64;
+        let mut last_cost = HashMap::new();
         poll_stats_cache(&path, &app, &mut last_mtime, &mut last_cost);
 
         // write a lower cost — sleep 1s to guarantee mtime change on all filesystems
@@ -966,14 +1520,221 @@ mod tests {
             writeln!(f, r#"{{"type":"user","message":{{"content":"hello"}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}}"#).unwrap();
         }
         let app = make_test_app();
-        let mut cursors: HashMap<PathBuf, (u64, String)> = HashMap::new();
-        poll_session_files(&dir, &app, &mut cursors);
+        let mut cursors: HashMap<PathBuf, FileCursor> = HashMap::new();
+        poll_session_files(&dir, &app, &mut cursors, &mut ScanIndex::default());
         let state = app.state.lock().unwrap();
         assert_eq!(state.recent.len(), 1);
         assert_eq!(state.recent[0].event, "user_message");
+        assert_eq!(state.collector_scan.files_changed, 1);
+        assert!(!state.collector_scan.scanned_at.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_delta_lines_replaced_file_resets_cursor() {
+        let dir = unique_tmp_dir("rdl_replace");
+        let path = dir.join("test.jsonl");
+        std::fs::write(&path, "line1\nline2\n").unwrap();
+        let mut cursor = FileCursor::default();
+        assert_eq!(read_delta_lines(&path, &mut cursor, 1024).len(), 2);
+        assert!(cursor.inode != 0);
+
+        // replace via rename so the new file has a different inode but a larger size
+        let tmp = dir.join("test.jsonl.tmp");
+        std::fs::write(&tmp, "fresh1\nfresh2\nfresh3\n").unwrap();
+        std::fs::rename(&tmp, &path).unwrap();
+        let lines = read_delta_lines(&path, &mut cursor, 1024);
+        assert_eq!(lines, vec!["fresh1", "fresh2", "fresh3"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_delta_lines_resumes_saved_remainder() {
+        let dir = unique_tmp_dir("rdl_resume");
+        let path = dir.join("test.jsonl");
+        std::fs::write(&path, "line1\npart").unwrap();
+        let mut cursor = FileCursor::default();
+        assert_eq!(read_delta_lines(&path, &mut cursor, 1024), vec!["line1"]);
+        assert_eq!(cursor.remainder, "part");
+
+        let mut restored = cursor.clone();
+        {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            write!(f, "ial\nline3\n").unwrap();
+        }
+        let lines = read_delta_lines(&path, &mut restored, 1024);
+        assert_eq!(lines, vec!["partial", "line3"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_persist_cursor_holds_back_after_dropped_write() {
+        use crate::db::Db;
+        use crate::db_writer::DbWriter;
+
+        let dir = unique_tmp_dir("cursor_held");
+        let db_path = dir.join("monitor.db");
+        let (writer, _handle) = DbWriter::spawn(Db::open(&db_path).unwrap(), 60_000, 1_000);
+        let mut app = make_test_app();
+        app.db_writer = Some(writer.clone());
+        let path = dir.join("s.jsonl");
+        let mut cursor = FileCursor {
+            offset: 10,
+            size: 10,
+            ..FileCursor::default()
+        };
+        // a write was dropped between taking the count and persisting
+        persist_cursor(&app, &path, &mut cursor, writer.dropped() + 1);
+        assert!(cursor.held_back);
+        cursor.offset = 20;
+        persist_cursor(&app, &path, &mut cursor, writer.dropped());
+        assert!(writer.flush());
+        assert!(Db::open(&db_path)
+            .unwrap()
+            .load_cursors()
+            .unwrap()
+            .is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_poll_session_files_resumes_from_persisted_cursors() {
+        use crate::db::Db;
+        use crate::db_writer::DbWriter;
+
+        let dir = unique_tmp_dir("psf_resume");
+        let projects = dir.join("projects");
+        let sub = projects.join("proj1");
+        std::fs::create_dir_all(&sub).unwrap();
+        let session = sub.join("session.jsonl");
+        {
+            let mut f = File::create(&session).unwrap();
+            writeln!(f, r#"{{"type":"user","message":{{"content":"hello"}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}}"#).unwrap();
+        }
+        let db_path = dir.join("monitor.db");
+        let (writer, _handle) = DbWriter::spawn(Db::open(&db_path).unwrap(), 60_000, 1_000);
+        let mut app = make_test_app();
+        app.db_writer = Some(writer.clone());
+        poll_session_files(
+            &projects,
+            &app,
+            &mut HashMap::new(),
+            &mut ScanIndex::default(),
+        );
+        assert!(writer.flush());
+
+        // simulated restart: fresh state, cursors loaded from the database
+        let mut cursors = Db::open(&db_path).unwrap().load_cursors().unwrap();
+        assert_eq!(
+            cursors[&session].offset,
+            std::fs::metadata(&session).unwrap().len()
+        );
+        {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&session)
+                .unwrap();
+            writeln!(f, r#"{{"type":"user","message":{{"content":"again"}},"sessionId":"s1","timestamp":"2025-01-01T00:01:00Z"}}"#).unwrap();
+        }
+        let restarted = make_test_app();
+        poll_session_files(
+            &projects,
+            &restarted,
+            &mut cursors,
+            &mut ScanIndex::default(),
+        );
+        let state = restarted.state.lock().unwrap();
+        assert_eq!(state.recent.len(), 1);
+        assert_eq!(state.recent[0].message, "again");
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_poll_session_files_reread_does_not_double_count() {
+        let dir = unique_tmp_dir("psf_dedup");
+        let sub = dir.join("proj1");
+        std::fs::create_dir_all(&sub).unwrap();
+        {
+            let mut f = File::create(sub.join("session.jsonl")).unwrap();
+            writeln!(f, r#"{{"type":"assistant","uuid":"u-9","message":{{"model":"m","content":[{{"type":"text","text":"hi"}}],"usage":{{"input_tokens":10,"output_tokens":5}}}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}}"#).unwrap();
+        }
+        let app = make_test_app();
+        poll_session_files(&dir, &app, &mut HashMap::new(), &mut ScanIndex::default());
+        // a lost cursor makes the whole file look new again
+        poll_session_files(&dir, &app, &mut HashMap::new(), &mut ScanIndex::default());
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.recent.len(), 2);
+        assert_eq!(state.token_total, 15);
+        assert_eq!(state.duplicates_skipped, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_collector_watch_picks_up_append_before_poll_interval() {
+        let home = unique_tmp_dir("watch_collector");
+        let sub = home.join("projects").join("proj1");
+        std::fs::create_dir_all(&sub).unwrap();
+        let session = sub.join("session.jsonl");
+        {
+            let mut f = File::create(&session).unwrap();
+            writeln!(f, r#"{{"type":"user","message":{{"content":"first"}},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}}"#).unwrap();
+        }
+        let app = make_test_app();
+        let poll_ms = 30_000;
+        let options = CollectorOptions {
+            poll_ms,
+            backfill_lines: 0,
+            watch: true,
+            dormant_after: Duration::from_secs(3600),
+            dormant_recheck: Duration::from_secs(60),
+        };
+        spawn_claude_collector(app.clone(), home.clone(), options, HashMap::new());
+
+        let wait_for = |expected: usize| {
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(5) {
+                if app.state.lock().unwrap().recent.len() >= expected {
+                    return Some(started.elapsed());
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            None
+        };
+        assert!(wait_for(1).is_some(), "initial scan never ran");
+
+        {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&session)
+                .unwrap();
+            writeln!(f, r#"{{"type":"user","message":{{"content":"second"}},"sessionId":"s1","timestamp":"2025-01-01T00:01:00Z"}}"#).unwrap();
+        }
+        let elapsed = wait_for(2).expect("appended line never arrived");
+        assert!(elapsed < Duration::from_millis(poll_ms / 10));
+        assert_eq!(app.state.lock().unwrap().recent[0].message, "second");
+        let _ = std::fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn test_is_session_file_matches_walk_rules() {
+        let projects = Path::new("/home/u/.claude/projects");
+        assert!(is_session_file(projects, &projects.join("p/s.jsonl")));
+        assert!(is_session_file(
+            projects,
+            &projects.join("p/s/subagents/a.jsonl")
+        ));
+        assert!(!is_session_file(projects, &projects.join("top.jsonl")));
+        assert!(!is_session_file(projects, &projects.join("p/notes.txt")));
+        assert!(!is_session_file(
+            projects,
+            Path::new("/elsewhere/p/s.jsonl")
+        ));
+    }
+
     // ── cwd parsing tests ──
 
     #[test]
@@ -1060,6 +1821,118 @@ mod tests {
         assert!(events.is_empty());
     }
 
+    // ── tool_use / tool_result pairing tests ──
+
+    #[test]
+    fn test_parse_session_line_tool_result_pairs_with_tool_use() {
+        let app = make_test_app();
+        let call = r#"{"type":"assistant","agentId":"agent-abc","message":{"model":"m","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{}}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        let calls = parse_session_line(call, &app);
+        assert_eq!(calls[0].metadata["toolUseId"], "toolu_1");
+        assert!(app
+            .state
+            .lock()
+            .unwrap()
+            .pending_tool_calls
+            .contains_key("toolu_1"));
+
+        let result = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"ok"}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:02.500Z"}"#;
+        let events = parse_session_line(result, &app);
+        assert_eq!(events.len(), 2); // tool_result + flattened user_message
+        assert_eq!(events[0].event, "tool_result");
+        assert_eq!(events[0].status, "ok");
+        assert_eq!(events[0].message, "Bash");
+        assert_eq!(events[0].agent_id, "agent-abc");
+        assert_eq!(events[0].latency_ms, Some(2500));
+        assert_eq!(events[1].event, "user_message");
+        assert!(app.state.lock().unwrap().pending_tool_calls.is_empty());
+    }
+
+    #[test]
+    fn test_task_result_tags_subagent_with_its_type() {
+        let app = make_test_app();
+        let ingest = |line: &str| {
+            for evt in parse_session_line(line, &app) {
+                crate::state::append_event(&app, evt);
+            }
+        };
+        ingest(
+            r#"{"type":"assistant","message":{"model":"m","content":[{"type":"tool_use","id":"toolu_t1","name":"Task","input":{"subagent_type":"refactor","prompt":"go"}}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#,
+        );
+        ingest(
+            r#"{"type":"assistant","agentId":"a1","isSidechain":true,"message":{"model":"m","content":[{"type":"text","text":"on it"}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:01Z"}"#,
+        );
+        ingest(
+            r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_t1","content":"done"}]},"toolUseResult":{"status":"completed","agentId":"a1"},"sessionId":"s1","timestamp":"2025-01-01T00:00:09Z"}"#,
+        );
+        // the result was read before the second subagent's own transcript
+        ingest(
+            r#"{"type":"assistant","message":{"model":"m","content":[{"type":"tool_use","id":"toolu_t2","name":"Task","input":{"subagent_type":"Explore"}}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:10Z"}"#,
+        );
+        ingest(
+            r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_t2","content":"done"}]},"toolUseResult":{"agentId":"a2"},"sessionId":"s1","timestamp":"2025-01-01T00:00:20Z"}"#,
+        );
+        ingest(
+            r#"{"type":"assistant","agentId":"a2","isSidechain":true,"message":{"model":"m","content":[{"type":"text","text":"looked"}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:11Z"}"#,
+        );
+
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.by_agent["a1"].subagent_type, "refactor");
+        assert_eq!(state.by_agent["a2"].subagent_type, "Explore");
+        assert_eq!(state.by_agent["lead-s1"].subagent_type, "");
+        assert!(state.pending_subagent_types.is_empty());
+    }
+
+    #[test]
+    fn test_parse_session_line_tool_result_error_status() {
+        let app = make_test_app();
+        let call = r#"{"type":"assistant","message":{"model":"m","content":[{"type":"tool_use","id":"toolu_2","name":"Edit","input":{}}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        parse_session_line(call, &app);
+        let result = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_2","is_error":true,"content":"file not found"}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:01Z"}"#;
+        let events = parse_session_line(result, &app);
+        assert_eq!(events[0].event, "tool_result");
+        assert_eq!(events[0].status, "error");
+        assert_eq!(events[0].metadata["isError"], true);
+
+        for evt in events {
+            append_event(&app, evt);
+        }
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.alerts.len(), 1);
+        assert_eq!(state.alerts[0].event, "tool_result");
+        assert_eq!(state.by_agent["lead-s1"].latency_ms, Some(1000));
+    }
+
+    #[test]
+    fn test_parse_session_line_tool_result_without_pending_call() {
+        let app = make_test_app();
+        let line = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_missing","content":"x"}]},"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z"}"#;
+        let events = parse_session_line(line, &app);
+        assert_eq!(events[0].event, "tool_result");
+        assert_eq!(events[0].message, "unknown_tool");
+        assert!(events[0].latency_ms.is_none());
+    }
+
+    #[test]
+    fn test_pending_tool_calls_are_bounded() {
+        let app = make_test_app();
+        for i in 0..(MAX_PENDING_TOOL_CALLS + 5) {
+            track_pending_tool_call(
+                &app,
+                &format!("toolu_{}", i),
+                PendingToolCall {
+                    tool_name: "Bash".to_string(),
+                    agent_id: "a1".to_string(),
+                    session_id: "s1".to_string(),
+                    started_at: format!("2025-01-01T00:00:{:02}Z", i % 60),
+                    subagent_type: String::new(),
+                },
+            );
+        }
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.pending_tool_calls.len(), MAX_PENDING_TOOL_CALLS);
+    }
+
     #[test]
     fn test_parse_history_event_invalid_timestamp_falls_back() {
         let app = make_test_app();
@@ -1105,13 +1978,82 @@ mod tests {
         #[cfg(unix)]
         {
             std::os::unix::fs::symlink(&proj, session.join("loop")).unwrap();
-            let files = walk_jsonl_files(&dir);
+            let files = walk_jsonl_files(&dir, &mut ScanIndex::default());
             // should find lead.jsonl but not infinite loop
             assert_eq!(files.len(), 1);
         }
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_walk_jsonl_files_index_skips_unchanged() {
+        let dir = unique_tmp_dir("walk_index");
+        let session = dir.join("proj").join("sess");
+        std::fs::create_dir_all(&session).unwrap();
+        let lead = session.join("lead.jsonl");
+        std::fs::write(&lead, "a\n").unwrap();
+        let mut index = ScanIndex::default();
+        assert_eq!(walk_jsonl_files(&dir, &mut index), vec![lead.clone()]);
+
+        assert!(walk_jsonl_files(&dir, &mut index).is_empty());
+        assert_eq!(index.last_scan.dirs_listed, 0);
+        assert_eq!(index.last_scan.dirs_skipped, 3);
+        assert_eq!(index.last_scan.files_checked, 1);
+
+        {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&lead)
+                .unwrap();
+            writeln!(f, "b").unwrap();
+        }
+        assert_eq!(walk_jsonl_files(&dir, &mut index), vec![lead.clone()]);
+        assert_eq!(index.last_scan.dirs_listed, 0);
+
+        let agent = session.join("agent.jsonl");
+        std::fs::write(&agent, "a\n").unwrap();
+        assert_eq!(walk_jsonl_files(&dir, &mut index), vec![agent]);
+        assert_eq!(index.last_scan.dirs_listed, 1);
+        assert_eq!(index.last_scan.files_changed, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_walk_jsonl_files_dormant_files_rechecked_occasionally() {
+        let dir = unique_tmp_dir("walk_dormant");
+        let session = dir.join("proj");
+        std::fs::create_dir_all(&session).unwrap();
+        let old = session.join("old.jsonl");
+        std::fs::write(&old, "a\n").unwrap();
+
+        let mut index = ScanIndex::new(Duration::ZERO, Duration::from_secs(3600));
+        walk_jsonl_files(&dir, &mut index);
+        std::fs::write(&old, "a\nb\n").unwrap();
+        assert!(walk_jsonl_files(&dir, &mut index).is_empty());
+        assert_eq!(index.last_scan.dormant_skipped, 1);
+
+        let mut eager = ScanIndex::new(Duration::ZERO, Duration::ZERO);
+        walk_jsonl_files(&dir, &mut eager);
+        std::fs::write(&old, "a\nb\nc\n").unwrap();
+        assert_eq!(walk_jsonl_files(&dir, &mut eager), vec![old]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_walk_jsonl_files_forgets_removed_dirs() {
+        let dir = unique_tmp_dir("walk_removed");
+        let session = dir.join("proj").join("sess");
+        std::fs::create_dir_all(&session).unwrap();
+        std::fs::write(session.join("lead.jsonl"), "a\n").unwrap();
+        let mut index = ScanIndex::default();
+        walk_jsonl_files(&dir, &mut index);
+        std::fs::remove_dir_all(dir.join("proj")).unwrap();
+        assert!(walk_jsonl_files(&dir, &mut index).is_empty());
+        assert!(index.files.is_empty());
+        assert_eq!(index.dirs.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_walk_jsonl_files_deep_nesting() {
         let dir = unique_tmp_dir("walk_deep");
@@ -1120,7 +2062,7 @@ mod tests {
         File::create(deep.join("agent.jsonl")).unwrap();
         File::create(dir.join("proj").join("sess").join("lead.jsonl")).unwrap();
 
-        let files = walk_jsonl_files(&dir);
+        let files = walk_jsonl_files(&dir, &mut ScanIndex::default());
         assert_eq!(files.len(), 2);
         assert!(files.iter().all(|p| p.extension().unwrap() == "jsonl"));
         let _ = std::fs::remove_dir_all(&dir);
@@ -0,0 +1,212 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::budgets::{validate_budgets, BudgetConfig};
+use crate::forecast::{validate_forecast, ForecastConfig};
+use crate::notify::{validate_notifiers, NotifierConfig};
+use crate::pricing::{load_price_overrides, PricingTable};
+use crate::rules::{validate_rules, AlertRule};
+use crate::session_state::{validate_session_state, SessionStateConfig};
+
+/// Optional `monitor.json` settings. Every section falls back to built-in
+/// defaults, so a missing file is the same as an empty one.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MonitorConfig {
+    pub pricing: PricingTable,
+    /// Extra price overrides in a TOML or JSON file, relative to
+    /// `monitor.json`; applied after `pricing`.
+    pub pricing_file: Option<PathBuf>,
+    /// When set, `POST` endpoints require `Authorization: Bearer <token>`.
+    pub ingest_token: Option<String>,
+    /// Server-side alert rules, evaluated continuously.
+    pub alert_rules: Vec<AlertRule>,
+    /// Where open alerts are sent (webhook, Slack, Discord).
+    pub notifiers: Vec<NotifierConfig>,
+    /// Spend caps per period (global, project or model).
+    pub budgets: Vec<BudgetConfig>,
+    /// Burn-rate windows for spend forecasts.
+    pub forecast: ForecastConfig,
+    /// Windows and terminal hints that decide session states.
+    pub session_state: SessionStateConfig,
+}
+
+impl MonitorConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                let mut config: Self = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+                if let Some(file) = &config.pricing_file {
+                    let file = path.parent().unwrap_or(Path::new("")).join(file);
+                    let overrides = load_price_overrides(&file)?;
+                    config.pricing = config.pricing.with_overrides(overrides);
+                }
+                validate_rules(&config.alert_rules)?;
+                validate_notifiers(&config.notifiers)?;
+                validate_budgets(&config.budgets)?;
+                validate_forecast(&config.forecast)?;
+                validate_session_state(&config.session_state)?;
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// What to run with after [`MonitorConfig::load`] failed: built-in
+    /// defaults, keeping an `ingestToken` that can still be read so a typo in
+    /// another section never leaves the `POST` endpoints open. `None` when the
+    /// token cannot be determined (unreadable file or invalid JSON).
+    pub fn fallback(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let raw: serde_json::Value = serde_json::from_str(&content).ok()?;
+        let ingest_token = match raw.get("ingestToken") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(token)) => Some(token.clone()),
+            Some(_) => return None,
+        };
+        Some(Self {
+            ingest_token,
+            ..Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_load_missing_file_uses_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MonitorConfig::load(&dir.path().join("monitor.json")).unwrap();
+        assert!(config.pricing.price_for("claude-sonnet-4").is_some());
+    }
+
+    #[test]
+    fn test_load_pricing_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        {
+            let mut f = std::fs::File::create(&path).unwrap();
+            write!(
+                f,
+                r#"{{"pricing":{{"claude-sonnet-4":{{"input":1,"output":2}}}}}}"#
+            )
+            .unwrap();
+        }
+        let config = MonitorConfig::load(&path).unwrap();
+        assert_eq!(
+            config.pricing.price_for("claude-sonnet-4").unwrap().input,
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_load_pricing_file_relative_to_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(
+            dir.path().join("prices.toml"),
+            "[claude-sonnet-4]\ninput = 1.5\noutput = 2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &path,
+            r#"{"pricing":{"claude-sonnet-4":{"input":1,"output":2}},"pricingFile":"prices.toml"}"#,
+        )
+        .unwrap();
+        let config = MonitorConfig::load(&path).unwrap();
+        assert_eq!(
+            config.pricing.price_for("claude-sonnet-4").unwrap().input,
+            1.5
+        );
+
+        std::fs::write(&path, r#"{"pricingFile":"missing.toml"}"#).unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_load_alert_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(
+            &path,
+            r#"{"alertRules":[{"id":"cost","scope":"session","metric":"costUsd","threshold":5}]}"#,
+        )
+        .unwrap();
+        let config = MonitorConfig::load(&path).unwrap();
+        assert_eq!(config.alert_rules.len(), 1);
+
+        std::fs::write(
+            &path,
+            r#"{"alertRules":[{"id":"cost","scope":"team","metric":"costUsd","threshold":5}]}"#,
+        )
+        .unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_load_budgets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(
+            &path,
+            r#"{"budgets":[{"id":"daily","scope":"global","period":"daily","capUsd":20}]}"#,
+        )
+        .unwrap();
+        let config = MonitorConfig::load(&path).unwrap();
+        assert_eq!(config.budgets.len(), 1);
+
+        std::fs::write(
+            &path,
+            r#"{"budgets":[{"id":"web","scope":"project","period":"daily","capUsd":20}]}"#,
+        )
+        .unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_load_session_state_windows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(
+            &path,
+            r#"{"sessionState":{"stuckWindowSecs":300,"agentTypes":{"subagent":{"stuckWindowSecs":600}}}}"#,
+        )
+        .unwrap();
+        let config = MonitorConfig::load(&path).unwrap();
+        assert_eq!(config.session_state.stuck_window_secs, 300);
+        assert_eq!(config.session_state.active_window_secs, 30);
+
+        std::fs::write(&path, r#"{"sessionState":{"stuckWindowSecs":10}}"#).unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_fallback_keeps_ingest_token_when_a_section_is_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(
+            &path,
+            r#"{"ingestToken":"secret","alertRules":[{"id":"cost","scope":"team","metric":"costUsd","threshold":5}]}"#,
+        )
+        .unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+        let config = MonitorConfig::fallback(&path).unwrap();
+        assert_eq!(config.ingest_token.as_deref(), Some("secret"));
+        assert!(config.alert_rules.is_empty());
+
+        std::fs::write(&path, r#"{"ingestToken":"secret","#).unwrap();
+        assert!(MonitorConfig::fallback(&path).is_none());
+    }
+
+    #[test]
+    fn test_load_invalid_json_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(MonitorConfig::load(&path).is_err());
+    }
+}
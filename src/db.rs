@@ -1,39 +1,636 @@
-use crate::types::HourBucket;
-use rusqlite::Connection;
-use std::path::Path;
+use crate::alerts::upgrade_legacy_alert;
+use crate::notify::NotificationDelivery;
+use crate::types::{
+    AgentRow, AlertRow, Event, FileCursor, HourBucket, ModelUsage, OtelSeries, ProjectRow,
+    SessionRow, TokenBreakdown,
+};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Session listing filters on indexed `sessions` columns.
+#[derive(Default)]
+pub struct SessionFilter<'a> {
+    pub since: Option<&'a str>,
+    pub until: Option<&'a str>,
+    pub project: Option<&'a str>,
+    pub min_cost: Option<f64>,
+    /// Left out, e.g. because a newer copy is held in memory.
+    pub exclude: Vec<&'a str>,
+}
+
+impl SessionFilter<'_> {
+    fn where_clause(&self) -> (String, Vec<Value>) {
+        let mut clause = String::from("1 = 1");
+        let mut params = Vec::new();
+        let text = |v: &str| Value::Text(v.to_string());
+        if let Some(since) = self.since {
+            clause.push_str(" AND last_seen >= ?");
+            params.push(text(since));
+        }
+        if let Some(until) = self.until {
+            clause.push_str(" AND last_seen <= ?");
+            params.push(text(until));
+        }
+        if let Some(project) = self.project {
+            clause.push_str(" AND project_name = ?");
+            params.push(text(project));
+        }
+        if let Some(min_cost) = self.min_cost {
+            clause.push_str(" AND cost_usd >= ?");
+            params.push(Value::Real(min_cost));
+        }
+        if !self.exclude.is_empty() {
+            clause.push_str(" AND session_id NOT IN (SELECT value FROM json_each(?))");
+            params.push(Value::Text(
+                serde_json::to_string(&self.exclude).unwrap_or_else(|_| "[]".to_string()),
+            ));
+        }
+        (clause, params)
+    }
+}
+
+/// Column a session listing is ordered by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionOrder {
+    LastSeen,
+    Cost,
+    Tokens,
+    SessionId,
+}
+
+impl SessionOrder {
+    fn column(self) -> &'static str {
+        match self {
+            Self::LastSeen => "last_seen",
+            Self::Cost => "cost_usd",
+            Self::Tokens => "token_total",
+            Self::SessionId => "session_id",
+        }
+    }
+}
+
+/// A write queued for the background writer (see `db_writer`).
+pub enum DbWrite {
+    Bucket {
+        hour_key: String,
+        tokens: u64,
+        cost: f64,
+        usage: TokenBreakdown,
+    },
+    Record {
+        event: Box<Event>,
+        agent: Box<AgentRow>,
+        session: Option<Box<SessionRow>>,
+        alert: Option<Box<AlertRow>>,
+    },
+    /// Hourly usage of one project or model.
+    ScopedBucket {
+        scope: String,
+        name: String,
+        hour_key: String,
+        delta: ModelUsage,
+    },
+    Cursor {
+        path: PathBuf,
+        cursor: FileCursor,
+    },
+    /// Alert state changed outside of an event (ack, snooze, resolve).
+    Alert(Box<AlertRow>),
+    /// Outcome of sending an alert to a notifier.
+    Delivery(Box<NotificationDelivery>),
+    Project(Box<ProjectRow>),
+    /// Session state changed with time rather than with an event.
+    Session(Box<SessionRow>),
+    /// Latest value of a cumulative OTLP series.
+    OtelSeries {
+        series: String,
+        last: OtelSeries,
+        updated_at: String,
+    },
+}
 
 pub struct Db {
     conn: Connection,
 }
 
 impl Db {
+    /// Opens (or creates) the database and brings its schema up to
+    /// [`SCHEMA_VERSION`]. A database written by a newer binary is refused
+    /// rather than silently misread.
     pub fn open(path: &Path) -> rusqlite::Result<Self> {
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS hourly_tokens (
-                hour_key   TEXT PRIMARY KEY,
-                token_total INTEGER NOT NULL DEFAULT 0,
-                cost_usd   REAL NOT NULL DEFAULT 0.0
-            )",
-        )?;
+        let mut conn = Connection::open(path)?;
+        // WAL lets the background writer and the read connection work side by side.
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut conn)?;
         Ok(Self { conn })
     }
 
-    pub fn upsert_bucket(&self, hour_key: &str, tokens: u64, cost: f64) -> rusqlite::Result<()> {
+    pub fn schema_version(&self) -> rusqlite::Result<i64> {
+        user_version(&self.conn)
+    }
+
+    /// Applies a batch of queued writes in one transaction.
+    pub fn apply_batch(&self, writes: &[DbWrite]) -> rusqlite::Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for write in writes {
+            self.apply(write)?;
+        }
+        tx.commit()
+    }
+
+    pub fn apply(&self, write: &DbWrite) -> rusqlite::Result<()> {
+        match write {
+            DbWrite::Bucket {
+                hour_key,
+                tokens,
+                cost,
+                usage,
+            } => self.upsert_bucket(hour_key, *tokens, *cost, usage),
+            DbWrite::Record {
+                event,
+                agent,
+                session,
+                alert,
+            } => self.record_event(event, agent, session.as_deref(), alert.as_deref()),
+            DbWrite::ScopedBucket {
+                scope,
+                name,
+                hour_key,
+                delta,
+            } => self.upsert_scoped_bucket(scope, name, hour_key, delta),
+            DbWrite::Cursor { path, cursor } => self.save_cursor(path, cursor),
+            DbWrite::Alert(alert) => self.upsert_alert(alert),
+            DbWrite::Delivery(delivery) => self.record_delivery(delivery),
+            DbWrite::Project(project) => self.upsert_project(project),
+            DbWrite::Session(session) => self.upsert_session(session),
+            DbWrite::OtelSeries {
+                series,
+                last,
+                updated_at,
+            } => self.save_otel_series(series, last, updated_at),
+        }
+    }
+
+    /// Writes one event together with the rows it touched. Callers that need
+    /// atomicity wrap this in a transaction (see [`Db::apply_batch`]).
+    pub fn record_event(
+        &self,
+        evt: &Event,
+        agent: &AgentRow,
+        session: Option<&SessionRow>,
+        alert: Option<&AlertRow>,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO events (id, session_id, agent_id, received_at, cwd, dedup_key, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                evt.id,
+                evt.session_id,
+                evt.agent_id,
+                evt.received_at,
+                evt.cwd,
+                evt.dedup_key,
+                to_json(evt)?
+            ],
+        )?;
+        self.upsert_agent(agent)?;
+        if let Some(session) = session {
+            self.upsert_session(session)?;
+        }
+        if let Some(alert) = alert {
+            self.upsert_alert(alert)?;
+        }
+        Ok(())
+    }
+
+    pub fn upsert_agent(&self, agent: &AgentRow) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO agents (agent_id, last_seen, display_name_from_user, session_id, data)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(agent_id) DO UPDATE SET
+               last_seen = excluded.last_seen,
+               display_name_from_user = excluded.display_name_from_user,
+               session_id = excluded.session_id,
+               data = excluded.data",
+            rusqlite::params![
+                agent.agent_id,
+                agent.last_seen,
+                agent.display_name_from_user,
+                agent.session_id,
+                to_json(agent)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn upsert_session(&self, session: &SessionRow) -> rusqlite::Result<()> {
         self.conn.execute(
-            "INSERT INTO hourly_tokens (hour_key, token_total, cost_usd)
-             VALUES (?1, ?2, ?3)
+            "INSERT INTO sessions (session_id, last_seen, display_name_locked, project_name,
+               cost_usd, token_total, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(session_id) DO UPDATE SET
+               last_seen = excluded.last_seen,
+               display_name_locked = excluded.display_name_locked,
+               project_name = excluded.project_name,
+               cost_usd = excluded.cost_usd,
+               token_total = excluded.token_total,
+               data = excluded.data",
+            rusqlite::params![
+                session.session_id,
+                session.last_seen,
+                session.display_name_locked,
+                session.project_name,
+                session.cost_usd,
+                session.token_total,
+                to_json(session)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn upsert_alert(&self, alert: &AlertRow) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO alerts (id, created_at, last_seen, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET
+               last_seen = excluded.last_seen,
+               data = excluded.data",
+            rusqlite::params![alert.id, alert.created_at, alert.last_seen, to_json(alert)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn upsert_project(&self, project: &ProjectRow) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO projects (project_name, last_seen, data) VALUES (?1, ?2, ?3)
+             ON CONFLICT(project_name) DO UPDATE SET
+               last_seen = excluded.last_seen,
+               data = excluded.data",
+            rusqlite::params![project.project_name, project.last_seen, to_json(project)?],
+        )?;
+        Ok(())
+    }
+
+    /// Most recently seen projects first.
+    pub fn load_projects(&self) -> rusqlite::Result<Vec<ProjectRow>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM projects ORDER BY last_seen DESC")?;
+        let rows = stmt.query_map([], |row| from_json(&row.get::<_, String>(0)?))?;
+        rows.collect()
+    }
+
+    /// Most recent events first.
+    pub fn load_recent_events(&self, limit: usize) -> rusqlite::Result<Vec<Event>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data, cwd FROM events ORDER BY seq DESC LIMIT ?1")?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], event_from_row)?;
+        rows.collect()
+    }
+
+    /// The newest `limit` events of a session, oldest first.
+    pub fn load_session_events(
+        &self,
+        session_id: &str,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<Event>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT data, cwd FROM events WHERE session_id = ?1
+             ORDER BY seq DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![session_id, limit as i64], event_from_row)?;
+        let mut events = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        events.reverse();
+        Ok(events)
+    }
+
+    /// Most recently seen sessions first.
+    pub fn load_sessions(&self, limit: usize) -> rusqlite::Result<Vec<SessionRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT data, display_name_locked FROM sessions
+             ORDER BY last_seen DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], |row| {
+            let mut session: SessionRow = from_json(&row.get::<_, String>(0)?)?;
+            session.display_name_locked = row.get(1)?;
+            Ok(session)
+        })?;
+        rows.collect()
+    }
+
+    /// Sessions matching `filter` in `order` (ties broken by ascending session
+    /// id), starting after the `(value, session_id)` position of a previous
+    /// page.
+    pub fn page_sessions(
+        &self,
+        filter: &SessionFilter,
+        order: SessionOrder,
+        ascending: bool,
+        after: Option<(Value, &str)>,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<SessionRow>> {
+        let (mut clause, mut params) = filter.where_clause();
+        let column = order.column();
+        if let Some((value, session_id)) = after {
+            let op = if ascending { ">" } else { "<" };
+            clause.push_str(&format!(
+                " AND ({column} {op} ? OR ({column} = ? AND session_id > ?))"
+            ));
+            params.extend([value.clone(), value, Value::Text(session_id.to_string())]);
+        }
+        params.push(Value::Integer(limit as i64));
+        let direction = if ascending { "ASC" } else { "DESC" };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT data, display_name_locked FROM sessions WHERE {clause}
+             ORDER BY {column} {direction}, session_id ASC LIMIT ?"
+        ))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            let mut session: SessionRow = from_json(&row.get::<_, String>(0)?)?;
+            session.display_name_locked = row.get(1)?;
+            Ok(session)
+        })?;
+        rows.collect()
+    }
+
+    pub fn count_sessions(&self, filter: &SessionFilter) -> rusqlite::Result<usize> {
+        let (clause, params) = filter.where_clause();
+        self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM sessions WHERE {clause}"),
+            rusqlite::params_from_iter(params),
+            |row| row.get::<_, i64>(0).map(|n| n as usize),
+        )
+    }
+
+    pub fn load_agents_for_sessions(
+        &self,
+        session_ids: &[&str],
+    ) -> rusqlite::Result<Vec<AgentRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT data, display_name_from_user FROM agents
+             WHERE session_id IN (SELECT value FROM json_each(?1))",
+        )?;
+        let ids = serde_json::to_string(session_ids)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let rows = stmt.query_map(rusqlite::params![ids], |row| {
+            let mut agent: AgentRow = from_json(&row.get::<_, String>(0)?)?;
+            agent.display_name_from_user = row.get(1)?;
+            Ok(agent)
+        })?;
+        rows.collect()
+    }
+
+    /// Most recently seen agents first.
+    pub fn load_agents(&self, limit: usize) -> rusqlite::Result<Vec<AgentRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT data, display_name_from_user FROM agents
+             ORDER BY last_seen DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], |row| {
+            let mut agent: AgentRow = from_json(&row.get::<_, String>(0)?)?;
+            agent.display_name_from_user = row.get(1)?;
+            Ok(agent)
+        })?;
+        rows.collect()
+    }
+
+    /// Tool calls per tool name summed over every stored agent.
+    pub fn load_tool_use_totals(&self) -> rusqlite::Result<HashMap<String, u64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tool.key, SUM(tool.value)
+             FROM agents, json_each(agents.data, '$.toolUseCounts') AS tool
+             GROUP BY tool.key",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+        rows.collect()
+    }
+
+    /// Distinct fingerprints starting with `prefix` of alerts created since
+    /// `since`, however many alerts that spans.
+    pub fn load_alert_fingerprints(
+        &self,
+        prefix: &str,
+        since: &str,
+    ) -> rusqlite::Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT json_extract(data, '$.fingerprint') FROM alerts
+             WHERE created_at >= ?1
+               AND substr(json_extract(data, '$.fingerprint'), 1, length(?2)) = ?2",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![since, prefix], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Most recently seen alerts first.
+    pub fn load_alerts(&self, limit: usize) -> rusqlite::Result<Vec<AlertRow>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM alerts ORDER BY last_seen DESC, rowid DESC LIMIT ?1")?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], |row| {
+            let mut alert: AlertRow = from_json(&row.get::<_, String>(0)?)?;
+            upgrade_legacy_alert(&mut alert);
+            Ok(alert)
+        })?;
+        rows.collect()
+    }
+
+    pub fn record_delivery(&self, delivery: &NotificationDelivery) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO notification_deliveries
+               (alert_id, notifier_id, status, attempts, http_status, error, sent_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                delivery.alert_id,
+                delivery.notifier_id,
+                delivery.status,
+                delivery.attempts,
+                delivery.http_status,
+                delivery.error,
+                delivery.sent_at,
+                to_json(delivery)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Highest numeric suffix among persisted `e{n}` / `a{n}` ids, so a restarted
+    /// process keeps issuing unique ids.
+    pub fn max_id_sequence(&self) -> rusqlite::Result<u64> {
+        let max: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT MAX(n) FROM (
+                   SELECT CAST(SUBSTR(id, 2) AS INTEGER) AS n FROM events WHERE id LIKE 'e%'
+                   UNION ALL
+                   SELECT CAST(SUBSTR(id, 2) AS INTEGER) AS n FROM alerts WHERE id LIKE 'a%'
+                 )",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(max.unwrap_or(0).max(0) as u64)
+    }
+
+    /// Dedup keys of the newest `limit` events, oldest first.
+    pub fn load_dedup_keys(&self, limit: usize) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT dedup_key FROM events WHERE dedup_key IS NOT NULL
+             ORDER BY seq DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], |row| row.get(0))?;
+        let mut keys = rows.collect::<rusqlite::Result<Vec<String>>>()?;
+        keys.reverse();
+        Ok(keys)
+    }
+
+    pub fn save_cursor(&self, path: &Path, cursor: &FileCursor) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO file_cursors (path, inode, offset, size, remainder)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(path) DO UPDATE SET
+               inode = excluded.inode,
+               offset = excluded.offset,
+               size = excluded.size,
+               remainder = excluded.remainder",
+            rusqlite::params![
+                path.to_string_lossy(),
+                cursor.inode as i64,
+                cursor.offset as i64,
+                cursor.size as i64,
+                cursor.remainder
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_otel_series(
+        &self,
+        series: &str,
+        last: &OtelSeries,
+        updated_at: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO otel_series (series, session_id, value, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(series) DO UPDATE SET
+               session_id = excluded.session_id,
+               value = excluded.value,
+               updated_at = excluded.updated_at",
+            rusqlite::params![series, last.session_id, last.value, updated_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_otel_series(&self, series: &str) -> rusqlite::Result<Option<OtelSeries>> {
+        self.conn
+            .query_row(
+                "SELECT session_id, value FROM otel_series WHERE series = ?1",
+                rusqlite::params![series],
+                |row| {
+                    Ok(OtelSeries {
+                        session_id: row.get(0)?,
+                        value: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn load_cursors(&self) -> rusqlite::Result<HashMap<PathBuf, FileCursor>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, inode, offset, size, remainder FROM file_cursors")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                PathBuf::from(row.get::<_, String>(0)?),
+                FileCursor {
+                    inode: row.get::<_, i64>(1)? as u64,
+                    offset: row.get::<_, i64>(2)? as u64,
+                    size: row.get::<_, i64>(3)? as u64,
+                    remainder: row.get(4)?,
+                    held_back: false,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// Drops events, sessions, alerts and OTLP series last touched before `before_iso`.
+    pub fn prune_records_before(&self, before_iso: &str) -> rusqlite::Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut removed = tx.execute(
+            "DELETE FROM events WHERE received_at < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        removed += tx.execute(
+            "DELETE FROM sessions WHERE last_seen < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        removed += tx.execute(
+            "DELETE FROM agents WHERE last_seen < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        removed += tx.execute(
+            "DELETE FROM alerts WHERE last_seen < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        removed += tx.execute(
+            "DELETE FROM notification_deliveries WHERE sent_at < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        removed += tx.execute(
+            "DELETE FROM otel_series WHERE updated_at < ?1",
+            rusqlite::params![before_iso],
+        )?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    pub fn upsert_bucket(
+        &self,
+        hour_key: &str,
+        tokens: u64,
+        cost: f64,
+        usage: &TokenBreakdown,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO hourly_tokens (hour_key, token_total, cost_usd, input_tokens,
+               output_tokens, cache_read_tokens, cache_creation_tokens,
+               web_search_requests, web_fetch_requests)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
              ON CONFLICT(hour_key) DO UPDATE SET
                token_total = token_total + excluded.token_total,
-               cost_usd = cost_usd + excluded.cost_usd",
-            rusqlite::params![hour_key, tokens, cost],
+               cost_usd = cost_usd + excluded.cost_usd,
+               input_tokens = input_tokens + excluded.input_tokens,
+               output_tokens = output_tokens + excluded.output_tokens,
+               cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens,
+               cache_creation_tokens = cache_creation_tokens + excluded.cache_creation_tokens,
+               web_search_requests = web_search_requests + excluded.web_search_requests,
+               web_fetch_requests = web_fetch_requests + excluded.web_fetch_requests",
+            rusqlite::params![
+                hour_key,
+                tokens,
+                cost,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.cache_read_input_tokens,
+                usage.cache_creation_input_tokens,
+                usage.web_search_requests,
+                usage.web_fetch_requests,
+            ],
         )?;
         Ok(())
     }
 
     pub fn query_since(&self, since_key: &str) -> rusqlite::Result<Vec<HourBucket>> {
         let mut stmt = self.conn.prepare(
-            "SELECT hour_key, token_total, cost_usd FROM hourly_tokens
+            "SELECT hour_key, token_total, cost_usd, input_tokens, output_tokens,
+               cache_read_tokens, cache_creation_tokens, web_search_requests,
+               web_fetch_requests
+             FROM hourly_tokens
              WHERE hour_key >= ?1 ORDER BY hour_key",
         )?;
         let rows = stmt.query_map(rusqlite::params![since_key], |row| {
@@ -41,11 +638,23 @@ impl Db {
                 hour_key: row.get(0)?,
                 token_total: row.get(1)?,
                 cost_usd: row.get(2)?,
+                usage: usage_from_row(row, 3)?,
             })
         })?;
         rows.collect()
     }
 
+    pub fn query_usage_totals(&self) -> rusqlite::Result<TokenBreakdown> {
+        self.conn.query_row(
+            "SELECT COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0),
+               COALESCE(SUM(cache_read_tokens), 0), COALESCE(SUM(cache_creation_tokens), 0),
+               COALESCE(SUM(web_search_requests), 0), COALESCE(SUM(web_fetch_requests), 0)
+             FROM hourly_tokens",
+            [],
+            |row| usage_from_row(row, 0),
+        )
+    }
+
     pub fn query_totals(&self) -> rusqlite::Result<(u64, f64)> {
         self.conn.query_row(
             "SELECT COALESCE(SUM(token_total), 0), COALESCE(SUM(cost_usd), 0.0)
@@ -56,10 +665,108 @@ impl Db {
     }
 
     pub fn prune_before(&self, before_key: &str) -> rusqlite::Result<usize> {
-        self.conn.execute(
+        let removed = self.conn.execute(
             "DELETE FROM hourly_tokens WHERE hour_key < ?1",
             rusqlite::params![before_key],
-        )
+        )?;
+        Ok(removed
+            + self.conn.execute(
+                "DELETE FROM hourly_scoped_usage WHERE hour_key < ?1",
+                rusqlite::params![before_key],
+            )?)
+    }
+
+    pub fn upsert_scoped_bucket(
+        &self,
+        scope: &str,
+        name: &str,
+        hour_key: &str,
+        delta: &ModelUsage,
+    ) -> rusqlite::Result<()> {
+        let usage = &delta.usage;
+        self.conn.execute(
+            "INSERT INTO hourly_scoped_usage (scope, name, hour_key, token_total, cost_usd,
+               input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
+               web_search_requests, web_fetch_requests, requests, errors)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(scope, name, hour_key) DO UPDATE SET
+               requests = requests + excluded.requests,
+               errors = errors + excluded.errors,
+               token_total = token_total + excluded.token_total,
+               cost_usd = cost_usd + excluded.cost_usd,
+               input_tokens = input_tokens + excluded.input_tokens,
+               output_tokens = output_tokens + excluded.output_tokens,
+               cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens,
+               cache_creation_tokens = cache_creation_tokens + excluded.cache_creation_tokens,
+               web_search_requests = web_search_requests + excluded.web_search_requests,
+               web_fetch_requests = web_fetch_requests + excluded.web_fetch_requests",
+            rusqlite::params![
+                scope,
+                name,
+                hour_key,
+                delta.token_total,
+                delta.cost_usd,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.cache_read_input_tokens,
+                usage.cache_creation_input_tokens,
+                usage.web_search_requests,
+                usage.web_fetch_requests,
+                delta.requests,
+                delta.errors,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Per-model totals over every retained hour.
+    pub fn load_model_totals(&self) -> rusqlite::Result<HashMap<String, ModelUsage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, SUM(token_total), SUM(cost_usd), SUM(input_tokens),
+               SUM(output_tokens), SUM(cache_read_tokens), SUM(cache_creation_tokens),
+               SUM(web_search_requests), SUM(web_fetch_requests), SUM(requests), SUM(errors)
+             FROM hourly_scoped_usage WHERE scope = 'model' GROUP BY name",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                ModelUsage {
+                    token_total: row.get(1)?,
+                    cost_usd: row.get(2)?,
+                    usage: usage_from_row(row, 3)?,
+                    requests: row.get(9)?,
+                    errors: row.get(10)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// `(scope, name, bucket)` rows from `since_key` on, oldest hour first.
+    pub fn query_scoped_since(
+        &self,
+        since_key: &str,
+    ) -> rusqlite::Result<Vec<(String, String, HourBucket)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT scope, name, hour_key, token_total, cost_usd, input_tokens,
+               output_tokens, cache_read_tokens, cache_creation_tokens,
+               web_search_requests, web_fetch_requests
+             FROM hourly_scoped_usage
+             WHERE hour_key >= ?1 ORDER BY hour_key",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![since_key], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                HourBucket {
+                    hour_key: row.get(2)?,
+                    token_total: row.get(3)?,
+                    cost_usd: row.get(4)?,
+                    usage: usage_from_row(row, 5)?,
+                },
+            ))
+        })?;
+        rows.collect()
     }
 
     pub fn restore_buckets(&self, max: usize) -> rusqlite::Result<Vec<HourBucket>> {
@@ -72,6 +779,307 @@ impl Db {
     }
 }
 
+type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+/// Ordered schema migrations; entry `i` upgrades `user_version` `i` to `i + 1`.
+/// Databases created before versioning report `user_version = 0` whatever
+/// their shape, so every step must tolerate objects that already exist.
+const MIGRATIONS: &[Migration] = &[
+    migrate_hourly_tokens,
+    migrate_hourly_usage_columns,
+    migrate_event_records,
+    migrate_file_cursors,
+    migrate_event_dedup_keys,
+    migrate_alert_lifecycle,
+    migrate_notification_deliveries,
+    migrate_hourly_scoped_usage,
+    migrate_projects,
+    migrate_scoped_usage_columns,
+    migrate_project_member_counts,
+    migrate_scoped_request_counts,
+    migrate_session_list_columns,
+    migrate_otel_series,
+];
+
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let current = user_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISMATCH),
+            Some(format!(
+                "database schema version {} is newer than supported version {}",
+                current, SCHEMA_VERSION
+            )),
+        ));
+    }
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(current.max(0) as usize) {
+        let tx = conn.transaction()?;
+        step(&tx)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn user_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+fn migrate_hourly_tokens(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS hourly_tokens (
+            hour_key   TEXT PRIMARY KEY,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd   REAL NOT NULL DEFAULT 0.0
+        )",
+    )
+}
+
+fn migrate_hourly_usage_columns(conn: &Connection) -> rusqlite::Result<()> {
+    for column in USAGE_COLUMNS {
+        add_column_if_missing(conn, "hourly_tokens", column, "INTEGER NOT NULL DEFAULT 0")?;
+    }
+    Ok(())
+}
+
+fn migrate_event_records(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS events (
+            seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+            id          TEXT NOT NULL,
+            session_id  TEXT NOT NULL DEFAULT '',
+            agent_id    TEXT NOT NULL,
+            received_at TEXT NOT NULL,
+            cwd         TEXT NOT NULL DEFAULT '',
+            data        TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_events_session ON events (session_id, seq);
+        CREATE INDEX IF NOT EXISTS idx_events_received_at ON events (received_at);
+        CREATE TABLE IF NOT EXISTS sessions (
+            session_id          TEXT PRIMARY KEY,
+            last_seen           TEXT NOT NULL,
+            display_name_locked INTEGER NOT NULL DEFAULT 0,
+            data                TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sessions_last_seen ON sessions (last_seen);
+        CREATE TABLE IF NOT EXISTS agents (
+            agent_id               TEXT PRIMARY KEY,
+            last_seen              TEXT NOT NULL,
+            display_name_from_user INTEGER NOT NULL DEFAULT 0,
+            data                   TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS alerts (
+            id         TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL,
+            data       TEXT NOT NULL
+        );",
+    )
+}
+
+fn migrate_file_cursors(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS file_cursors (
+            path      TEXT PRIMARY KEY,
+            inode     INTEGER NOT NULL DEFAULT 0,
+            offset    INTEGER NOT NULL DEFAULT 0,
+            size      INTEGER NOT NULL DEFAULT 0,
+            remainder TEXT NOT NULL DEFAULT ''
+        )",
+    )
+}
+
+fn migrate_event_dedup_keys(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "events", "dedup_key", "TEXT")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_events_dedup_key ON events (dedup_key)")
+}
+
+/// Alerts now repeat in place, so retention and ordering follow the last repeat.
+fn migrate_alert_lifecycle(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "alerts", "last_seen", "TEXT NOT NULL DEFAULT ''")?;
+    conn.execute_batch(
+        "UPDATE alerts SET last_seen = created_at WHERE last_seen = '';
+        CREATE INDEX IF NOT EXISTS idx_alerts_last_seen ON alerts (last_seen)",
+    )
+}
+
+fn migrate_notification_deliveries(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS notification_deliveries (
+            seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+            alert_id    TEXT NOT NULL,
+            notifier_id TEXT NOT NULL,
+            status      TEXT NOT NULL,
+            attempts    INTEGER NOT NULL DEFAULT 0,
+            http_status INTEGER,
+            error       TEXT NOT NULL DEFAULT '',
+            sent_at     TEXT NOT NULL,
+            data        TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_notification_deliveries_sent_at
+            ON notification_deliveries (sent_at)",
+    )
+}
+
+fn migrate_hourly_scoped_usage(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS hourly_scoped_usage (
+            scope       TEXT NOT NULL,
+            name        TEXT NOT NULL,
+            hour_key    TEXT NOT NULL,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd    REAL NOT NULL DEFAULT 0.0,
+            PRIMARY KEY (scope, name, hour_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_hourly_scoped_usage_hour
+            ON hourly_scoped_usage (hour_key)",
+    )
+}
+
+fn migrate_projects(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS projects (
+            project_name TEXT PRIMARY KEY,
+            last_seen    TEXT NOT NULL,
+            data         TEXT NOT NULL
+        )",
+    )
+}
+
+fn migrate_scoped_usage_columns(conn: &Connection) -> rusqlite::Result<()> {
+    for column in USAGE_COLUMNS {
+        add_column_if_missing(
+            conn,
+            "hourly_scoped_usage",
+            column,
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+    }
+    Ok(())
+}
+
+/// Project rows used to carry every session and agent id; keep only counts.
+fn migrate_project_member_counts(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "UPDATE projects SET data = json_remove(
+             json_set(data,
+                 '$.sessionCount', json_array_length(data, '$.sessionIds'),
+                 '$.agentCount', json_array_length(data, '$.agentIds')),
+             '$.sessionIds', '$.agentIds')
+         WHERE json_type(data, '$.sessionIds') = 'array'
+           AND json_type(data, '$.agentIds') = 'array'",
+    )
+}
+
+fn migrate_scoped_request_counts(conn: &Connection) -> rusqlite::Result<()> {
+    for column in ["requests", "errors"] {
+        add_column_if_missing(
+            conn,
+            "hourly_scoped_usage",
+            column,
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+    }
+    Ok(())
+}
+
+/// Columns `GET /api/sessions` filters and sorts on, copied out of `data`.
+fn migrate_session_list_columns(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "sessions", "project_name", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(conn, "sessions", "cost_usd", "REAL NOT NULL DEFAULT 0.0")?;
+    add_column_if_missing(
+        conn,
+        "sessions",
+        "token_total",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "agents", "session_id", "TEXT NOT NULL DEFAULT ''")?;
+    conn.execute_batch(
+        "UPDATE sessions SET
+           project_name = COALESCE(json_extract(data, '$.projectName'), ''),
+           cost_usd = COALESCE(json_extract(data, '$.costUsd'), 0.0),
+           token_total = COALESCE(json_extract(data, '$.tokenTotal'), 0);
+        UPDATE agents SET session_id = COALESCE(json_extract(data, '$.sessionId'), '');
+        CREATE INDEX IF NOT EXISTS idx_sessions_project
+            ON sessions (project_name, last_seen);
+        CREATE INDEX IF NOT EXISTS idx_sessions_cost ON sessions (cost_usd);
+        CREATE INDEX IF NOT EXISTS idx_sessions_tokens ON sessions (token_total);
+        CREATE INDEX IF NOT EXISTS idx_agents_session ON agents (session_id)",
+    )
+}
+
+fn migrate_otel_series(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS otel_series (
+            series     TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL DEFAULT '',
+            value      REAL NOT NULL DEFAULT 0.0,
+            updated_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_otel_series_updated_at ON otel_series (updated_at)",
+    )
+}
+
+const USAGE_COLUMNS: [&str; 6] = [
+    "input_tokens",
+    "output_tokens",
+    "cache_read_tokens",
+    "cache_creation_tokens",
+    "web_search_requests",
+    "web_fetch_requests",
+];
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, decl
+        ))?;
+    }
+    Ok(())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn event_from_row(row: &rusqlite::Row) -> rusqlite::Result<Event> {
+    let mut evt: Event = from_json(&row.get::<_, String>(0)?)?;
+    evt.cwd = row.get(1)?;
+    Ok(evt)
+}
+
+fn usage_from_row(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<TokenBreakdown> {
+    let mut usage = TokenBreakdown::default();
+    usage.add(&TokenBreakdown {
+        input_tokens: row.get(offset)?,
+        output_tokens: row.get(offset + 1)?,
+        cache_read_input_tokens: row.get(offset + 2)?,
+        cache_creation_input_tokens: row.get(offset + 3)?,
+        web_search_requests: row.get(offset + 4)?,
+        web_fetch_requests: row.get(offset + 5)?,
+        cache_hit_ratio: 0.0,
+    });
+    Ok(usage)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,7 +1109,8 @@ mod tests {
     #[test]
     fn test_db_upsert_new_bucket() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T14", 100, 0.05).unwrap();
+        db.upsert_bucket("2025-01-01T14", 100, 0.05, &TokenBreakdown::default())
+            .unwrap();
         let rows = db.query_since("").unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].hour_key, "2025-01-01T14");
@@ -112,8 +1121,10 @@ mod tests {
     #[test]
     fn test_db_upsert_existing_accumulates() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T14", 100, 0.05).unwrap();
-        db.upsert_bucket("2025-01-01T14", 200, 0.10).unwrap();
+        db.upsert_bucket("2025-01-01T14", 100, 0.05, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T14", 200, 0.10, &TokenBreakdown::default())
+            .unwrap();
         let rows = db.query_since("").unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].token_total, 300);
@@ -123,9 +1134,12 @@ mod tests {
     #[test]
     fn test_db_query_since_filters() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T10", 10, 0.01).unwrap();
-        db.upsert_bucket("2025-01-01T12", 20, 0.02).unwrap();
-        db.upsert_bucket("2025-01-01T14", 30, 0.03).unwrap();
+        db.upsert_bucket("2025-01-01T10", 10, 0.01, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T12", 20, 0.02, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T14", 30, 0.03, &TokenBreakdown::default())
+            .unwrap();
         let rows = db.query_since("2025-01-01T12").unwrap();
         assert_eq!(rows.len(), 2);
         assert_eq!(rows[0].hour_key, "2025-01-01T12");
@@ -135,8 +1149,10 @@ mod tests {
     #[test]
     fn test_db_query_totals() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T10", 100, 0.05).unwrap();
-        db.upsert_bucket("2025-01-01T12", 200, 0.10).unwrap();
+        db.upsert_bucket("2025-01-01T10", 100, 0.05, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T12", 200, 0.10, &TokenBreakdown::default())
+            .unwrap();
         let (total_tokens, total_cost) = db.query_totals().unwrap();
         assert_eq!(total_tokens, 300);
         assert!((total_cost - 0.15).abs() < 1e-9);
@@ -145,9 +1161,12 @@ mod tests {
     #[test]
     fn test_db_prune_before() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T10", 10, 0.01).unwrap();
-        db.upsert_bucket("2025-01-01T12", 20, 0.02).unwrap();
-        db.upsert_bucket("2025-01-01T14", 30, 0.03).unwrap();
+        db.upsert_bucket("2025-01-01T10", 10, 0.01, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T12", 20, 0.02, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T14", 30, 0.03, &TokenBreakdown::default())
+            .unwrap();
         let deleted = db.prune_before("2025-01-01T12").unwrap();
         assert_eq!(deleted, 1);
         let rows = db.query_since("").unwrap();
@@ -159,8 +1178,13 @@ mod tests {
     fn test_db_restore_buckets_caps_at_max() {
         let (db, _dir) = open_temp_db();
         for i in 0..10 {
-            db.upsert_bucket(&format!("2025-01-01T{:02}", i), 10, 0.01)
-                .unwrap();
+            db.upsert_bucket(
+                &format!("2025-01-01T{:02}", i),
+                10,
+                0.01,
+                &TokenBreakdown::default(),
+            )
+            .unwrap();
         }
         let buckets = db.restore_buckets(5).unwrap();
         assert_eq!(buckets.len(), 5);
@@ -171,16 +1195,843 @@ mod tests {
     #[test]
     fn test_db_restore_buckets_under_max() {
         let (db, _dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T10", 10, 0.01).unwrap();
-        db.upsert_bucket("2025-01-01T11", 20, 0.02).unwrap();
+        db.upsert_bucket("2025-01-01T10", 10, 0.01, &TokenBreakdown::default())
+            .unwrap();
+        db.upsert_bucket("2025-01-01T11", 20, 0.02, &TokenBreakdown::default())
+            .unwrap();
         let buckets = db.restore_buckets(744).unwrap();
         assert_eq!(buckets.len(), 2);
     }
 
+    #[test]
+    fn test_db_upsert_accumulates_usage_breakdown() {
+        let (db, _dir) = open_temp_db();
+        let usage = TokenBreakdown {
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_read_input_tokens: 30,
+            cache_creation_input_tokens: 10,
+            web_search_requests: 1,
+            web_fetch_requests: 0,
+            cache_hit_ratio: 0.0,
+        };
+        db.upsert_bucket("2025-01-01T14", 15, 0.01, &usage).unwrap();
+        db.upsert_bucket("2025-01-01T14", 15, 0.01, &usage).unwrap();
+        let rows = db.query_since("").unwrap();
+        assert_eq!(rows[0].usage.input_tokens, 20);
+        assert_eq!(rows[0].usage.cache_read_input_tokens, 60);
+        assert_eq!(rows[0].usage.web_search_requests, 2);
+        assert!((rows[0].usage.cache_hit_ratio - 0.6).abs() < 1e-9);
+        let totals = db.query_usage_totals().unwrap();
+        assert_eq!(totals.cache_creation_input_tokens, 20);
+    }
+
+    #[test]
+    fn test_db_open_adds_usage_columns_to_old_table() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("old.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE hourly_tokens (
+                    hour_key   TEXT PRIMARY KEY,
+                    token_total INTEGER NOT NULL DEFAULT 0,
+                    cost_usd   REAL NOT NULL DEFAULT 0.0
+                );
+                INSERT INTO hourly_tokens VALUES ('2025-01-01T10', 42, 0.5);",
+            )
+            .unwrap();
+        }
+        let db = Db::open(&path).unwrap();
+        let rows = db.query_since("").unwrap();
+        assert_eq!(rows[0].token_total, 42);
+        assert_eq!(rows[0].usage, TokenBreakdown::default());
+    }
+
+    /// Schemas shipped before `user_version` was recorded, oldest first.
+    const LEGACY_FIXTURES: &[&str] = &[
+        // hourly buckets only
+        "CREATE TABLE hourly_tokens (
+            hour_key   TEXT PRIMARY KEY,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd   REAL NOT NULL DEFAULT 0.0
+        );
+        INSERT INTO hourly_tokens VALUES ('2025-01-01T10', 42, 0.5);",
+        // hourly buckets with the usage breakdown columns
+        "CREATE TABLE hourly_tokens (
+            hour_key   TEXT PRIMARY KEY,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd   REAL NOT NULL DEFAULT 0.0,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            web_search_requests INTEGER NOT NULL DEFAULT 0,
+            web_fetch_requests INTEGER NOT NULL DEFAULT 0
+        );
+        INSERT INTO hourly_tokens (hour_key, token_total, cost_usd, input_tokens)
+            VALUES ('2025-01-01T10', 42, 0.5, 30);",
+    ];
+
+    fn open_fixture(sql: &str) -> (Db, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fixture.db");
+        Connection::open(&path).unwrap().execute_batch(sql).unwrap();
+        (Db::open(&path).unwrap(), dir)
+    }
+
+    /// The schema each migration shipped, frozen as written at the time:
+    /// entry `i` took `user_version` `i` to `i + 1`. Replaying a prefix gives
+    /// the exact database an older release left on disk.
+    const SHIPPED_SCHEMAS: &[&str] = &[
+        // 1: hourly buckets
+        "CREATE TABLE hourly_tokens (
+            hour_key   TEXT PRIMARY KEY,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd   REAL NOT NULL DEFAULT 0.0
+        );",
+        // 2: usage breakdown columns
+        "ALTER TABLE hourly_tokens ADD COLUMN input_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_tokens ADD COLUMN output_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_tokens ADD COLUMN cache_read_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_tokens ADD COLUMN cache_creation_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_tokens ADD COLUMN web_search_requests INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_tokens ADD COLUMN web_fetch_requests INTEGER NOT NULL DEFAULT 0;",
+        // 3: event, session, agent and alert records
+        "CREATE TABLE events (
+            seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+            id          TEXT NOT NULL,
+            session_id  TEXT NOT NULL DEFAULT '',
+            agent_id    TEXT NOT NULL,
+            received_at TEXT NOT NULL,
+            cwd         TEXT NOT NULL DEFAULT '',
+            data        TEXT NOT NULL
+        );
+        CREATE INDEX idx_events_session ON events (session_id, seq);
+        CREATE INDEX idx_events_received_at ON events (received_at);
+        CREATE TABLE sessions (
+            session_id          TEXT PRIMARY KEY,
+            last_seen           TEXT NOT NULL,
+            display_name_locked INTEGER NOT NULL DEFAULT 0,
+            data                TEXT NOT NULL
+        );
+        CREATE INDEX idx_sessions_last_seen ON sessions (last_seen);
+        CREATE TABLE agents (
+            agent_id               TEXT PRIMARY KEY,
+            last_seen              TEXT NOT NULL,
+            display_name_from_user INTEGER NOT NULL DEFAULT 0,
+            data                   TEXT NOT NULL
+        );
+        CREATE TABLE alerts (
+            id         TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL,
+            data       TEXT NOT NULL
+        );",
+        // 4: transcript file cursors
+        "CREATE TABLE file_cursors (
+            path      TEXT PRIMARY KEY,
+            inode     INTEGER NOT NULL DEFAULT 0,
+            offset    INTEGER NOT NULL DEFAULT 0,
+            size      INTEGER NOT NULL DEFAULT 0,
+            remainder TEXT NOT NULL DEFAULT ''
+        );",
+        // 5: event dedup keys
+        "ALTER TABLE events ADD COLUMN dedup_key TEXT;
+        CREATE INDEX idx_events_dedup_key ON events (dedup_key);",
+        // 6: alerts that repeat in place
+        "ALTER TABLE alerts ADD COLUMN last_seen TEXT NOT NULL DEFAULT '';
+        UPDATE alerts SET last_seen = created_at WHERE last_seen = '';
+        CREATE INDEX idx_alerts_last_seen ON alerts (last_seen);",
+        // 7: notification deliveries
+        "CREATE TABLE notification_deliveries (
+            seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+            alert_id    TEXT NOT NULL,
+            notifier_id TEXT NOT NULL,
+            status      TEXT NOT NULL,
+            attempts    INTEGER NOT NULL DEFAULT 0,
+            http_status INTEGER,
+            error       TEXT NOT NULL DEFAULT '',
+            sent_at     TEXT NOT NULL,
+            data        TEXT NOT NULL
+        );
+        CREATE INDEX idx_notification_deliveries_sent_at
+            ON notification_deliveries (sent_at);",
+        // 8: per-scope hourly usage
+        "CREATE TABLE hourly_scoped_usage (
+            scope       TEXT NOT NULL,
+            name        TEXT NOT NULL,
+            hour_key    TEXT NOT NULL,
+            token_total INTEGER NOT NULL DEFAULT 0,
+            cost_usd    REAL NOT NULL DEFAULT 0.0,
+            PRIMARY KEY (scope, name, hour_key)
+        );
+        CREATE INDEX idx_hourly_scoped_usage_hour ON hourly_scoped_usage (hour_key);",
+        // 9: project rollups
+        "CREATE TABLE projects (
+            project_name TEXT PRIMARY KEY,
+            last_seen    TEXT NOT NULL,
+            data         TEXT NOT NULL
+        );",
+        // 10: per-scope usage breakdown columns
+        "ALTER TABLE hourly_scoped_usage ADD COLUMN input_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN output_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN cache_read_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN cache_creation_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN web_search_requests INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN web_fetch_requests INTEGER NOT NULL DEFAULT 0;",
+        // 11: project member counts instead of id lists (data only)
+        "UPDATE projects SET data = json_remove(
+             json_set(data,
+                 '$.sessionCount', json_array_length(data, '$.sessionIds'),
+                 '$.agentCount', json_array_length(data, '$.agentIds')),
+             '$.sessionIds', '$.agentIds')
+         WHERE json_type(data, '$.sessionIds') = 'array'
+           AND json_type(data, '$.agentIds') = 'array';",
+        // 12: per-scope request and error counts
+        "ALTER TABLE hourly_scoped_usage ADD COLUMN requests INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE hourly_scoped_usage ADD COLUMN errors INTEGER NOT NULL DEFAULT 0;",
+        // 13: session list columns
+        "ALTER TABLE sessions ADD COLUMN project_name TEXT NOT NULL DEFAULT '';
+        ALTER TABLE sessions ADD COLUMN cost_usd REAL NOT NULL DEFAULT 0.0;
+        ALTER TABLE sessions ADD COLUMN token_total INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE agents ADD COLUMN session_id TEXT NOT NULL DEFAULT '';
+        UPDATE sessions SET
+          project_name = COALESCE(json_extract(data, '$.projectName'), ''),
+          cost_usd = COALESCE(json_extract(data, '$.costUsd'), 0.0),
+          token_total = COALESCE(json_extract(data, '$.tokenTotal'), 0);
+        UPDATE agents SET session_id = COALESCE(json_extract(data, '$.sessionId'), '');
+        CREATE INDEX idx_sessions_project ON sessions (project_name, last_seen);
+        CREATE INDEX idx_sessions_cost ON sessions (cost_usd);
+        CREATE INDEX idx_sessions_tokens ON sessions (token_total);
+        CREATE INDEX idx_agents_session ON agents (session_id);",
+        // 14: last values of cumulative OTLP series
+        "CREATE TABLE otel_series (
+            series     TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL DEFAULT '',
+            value      REAL NOT NULL DEFAULT 0.0,
+            updated_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_otel_series_updated_at ON otel_series (updated_at);",
+    ];
+
+    /// A database as the release at `version` left it, holding one row in
+    /// every table that version had.
+    fn open_versioned_fixture(version: usize) -> (Db, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fixture.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            for sql in &SHIPPED_SCHEMAS[..version] {
+                conn.execute_batch(sql).unwrap();
+            }
+            if version >= 1 {
+                conn.execute(
+                    "INSERT INTO hourly_tokens (hour_key, token_total, cost_usd)
+                     VALUES ('2025-01-01T10', 42, 0.5)",
+                    [],
+                )
+                .unwrap();
+            }
+            if version >= 3 {
+                let at = "2025-01-01T00:00:00Z";
+                let event = sample_event("e1", "s1", at);
+                conn.execute(
+                    "INSERT INTO events (id, session_id, agent_id, received_at, cwd, data)
+                     VALUES ('e1', 's1', 'a1', ?1, ?2, ?3)",
+                    rusqlite::params![at, event.cwd, to_json(&event).unwrap()],
+                )
+                .unwrap();
+                conn.execute(
+                    "INSERT INTO sessions (session_id, last_seen, display_name_locked, data)
+                     VALUES ('s1', ?1, 1, ?2)",
+                    rusqlite::params![at, to_json(&sample_session(at)).unwrap()],
+                )
+                .unwrap();
+                conn.execute(
+                    "INSERT INTO agents (agent_id, last_seen, data) VALUES ('a1', ?1, ?2)",
+                    rusqlite::params![at, to_json(&sample_agent(at)).unwrap()],
+                )
+                .unwrap();
+                if version >= 13 {
+                    conn.execute_batch(
+                        "UPDATE sessions SET project_name = 'proj', cost_usd = 0.5, token_total = 10;
+                        UPDATE agents SET session_id = 's1';",
+                    )
+                    .unwrap();
+                }
+                let alert = AlertRow {
+                    id: "al1".to_string(),
+                    severity: "error".to_string(),
+                    created_at: at.to_string(),
+                    ..AlertRow::default()
+                };
+                conn.execute(
+                    "INSERT INTO alerts (id, created_at, data) VALUES ('al1', ?1, ?2)",
+                    rusqlite::params![at, to_json(&alert).unwrap()],
+                )
+                .unwrap();
+            }
+            if version >= 4 {
+                conn.execute(
+                    "INSERT INTO file_cursors (path, inode, offset, size)
+                     VALUES ('/tmp/t.jsonl', 7, 100, 100)",
+                    [],
+                )
+                .unwrap();
+            }
+            if version >= 8 {
+                conn.execute(
+                    "INSERT INTO hourly_scoped_usage (scope, name, hour_key, token_total, cost_usd)
+                     VALUES ('model', 'm', '2025-01-01T10', 42, 0.5)",
+                    [],
+                )
+                .unwrap();
+            }
+            if version >= 9 {
+                let members = if version < 11 {
+                    r#""sessionIds":["s1","s2"],"agentIds":["a1"]"#
+                } else {
+                    r#""sessionCount":2,"agentCount":1"#
+                };
+                conn.execute(
+                    "INSERT INTO projects (project_name, last_seen, data) VALUES ('proj', 't', ?1)",
+                    [format!(
+                        r#"{{"projectName":"proj","lastSeen":"t","total":3,"ok":3,"warning":0,
+                        "error":0,"tokenTotal":0,"costUsd":0.0,"usage":{{}},{},
+                        "toolUseCounts":{{}}}}"#,
+                        members
+                    )],
+                )
+                .unwrap();
+            }
+            conn.pragma_update(None, "user_version", version as i64)
+                .unwrap();
+        }
+        (Db::open(&path).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_db_new_database_is_at_current_version() {
+        let (db, _dir) = open_temp_db();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_db_migrates_legacy_fixtures() {
+        for sql in LEGACY_FIXTURES {
+            let (db, _dir) = open_fixture(sql);
+            assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+            let rows = db.query_since("").unwrap();
+            assert_eq!(rows[0].token_total, 42);
+            db.upsert_bucket("2025-01-01T10", 1, 0.0, &TokenBreakdown::default())
+                .unwrap();
+            assert!(db.load_recent_events(1).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_db_shipped_schemas_cover_every_version() {
+        assert_eq!(SHIPPED_SCHEMAS.len() as i64, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_db_upgrades_every_shipped_version() {
+        for version in 1..SHIPPED_SCHEMAS.len() {
+            let (db, _dir) = open_versioned_fixture(version);
+            assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION, "v{}", version);
+            assert_eq!(db.query_since("").unwrap()[0].token_total, 42);
+            db.upsert_bucket("2025-01-01T11", 1, 0.0, &TokenBreakdown::default())
+                .unwrap();
+            if version >= 3 {
+                assert_eq!(db.load_recent_events(10).unwrap()[0].id, "e1");
+                let filter = SessionFilter {
+                    project: Some("proj"),
+                    min_cost: Some(0.5),
+                    ..SessionFilter::default()
+                };
+                assert_eq!(db.count_sessions(&filter).unwrap(), 1, "v{}", version);
+                assert_eq!(db.load_agents(10).unwrap().len(), 1);
+                let alerts = db.load_alerts(10).unwrap();
+                assert_eq!(alerts[0].id, "al1");
+            }
+            if version >= 4 {
+                assert_eq!(db.load_cursors().unwrap().len(), 1);
+            }
+            if version >= 8 {
+                assert_eq!(db.load_model_totals().unwrap()["m"].token_total, 42);
+            }
+            if version >= 9 {
+                let project = &db.load_projects().unwrap()[0];
+                assert_eq!((project.session_count, project.agent_count), (2, 1));
+            }
+            db.record_event(
+                &sample_event("e2", "s2", "2025-01-02T00:00:00Z"),
+                &sample_agent("2025-01-02T00:00:00Z"),
+                None,
+                None,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_db_migrates_unversioned_database_with_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("records.db");
+        {
+            let db = Db::open(&path).unwrap();
+            db.record_event(
+                &sample_event("e1", "s1", "2025-01-01T00:00:00Z"),
+                &sample_agent("2025-01-01T00:00:00Z"),
+                None,
+                None,
+            )
+            .unwrap();
+            db.conn.pragma_update(None, "user_version", 0).unwrap();
+        }
+        let db = Db::open(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.load_recent_events(10).unwrap()[0].id, "e1");
+    }
+
+    #[test]
+    fn test_db_resumes_from_intermediate_version() {
+        let (db, dir) = open_fixture(LEGACY_FIXTURES[0]);
+        db.conn.execute_batch("DROP TABLE events").unwrap();
+        db.conn.pragma_update(None, "user_version", 2).unwrap();
+        drop(db);
+        let db = Db::open(&dir.path().join("fixture.db")).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert!(db.load_recent_events(1).unwrap().is_empty());
+        assert!(db.load_cursors().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_db_refuses_newer_schema() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("future.db");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        let err = Db::open(&path).err().expect("newer schema must be refused");
+        assert!(err.to_string().contains("newer than supported"));
+    }
+
+    fn sample_event(id: &str, session_id: &str, received_at: &str) -> Event {
+        Event {
+            id: id.to_string(),
+            agent_id: "a1".to_string(),
+            event: "user_message".to_string(),
+            status: "ok".to_string(),
+            latency_ms: Some(12),
+            message: "hello".to_string(),
+            metadata: serde_json::json!({ "source": "claude_session" }),
+            timestamp: received_at.to_string(),
+            received_at: received_at.to_string(),
+            model: "m".to_string(),
+            is_sidechain: false,
+            session_id: session_id.to_string(),
+            cwd: "/home/user/proj".to_string(),
+            dedup_key: None,
+        }
+    }
+
+    fn sample_agent(last_seen: &str) -> AgentRow {
+        AgentRow {
+            agent_id: "a1".to_string(),
+            last_seen: last_seen.to_string(),
+            total: 1,
+            ok: 1,
+            last_event: "user_message".to_string(),
+            session_id: "s1".to_string(),
+            tool_use_counts: std::collections::HashMap::from([("Bash".to_string(), 2)]),
+            display_name: "Fix bug".to_string(),
+            display_name_from_user: true,
+            ..AgentRow::default()
+        }
+    }
+
+    fn sample_session(last_seen: &str) -> SessionRow {
+        SessionRow {
+            session_id: "s1".to_string(),
+            last_seen: last_seen.to_string(),
+            token_total: 10,
+            cost_usd: 0.5,
+            agent_ids: vec!["a1".to_string()],
+            display_name: "Fix bug".to_string(),
+            project_name: "proj".to_string(),
+            short_session_id: "s1".to_string(),
+            display_name_locked: true,
+            ..SessionRow::default()
+        }
+    }
+
+    #[test]
+    fn test_db_record_event_roundtrip() {
+        let (db, _dir) = open_temp_db();
+        let alert = AlertRow {
+            id: "a7".to_string(),
+            severity: "error".to_string(),
+            agent_id: "a1".to_string(),
+            session_id: "s1".to_string(),
+            event: "tool_result".to_string(),
+            message: "boom".to_string(),
+            created_at: "2025-01-01T00:00:01Z".to_string(),
+            ..AlertRow::default()
+        };
+        db.record_event(
+            &sample_event("e1", "s1", "2025-01-01T00:00:00Z"),
+            &sample_agent("2025-01-01T00:00:00Z"),
+            Some(&sample_session("2025-01-01T00:00:00Z")),
+            None,
+        )
+        .unwrap();
+        db.record_event(
+            &sample_event("e9", "s1", "2025-01-01T00:00:01Z"),
+            &sample_agent("2025-01-01T00:00:01Z"),
+            Some(&sample_session("2025-01-01T00:00:01Z")),
+            Some(&alert),
+        )
+        .unwrap();
+
+        let recent = db.load_recent_events(10).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].id, "e9");
+        assert_eq!(recent[0].cwd, "/home/user/proj");
+        assert_eq!(recent[0].latency_ms, Some(12));
+
+        let timeline = db.load_session_events("s1", 10).unwrap();
+        assert_eq!(timeline[0].id, "e1");
+
+        let sessions = db.load_sessions(10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].last_seen, "2025-01-01T00:00:01Z");
+        assert!(sessions[0].display_name_locked);
+
+        let agents = db.load_agents(10).unwrap();
+        assert!(agents[0].display_name_from_user);
+        assert_eq!(agents[0].tool_use_counts["Bash"], 2);
+
+        assert_eq!(db.load_alerts(10).unwrap()[0].message, "boom");
+        assert_eq!(db.max_id_sequence().unwrap(), 9);
+    }
+
+    #[test]
+    fn test_db_load_session_events_keeps_newest() {
+        let (db, _dir) = open_temp_db();
+        for i in 0..5 {
+            db.record_event(
+                &sample_event(&format!("e{}", i), "s1", "2025-01-01T00:00:00Z"),
+                &sample_agent("2025-01-01T00:00:00Z"),
+                None,
+                None,
+            )
+            .unwrap();
+        }
+        let ids: Vec<String> = db
+            .load_session_events("s1", 2)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(ids, vec!["e3", "e4"]);
+    }
+
+    #[test]
+    fn test_db_prune_records_before() {
+        let (db, _dir) = open_temp_db();
+        db.record_event(
+            &sample_event("e1", "s1", "2025-01-01T00:00:00Z"),
+            &sample_agent("2025-01-01T00:00:00Z"),
+            Some(&sample_session("2025-01-01T00:00:00Z")),
+            None,
+        )
+        .unwrap();
+        db.record_event(
+            &sample_event("e2", "s2", "2025-03-01T00:00:00Z"),
+            &sample_agent("2025-03-01T00:00:00Z"),
+            None,
+            None,
+        )
+        .unwrap();
+        db.prune_records_before("2025-02-01T00:00:00Z").unwrap();
+        let recent = db.load_recent_events(10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, "e2");
+        assert!(db.load_sessions(10).unwrap().is_empty());
+        assert_eq!(db.load_agents(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_project_member_counts_replaces_id_lists() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate_projects(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (project_name, last_seen, data) VALUES ('web', 't', ?1)",
+            [
+                r#"{"projectName":"web","lastSeen":"t","total":3,"ok":3,"warning":0,"error":0,
+                "tokenTotal":0,"costUsd":0.0,"usage":{},"sessionIds":["s1","s2"],
+                "agentIds":["a1"],"toolUseCounts":{}}"#,
+            ],
+        )
+        .unwrap();
+        migrate_project_member_counts(&conn).unwrap();
+        migrate_project_member_counts(&conn).unwrap();
+        let data: String = conn
+            .query_row("SELECT data FROM projects", [], |row| row.get(0))
+            .unwrap();
+        let project: ProjectRow = serde_json::from_str(&data).unwrap();
+        assert_eq!((project.session_count, project.agent_count), (2, 1));
+        assert!(!data.contains("sessionIds"));
+    }
+
+    #[test]
+    fn test_migrate_session_list_columns_backfills_from_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate_event_records(&conn).unwrap();
+        let session = sample_session("2025-01-01T00:00:00Z");
+        conn.execute(
+            "INSERT INTO sessions (session_id, last_seen, data) VALUES ('s1', ?1, ?2)",
+            rusqlite::params![session.last_seen, to_json(&session).unwrap()],
+        )
+        .unwrap();
+        migrate_session_list_columns(&conn).unwrap();
+        migrate_session_list_columns(&conn).unwrap();
+        let db = Db { conn };
+        let filter = SessionFilter {
+            project: Some("proj"),
+            min_cost: Some(0.5),
+            ..SessionFilter::default()
+        };
+        assert_eq!(db.count_sessions(&filter).unwrap(), 1);
+        let page = db
+            .page_sessions(&filter, SessionOrder::Cost, false, None, 10)
+            .unwrap();
+        assert_eq!(page[0].session_id, "s1");
+    }
+
+    #[test]
+    fn test_db_projects_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(&dir.path().join("monitor.db")).unwrap();
+        let mut project = ProjectRow {
+            project_name: "web".to_string(),
+            last_seen: "2025-01-01T10:00:00Z".to_string(),
+            total: 1,
+            ..ProjectRow::default()
+        };
+        db.upsert_project(&project).unwrap();
+        project.total = 2;
+        project.tool_use_counts.insert("Bash".to_string(), 1);
+        db.upsert_project(&project).unwrap();
+
+        let projects = db.load_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].total, 2);
+        assert_eq!(projects[0].tool_use_counts["Bash"], 1);
+    }
+
+    #[test]
+    fn test_db_scoped_buckets_accumulate_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(&dir.path().join("monitor.db")).unwrap();
+        let usage = TokenBreakdown {
+            input_tokens: 40,
+            output_tokens: 10,
+            ..TokenBreakdown::default()
+        };
+        let delta = |token_total, cost_usd, usage| ModelUsage {
+            requests: 1,
+            token_total,
+            cost_usd,
+            usage,
+            ..ModelUsage::default()
+        };
+        db.upsert_scoped_bucket("project", "web", "2025-01-01T10", &delta(100, 0.5, usage))
+            .unwrap();
+        db.upsert_scoped_bucket("project", "web", "2025-01-01T10", &delta(50, 0.25, usage))
+            .unwrap();
+        let opus = delta(10, 1.0, TokenBreakdown::default());
+        db.upsert_scoped_bucket("model", "claude-opus-4", "2025-01-01T12", &opus)
+            .unwrap();
+        db.upsert_scoped_bucket("model", "claude-opus-4", "2025-01-02T00", &opus)
+            .unwrap();
+        let totals = db.load_model_totals().unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals["claude-opus-4"].requests, 2);
+        assert_eq!(totals["claude-opus-4"].token_total, 20);
+
+        let rows = db.query_scoped_since("2025-01-01T00").unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].0, "project");
+        assert_eq!(rows[0].1, "web");
+        assert_eq!(rows[0].2.token_total, 150);
+        assert!((rows[0].2.cost_usd - 0.75).abs() < 1e-9);
+        assert_eq!(rows[0].2.usage.input_tokens, 80);
+        assert_eq!(rows[0].2.usage.output_tokens, 20);
+
+        db.prune_before("2025-01-02T00").unwrap();
+        let rows = db.query_scoped_since("").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1, "claude-opus-4");
+    }
+
+    #[test]
+    fn test_db_records_notification_deliveries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(&dir.path().join("monitor.db")).unwrap();
+        let delivery = NotificationDelivery {
+            alert_id: "a1".to_string(),
+            notifier_id: "ops".to_string(),
+            kind: crate::notify::NotifierKind::Slack,
+            status: "failed".to_string(),
+            attempts: 3,
+            http_status: Some(503),
+            exit_code: None,
+            error: "HTTP 503".to_string(),
+            sent_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        db.apply_batch(&[DbWrite::Delivery(Box::new(delivery))])
+            .unwrap();
+        let (notifier, status, attempts, http_status): (String, String, u32, Option<u16>) = db
+            .conn
+            .query_row(
+                "SELECT notifier_id, status, attempts, http_status FROM notification_deliveries",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(notifier, "ops");
+        assert_eq!(status, "failed");
+        assert_eq!(attempts, 3);
+        assert_eq!(http_status, Some(503));
+
+        assert_eq!(db.prune_records_before("2025-02-01T00:00:00Z").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_db_alert_upsert_tracks_last_seen() {
+        let (db, _dir) = open_temp_db();
+        let mut old = AlertRow {
+            id: "a1".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            last_seen: "2025-01-01T00:00:00Z".to_string(),
+            status: "open".to_string(),
+            count: 1,
+            ..AlertRow::default()
+        };
+        let newer = AlertRow {
+            id: "a2".to_string(),
+            created_at: "2025-01-02T00:00:00Z".to_string(),
+            last_seen: "2025-01-02T00:00:00Z".to_string(),
+            ..old.clone()
+        };
+        db.apply_batch(&[
+            DbWrite::Alert(Box::new(old.clone())),
+            DbWrite::Alert(Box::new(newer)),
+        ])
+        .unwrap();
+        old.count = 5;
+        old.last_seen = "2025-03-01T00:00:00Z".to_string();
+        old.status = "acked".to_string();
+        db.apply(&DbWrite::Alert(Box::new(old))).unwrap();
+
+        let alerts = db.load_alerts(10).unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].id, "a1");
+        assert_eq!(alerts[0].count, 5);
+        assert_eq!(alerts[0].status, "acked");
+
+        // retention follows the last repeat, not the first occurrence
+        db.prune_records_before("2025-02-01T00:00:00Z").unwrap();
+        let alerts = db.load_alerts(10).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].id, "a1");
+    }
+
+    #[test]
+    fn test_db_loads_budget_fingerprints_beyond_alert_window() {
+        let (db, _dir) = open_temp_db();
+        let alert = |id: &str, created_at: &str, fingerprint: &str| {
+            DbWrite::Alert(Box::new(AlertRow {
+                id: id.to_string(),
+                created_at: created_at.to_string(),
+                last_seen: created_at.to_string(),
+                fingerprint: fingerprint.to_string(),
+                ..AlertRow::default()
+            }))
+        };
+        let mut writes = vec![
+            alert("b1", "2025-01-15T01:00:00Z", "budget:g|2025-01-15T00|warn"),
+            alert("b0", "2024-11-01T00:00:00Z", "budget:g|2024-11-01T00|warn"),
+        ];
+        writes
+            .extend((0..200).map(|i| alert(&format!("r{}", i), "2025-01-15T02:00:00Z", "rule:x")));
+        db.apply_batch(&writes).unwrap();
+
+        let fired = db
+            .load_alert_fingerprints("budget:", "2024-12-15T00:00:00Z")
+            .unwrap();
+        assert_eq!(
+            fired,
+            HashSet::from(["budget:g|2025-01-15T00|warn".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_db_legacy_alerts_get_lifecycle_defaults() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("legacy.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            migrate_event_records(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO alerts (id, created_at, data) VALUES ('a3', '2025-01-01T00:00:00Z', ?1)",
+                [r#"{"id":"a3","severity":"error","agentId":"x","sessionId":"s","event":"tool_result","message":"m","createdAt":"2025-01-01T00:00:00Z"}"#],
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 3).unwrap();
+        }
+        let db = Db::open(&path).unwrap();
+        let alerts = db.load_alerts(10).unwrap();
+        assert_eq!(alerts[0].fingerprint, "x|s|tool_result");
+        assert_eq!(alerts[0].status, "open");
+        assert_eq!(alerts[0].count, 1);
+        assert_eq!(alerts[0].last_seen, "2025-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_db_cursor_roundtrip() {
+        let (db, _dir) = open_temp_db();
+        let path = PathBuf::from("/tmp/projects/p/s.jsonl");
+        let mut cursor = FileCursor {
+            offset: 120,
+            remainder: "{\"type\":\"us".to_string(),
+            inode: 42,
+            size: 120,
+            held_back: false,
+        };
+        db.save_cursor(&path, &cursor).unwrap();
+        cursor.offset = 200;
+        cursor.size = 200;
+        db.save_cursor(&path, &cursor).unwrap();
+        let cursors = db.load_cursors().unwrap();
+        assert_eq!(cursors.len(), 1);
+        assert_eq!(cursors[&path], cursor);
+    }
+
+    #[test]
+    fn test_db_max_id_sequence_empty() {
+        let (db, _dir) = open_temp_db();
+        assert_eq!(db.max_id_sequence().unwrap(), 0);
+    }
+
     #[test]
     fn test_db_reopen_preserves_data() {
         let (db, dir) = open_temp_db();
-        db.upsert_bucket("2025-01-01T14", 100, 0.05).unwrap();
+        db.upsert_bucket("2025-01-01T14", 100, 0.05, &TokenBreakdown::default())
+            .unwrap();
         drop(db);
 
         let db2 = Db::open(&dir.path().join("test.db")).unwrap();
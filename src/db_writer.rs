@@ -0,0 +1,254 @@
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::db::{Db, DbWrite};
+
+const QUEUE_CAPACITY: usize = 10_000;
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Message {
+    Write(DbWrite),
+    Flush(mpsc::Sender<()>),
+}
+
+#[derive(Default)]
+struct WriterStats {
+    written: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+    batches: AtomicU64,
+}
+
+/// Handle to the background thread that owns the write connection.
+/// Writes are queued without blocking and committed in batches of up to
+/// `batch_rows` rows or every `batch_ms` milliseconds, whichever comes first.
+#[derive(Clone)]
+pub struct DbWriter {
+    tx: SyncSender<Message>,
+    stats: Arc<WriterStats>,
+}
+
+impl DbWriter {
+    pub fn spawn(db: Db, batch_ms: u64, batch_rows: usize) -> (Self, JoinHandle<()>) {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let stats = Arc::new(WriterStats::default());
+        let thread_stats = stats.clone();
+        let handle = thread::spawn(move || {
+            run_writer(
+                db,
+                rx,
+                Duration::from_millis(batch_ms),
+                batch_rows.max(1),
+                &thread_stats,
+            )
+        });
+        (Self { tx, stats }, handle)
+    }
+
+    /// Queues a write. Never blocks: when the queue is full the write is dropped and counted.
+    pub fn send(&self, write: DbWrite) {
+        match self.tx.try_send(Message::Write(write)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Writes dropped so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until everything queued before this call has been committed.
+    pub fn flush(&self) -> bool {
+        let (ack_tx, ack_rx) = mpsc::channel();
+        if self.tx.send(Message::Flush(ack_tx)).is_err() {
+            return false;
+        }
+        ack_rx.recv_timeout(FLUSH_TIMEOUT).is_ok()
+    }
+
+    pub fn health(&self) -> Value {
+        json!({
+            "written": self.stats.written.load(Ordering::Relaxed),
+            "failed": self.stats.failed.load(Ordering::Relaxed),
+            "dropped": self.stats.dropped.load(Ordering::Relaxed),
+            "batches": self.stats.batches.load(Ordering::Relaxed),
+        })
+    }
+}
+
+fn run_writer(
+    db: Db,
+    rx: Receiver<Message>,
+    batch_window: Duration,
+    batch_rows: usize,
+    stats: &WriterStats,
+) {
+    let mut batch: Vec<DbWrite> = Vec::with_capacity(batch_rows);
+    let mut waiters: Vec<mpsc::Sender<()>> = Vec::new();
+    loop {
+        let first = match rx.recv() {
+            Ok(msg) => msg,
+            Err(_) => break,
+        };
+        let deadline = Instant::now() + batch_window;
+        let mut disconnected = false;
+        let mut next = Some(first);
+        while let Some(msg) = next.take() {
+            match msg {
+                Message::Write(write) => batch.push(write),
+                Message::Flush(ack) => {
+                    waiters.push(ack);
+                    break;
+                }
+            }
+            if batch.len() >= batch_rows {
+                break;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(remaining) {
+                Ok(msg) => next = Some(msg),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => disconnected = true,
+            }
+        }
+        commit_batch(&db, &mut batch, stats);
+        for ack in waiters.drain(..) {
+            let _ = ack.send(());
+        }
+        if disconnected {
+            break;
+        }
+    }
+    commit_batch(&db, &mut batch, stats);
+}
+
+/// Commits the batch in one transaction. If that fails, rows are retried one
+/// by one so a single bad row does not take the rest of the batch with it.
+fn commit_batch(db: &Db, batch: &mut Vec<DbWrite>, stats: &WriterStats) {
+    if batch.is_empty() {
+        return;
+    }
+    stats.batches.fetch_add(1, Ordering::Relaxed);
+    match db.apply_batch(batch) {
+        Ok(()) => {
+            stats
+                .written
+                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+        }
+        Err(e) => {
+            eprintln!("[db] batch of {} failed: {e} — retrying rows", batch.len());
+            for write in batch.iter() {
+                match db.apply(write) {
+                    Ok(()) => stats.written.fetch_add(1, Ordering::Relaxed),
+                    Err(e) => {
+                        eprintln!("[db] write failed: {e}");
+                        stats.failed.fetch_add(1, Ordering::Relaxed)
+                    }
+                };
+            }
+        }
+    }
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TokenBreakdown;
+
+    fn bucket(hour_key: &str, tokens: u64) -> DbWrite {
+        DbWrite::Bucket {
+            hour_key: hour_key.to_string(),
+            tokens,
+            cost: 0.0,
+            usage: TokenBreakdown::default(),
+        }
+    }
+
+    fn spawn_writer(dir: &tempfile::TempDir, batch_ms: u64, rows: usize) -> (DbWriter, Db) {
+        let path = dir.path().join("test.db");
+        let reader = Db::open(&path).unwrap();
+        let (writer, _handle) = DbWriter::spawn(Db::open(&path).unwrap(), batch_ms, rows);
+        (writer, reader)
+    }
+
+    #[test]
+    fn test_writer_flush_commits_pending_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (writer, reader) = spawn_writer(&dir, 60_000, 1_000);
+        for _ in 0..10 {
+            writer.send(bucket("2025-01-01T10", 5));
+        }
+        assert!(writer.flush());
+        assert_eq!(reader.query_since("").unwrap()[0].token_total, 50);
+        assert_eq!(writer.health()["written"], 10);
+        assert_eq!(writer.health()["batches"], 1);
+    }
+
+    #[test]
+    fn test_writer_batches_by_row_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let (writer, _reader) = spawn_writer(&dir, 60_000, 4);
+        for _ in 0..8 {
+            writer.send(bucket("2025-01-01T10", 1));
+        }
+        assert!(writer.flush());
+        assert_eq!(writer.health()["written"], 8);
+        assert_eq!(writer.health()["batches"], 2);
+    }
+
+    #[test]
+    fn test_writer_commits_after_batch_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let (writer, reader) = spawn_writer(&dir, 20, 1_000);
+        writer.send(bucket("2025-01-01T10", 7));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while reader.query_since("").unwrap().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(reader.query_since("").unwrap()[0].token_total, 7);
+    }
+
+    #[test]
+    fn test_writer_drains_queue_when_last_handle_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let (writer, handle) = DbWriter::spawn(Db::open(&path).unwrap(), 60_000, 1_000);
+        writer.send(bucket("2025-01-01T10", 3));
+        drop(writer);
+        handle.join().unwrap();
+        let reader = Db::open(&path).unwrap();
+        assert_eq!(reader.query_since("").unwrap()[0].token_total, 3);
+    }
+
+    #[test]
+    fn test_writer_counts_failed_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let (writer, reader) = spawn_writer(&dir, 60_000, 1_000);
+        reader.apply(&bucket("seed", 0)).unwrap();
+        drop(reader);
+        {
+            let conn = rusqlite::Connection::open(dir.path().join("test.db")).unwrap();
+            conn.execute_batch(
+                "CREATE TRIGGER reject_bad BEFORE INSERT ON hourly_tokens
+                 WHEN NEW.hour_key = 'bad'
+                 BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+            )
+            .unwrap();
+        }
+        writer.send(bucket("2025-01-01T10", 1));
+        writer.send(bucket("bad", 1));
+        writer.send(bucket("2025-01-01T11", 1));
+        assert!(writer.flush());
+        let health = writer.health();
+        assert_eq!(health["written"], 2);
+        assert_eq!(health["failed"], 1);
+    }
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::budgets::{hour_key, period_bounds, spend_between, BudgetPeriod};
+use crate::state::MAX_HOUR_BUCKETS;
+use crate::types::{HourBucket, State};
+
+const HOURS_PER_WEEK: usize = 168;
+
+/// The `forecast` section of `monitor.json`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ForecastConfig {
+    /// Trailing windows, in hours, over which burn rate is reported.
+    pub burn_rate_windows_hours: Vec<u64>,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            burn_rate_windows_hours: vec![1, 6, 24],
+        }
+    }
+}
+
+pub fn validate_forecast(config: &ForecastConfig) -> Result<(), String> {
+    for &hours in &config.burn_rate_windows_hours {
+        if hours == 0 || hours as usize > MAX_HOUR_BUCKETS {
+            return Err(format!(
+                "forecast: burnRateWindowsHours must be between 1 and {}",
+                MAX_HOUR_BUCKETS
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BurnRate {
+    pub window_hours: u64,
+    pub cost_usd: f64,
+    pub tokens: u64,
+    pub cost_usd_per_hour: f64,
+    pub tokens_per_hour: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodProjection {
+    pub period: BudgetPeriod,
+    pub period_start: String,
+    pub period_end: String,
+    pub spent_usd: f64,
+    /// Spent so far plus the expected spend of every remaining hour, taken
+    /// from the average of the same hour-of-week in the history.
+    pub projected_usd: f64,
+    /// Spent so far extrapolated at the period's average pace.
+    pub linear_usd: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendForecast {
+    /// Complete hours of history the seasonal model was built from.
+    pub history_hours: u64,
+    pub burn_rates: Vec<BurnRate>,
+    pub projections: Vec<PeriodProjection>,
+}
+
+fn parse_hour_key(key: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(&format!("{}:00:00Z", key), &Rfc3339).ok()
+}
+
+fn hour_start(at: OffsetDateTime) -> OffsetDateTime {
+    at.replace_minute(0)
+        .and_then(|t| t.replace_second(0))
+        .and_then(|t| t.replace_nanosecond(0))
+        .unwrap_or(at)
+}
+
+/// Monday 00:00 UTC is slot 0.
+fn hour_of_week(at: OffsetDateTime) -> usize {
+    at.weekday().number_days_from_monday() as usize * 24 + at.hour() as usize
+}
+
+/// Average spend per hour-of-week over the complete hours before `now`.
+struct SeasonalModel {
+    slots: [Option<f64>; HOURS_PER_WEEK],
+    /// Used for slots the history has not covered yet.
+    fallback: f64,
+    history_hours: u64,
+}
+
+impl SeasonalModel {
+    fn build(buckets: &[HourBucket], now: OffsetDateTime) -> Self {
+        let current = hour_start(now);
+        let by_hour: HashMap<&str, f64> = buckets
+            .iter()
+            .map(|b| (b.hour_key.as_str(), b.cost_usd))
+            .collect();
+        let first = buckets
+            .iter()
+            .filter_map(|b| parse_hour_key(&b.hour_key))
+            .filter(|at| *at < current)
+            .min();
+        let mut sums = [0.0; HOURS_PER_WEEK];
+        let mut counts = [0u32; HOURS_PER_WEEK];
+        let mut history_hours = 0;
+        let mut total = 0.0;
+        if let Some(mut at) = first {
+            while at < current {
+                // Hours without a bucket spent nothing.
+                let cost = by_hour.get(hour_key(at).as_str()).copied().unwrap_or(0.0);
+                sums[hour_of_week(at)] += cost;
+                counts[hour_of_week(at)] += 1;
+                total += cost;
+                history_hours += 1;
+                at += Duration::hours(1);
+            }
+        }
+        let mut slots = [None; HOURS_PER_WEEK];
+        for (slot, value) in slots.iter_mut().enumerate() {
+            if counts[slot] > 0 {
+                *value = Some(sums[slot] / counts[slot] as f64);
+            }
+        }
+        Self {
+            slots,
+            fallback: if history_hours > 0 {
+                total / history_hours as f64
+            } else {
+                0.0
+            },
+            history_hours,
+        }
+    }
+
+    fn expected(&self, at: OffsetDateTime) -> f64 {
+        self.slots[hour_of_week(at)].unwrap_or(self.fallback)
+    }
+}
+
+/// Cost and tokens of the last `hours` hours, the current partial hour included.
+pub fn burn_rate(buckets: &[HourBucket], hours: u64, now: OffsetDateTime) -> BurnRate {
+    let since = hour_key(hour_start(now) - Duration::hours(hours as i64 - 1));
+    let (cost_usd, tokens) = buckets
+        .iter()
+        .filter(|b| b.hour_key >= since)
+        .fold((0.0, 0), |(cost, tokens), b| {
+            (cost + b.cost_usd, tokens + b.token_total)
+        });
+    BurnRate {
+        window_hours: hours,
+        cost_usd,
+        tokens,
+        cost_usd_per_hour: cost_usd / hours as f64,
+        tokens_per_hour: tokens as f64 / hours as f64,
+    }
+}
+
+fn project_period(
+    buckets: &[HourBucket],
+    model: &SeasonalModel,
+    period: BudgetPeriod,
+    now: OffsetDateTime,
+) -> PeriodProjection {
+    let (start, end) = period_bounds(period, now);
+    let spent = spend_between(buckets, &hour_key(start), &hour_key(end));
+
+    let current = hour_start(now);
+    let left_in_hour = 1.0 - (now - current).as_seconds_f64() / 3600.0;
+    let mut remaining = model.expected(current) * left_in_hour;
+    let mut at = current + Duration::hours(1);
+    while at < end {
+        remaining += model.expected(at);
+        at += Duration::hours(1);
+    }
+
+    let elapsed_hours = ((now - start).as_seconds_f64() / 3600.0).max(1.0);
+    let total_hours = (end - start).as_seconds_f64() / 3600.0;
+    PeriodProjection {
+        period,
+        period_start: start.format(&Rfc3339).unwrap_or_default(),
+        period_end: end.format(&Rfc3339).unwrap_or_default(),
+        spent_usd: spent,
+        projected_usd: spent + remaining,
+        linear_usd: (spent / elapsed_hours * total_hours).max(spent),
+    }
+}
+
+/// Burn rates over the configured windows and end-of-day/week/month spend
+/// projections from the global hourly buckets.
+pub fn spend_forecast(
+    state: &State,
+    config: &ForecastConfig,
+    now: OffsetDateTime,
+) -> SpendForecast {
+    let buckets = &state.hourly_buckets;
+    let model = SeasonalModel::build(buckets, now);
+    SpendForecast {
+        history_hours: model.history_hours,
+        burn_rates: config
+            .burn_rate_windows_hours
+            .iter()
+            .map(|&hours| burn_rate(buckets, hours, now))
+            .collect(),
+        projections: [
+            BudgetPeriod::Daily,
+            BudgetPeriod::Weekly,
+            BudgetPeriod::Monthly,
+        ]
+        .into_iter()
+        .map(|period| project_period(buckets, &model, period, now))
+        .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TokenBreakdown;
+
+    fn at(iso: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(iso, &Rfc3339).unwrap()
+    }
+
+    fn bucket(hour_key: &str, tokens: u64, cost_usd: f64) -> HourBucket {
+        HourBucket {
+            hour_key: hour_key.to_string(),
+            token_total: tokens,
+            cost_usd,
+            usage: TokenBreakdown::default(),
+        }
+    }
+
+    #[test]
+    fn test_burn_rate_windows_include_current_hour() {
+        let buckets = vec![
+            bucket("2025-01-15T04", 1000, 4.0),
+            bucket("2025-01-15T09", 600, 1.0),
+            bucket("2025-01-15T10", 600, 2.0),
+        ];
+        let now = at("2025-01-15T10:30:00Z");
+        let one = burn_rate(&buckets, 1, now);
+        assert!((one.cost_usd - 2.0).abs() < 1e-9);
+        assert_eq!(one.tokens, 600);
+        let six = burn_rate(&buckets, 6, now);
+        assert!((six.cost_usd - 3.0).abs() < 1e-9);
+        assert!((six.cost_usd_per_hour - 0.5).abs() < 1e-9);
+        assert!((six.tokens_per_hour - 200.0).abs() < 1e-9);
+        let seven = burn_rate(&buckets, 7, now);
+        assert!((seven.cost_usd - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_seasonal_projection_follows_hour_of_week() {
+        // Last week: $1/hour during 09-17 UTC every weekday, nothing otherwise.
+        let mut buckets = Vec::new();
+        let mut day = at("2025-01-06T00:00:00Z");
+        while day < at("2025-01-13T00:00:00Z") {
+            if day.weekday().number_days_from_monday() < 5 {
+                for hour in 9..17 {
+                    buckets.push(bucket(&hour_key(day + Duration::hours(hour)), 0, 1.0));
+                }
+            }
+            day += Duration::days(1);
+        }
+        // This week so far: Monday matched last week.
+        for hour in 9..17 {
+            buckets.push(bucket(
+                &hour_key(at("2025-01-13T00:00:00Z") + Duration::hours(hour)),
+                0,
+                1.0,
+            ));
+        }
+        let state = State {
+            hourly_buckets: buckets,
+            ..State::default()
+        };
+        let forecast = spend_forecast(
+            &state,
+            &ForecastConfig::default(),
+            at("2025-01-14T00:00:00Z"),
+        );
+        assert_eq!(forecast.history_hours, 8 * 24 - 9);
+        let weekly = &forecast.projections[1];
+        assert_eq!(weekly.period, BudgetPeriod::Weekly);
+        assert!((weekly.spent_usd - 8.0).abs() < 1e-9);
+        // Four more weekdays of 8 hours each; the weekend is quiet.
+        assert!((weekly.projected_usd - 40.0).abs() < 1e-9);
+        // A flat extrapolation of Monday would also charge the weekend.
+        assert!((weekly.linear_usd - 56.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_forecast_without_history_projects_spend_so_far() {
+        let state = State {
+            hourly_buckets: vec![bucket("2025-01-15T10", 10, 0.5)],
+            ..State::default()
+        };
+        let forecast = spend_forecast(
+            &state,
+            &ForecastConfig::default(),
+            at("2025-01-15T10:30:00Z"),
+        );
+        assert_eq!(forecast.history_hours, 0);
+        assert_eq!(forecast.burn_rates.len(), 3);
+        let daily = &forecast.projections[0];
+        assert!((daily.projected_usd - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_validate_forecast() {
+        assert!(validate_forecast(&ForecastConfig::default()).is_ok());
+        let zero = ForecastConfig {
+            burn_rate_windows_hours: vec![0],
+        };
+        assert!(validate_forecast(&zero).is_err());
+    }
+}
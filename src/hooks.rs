@@ -0,0 +1,299 @@
+use serde_json::{json, Map, Value};
+use std::sync::atomic::Ordering;
+
+use crate::collector::{millis_between, take_pending_tool_call, track_pending_tool_call};
+use crate::types::{App, Event, PendingToolCall};
+use crate::utils::now_iso;
+
+/// Maps one Claude Code hook payload (the JSON a hook receives on stdin) to a
+/// monitor event. Event names are the snake_cased hook names, so `Stop`,
+/// `SubagentStop` and `SessionEnd` arrive as terminal events and tool hooks
+/// never collide with the collector's `tool_call` / `tool_result`.
+pub fn parse_hook_payload(body: &[u8], app: &App) -> Result<Event, String> {
+    let payload: Map<String, Value> =
+        serde_json::from_slice(body).map_err(|e| format!("invalid hook payload: {}", e))?;
+    let text = |key: &str| {
+        payload
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let hook = text("hook_event_name");
+    if hook.is_empty() {
+        return Err("hook_event_name is required".to_string());
+    }
+    let session_id = text("session_id");
+    if session_id.is_empty() {
+        return Err("session_id is required".to_string());
+    }
+
+    let is_sidechain = hook == "SubagentStart" || hook == "SubagentStop";
+    // Same id the collector derives for the lead agent, so both feeds land on
+    // one row. A subagent hook never lands there: a subagent stop's terminal
+    // hint would mark a lead that is still working as completed.
+    let short_session_id: String = session_id.chars().take(8).collect();
+    let agent_id = Some(text("agent_id"))
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| {
+            let prefix = if is_sidechain { "subagent" } else { "lead" };
+            format!("{}-{}", prefix, short_session_id)
+        });
+    let tool_name = text("tool_name");
+    let tool_use_id = text("tool_use_id");
+    let timestamp = now_iso();
+
+    let mut metadata = json!({
+        "source": "claude_hooks",
+        "hookEventName": hook,
+        "sessionId": session_id,
+        "isSidechain": is_sidechain,
+    });
+    let mut status = "ok";
+    let mut latency_ms = None;
+    let message = match hook.as_str() {
+        "PreToolUse" | "PostToolUse" => {
+            metadata["toolName"] = json!(tool_name);
+            metadata["toolUseId"] = json!(tool_use_id);
+            let pending_key = format!("hook:{}", tool_use_id);
+            if hook == "PreToolUse" {
+                metadata["toolInput"] = payload.get("tool_input").cloned().unwrap_or(json!({}));
+                if !tool_use_id.is_empty() {
+                    track_pending_tool_call(
+                        app,
+                        &pending_key,
+                        PendingToolCall {
+                            tool_name: tool_name.clone(),
+                            agent_id: agent_id.clone(),
+                            session_id: session_id.clone(),
+                            started_at: timestamp.clone(),
+                            subagent_type: String::new(),
+                        },
+                    );
+                }
+            } else {
+                let is_error = tool_response_failed(payload.get("tool_response"));
+                metadata["isError"] = json!(is_error);
+                if is_error {
+                    status = "error";
+                }
+                if !tool_use_id.is_empty() {
+                    latency_ms = take_pending_tool_call(app, &pending_key)
+                        .and_then(|call| millis_between(&call.started_at, &timestamp));
+                }
+            }
+            tool_name
+        }
+        "Notification" => {
+            // Claude Code is blocked on the user (permission prompt, idle input).
+            status = "warning";
+            if let Some(kind) = payload.get("notification_type") {
+                metadata["notificationType"] = kind.clone();
+            }
+            text("message")
+        }
+        "SubagentStart" => {
+            metadata["subagentType"] = json!(text("agent_type"));
+            hook.clone()
+        }
+        "Stop" | "SubagentStop" => {
+            if is_sidechain {
+                metadata["subagentType"] = json!(text("agent_type"));
+            }
+            metadata["stopHookActive"] = json!(payload
+                .get("stop_hook_active")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false));
+            hook.clone()
+        }
+        "SessionEnd" => Some(text("reason"))
+            .filter(|r| !r.is_empty())
+            .unwrap_or_else(|| hook.clone()),
+        "UserPromptSubmit" => text("prompt"),
+        _ => hook.clone(),
+    };
+
+    Ok(Event {
+        id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
+        agent_id,
+        event: snake_case(&hook),
+        status: status.to_string(),
+        latency_ms,
+        message: message.chars().take(120).collect(),
+        metadata,
+        timestamp,
+        received_at: now_iso(),
+        model: String::new(),
+        is_sidechain,
+        session_id,
+        cwd: text("cwd"),
+        dedup_key: None,
+    })
+}
+
+/// Tool responses have no common schema; these are the failure markers the
+/// built-in tools use.
+fn tool_response_failed(response: Option<&Value>) -> bool {
+    let Some(Value::Object(map)) = response else {
+        return false;
+    };
+    map.get("is_error").and_then(|v| v.as_bool()) == Some(true)
+        || map.get("success").and_then(|v| v.as_bool()) == Some(false)
+        || map
+            .get("error")
+            .is_some_and(|v| !v.is_null() && v != &json!(false))
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, ch) in name.chars().enumerate() {
+        if ch.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(ch.to_ascii_lowercase());
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorConfig;
+    use crate::types::State;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+
+    fn make_test_app() -> App {
+        App {
+            state: Arc::new(Mutex::new(State::default())),
+            sse_clients: Arc::new(Mutex::new(Vec::new())),
+            event_seq: Arc::new(AtomicU64::new(1)),
+            public_dir: Arc::new(PathBuf::from("public")),
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
+        }
+    }
+
+    #[test]
+    fn test_snake_case_hook_names() {
+        assert_eq!(snake_case("Stop"), "stop");
+        assert_eq!(snake_case("SubagentStop"), "subagent_stop");
+        assert_eq!(snake_case("PostToolUse"), "post_tool_use");
+    }
+
+    #[test]
+    fn test_stop_hook_is_terminal_event_for_lead_agent() {
+        let app = make_test_app();
+        let body = br#"{"hook_event_name":"Stop","session_id":"abcdef123456","cwd":"/w/proj","stop_hook_active":false}"#;
+        let evt = parse_hook_payload(body, &app).unwrap();
+        assert_eq!(evt.event, "stop");
+        assert_eq!(evt.agent_id, "lead-abcdef12");
+        assert_eq!(evt.session_id, "abcdef123456");
+        assert_eq!(evt.cwd, "/w/proj");
+        assert_eq!(evt.metadata["source"], "claude_hooks");
+        assert!(!evt.is_sidechain);
+    }
+
+    #[test]
+    fn test_subagent_stop_is_sidechain() {
+        let app = make_test_app();
+        let body = br#"{"hook_event_name":"SubagentStop","session_id":"s1","agent_id":"a-9"}"#;
+        let evt = parse_hook_payload(body, &app).unwrap();
+        assert_eq!(evt.event, "subagent_stop");
+        assert_eq!(evt.agent_id, "a-9");
+        assert!(evt.is_sidechain);
+    }
+
+    #[test]
+    fn test_subagent_hooks_carry_agent_type() {
+        let app = make_test_app();
+        let body = br#"{"hook_event_name":"SubagentStart","session_id":"s1","agent_id":"a-9","agent_type":"refactor"}"#;
+        let evt = parse_hook_payload(body, &app).unwrap();
+        assert_eq!(evt.event, "subagent_start");
+        assert!(evt.is_sidechain);
+        crate::state::append_event(&app, evt);
+        assert_eq!(
+            app.state.lock().unwrap().by_agent["a-9"].subagent_type,
+            "refactor"
+        );
+    }
+
+    #[test]
+    fn test_subagent_stop_without_agent_id_leaves_lead_open() {
+        let app = make_test_app();
+        let body = br#"{"hook_event_name":"SubagentStop","session_id":"abcdef123456"}"#;
+        let evt = parse_hook_payload(body, &app).unwrap();
+        assert_eq!(evt.agent_id, "subagent-abcdef12");
+        assert!(evt.is_sidechain);
+
+        let prompt =
+            br#"{"hook_event_name":"UserPromptSubmit","session_id":"abcdef123456","prompt":"go"}"#;
+        crate::state::append_event(&app, parse_hook_payload(prompt, &app).unwrap());
+        crate::state::append_event(&app, evt);
+        let state = app.state.lock().unwrap();
+        assert_eq!(
+            state.by_agent["lead-abcdef12"].last_event,
+            "user_prompt_submit"
+        );
+        let now = time::OffsetDateTime::now_utc() + time::Duration::minutes(5);
+        let session = &state.by_session["abcdef123456"];
+        assert_eq!(
+            crate::state::session_state_at(&state, session, now),
+            "stuck"
+        );
+    }
+
+    #[test]
+    fn test_notification_hook_is_warning() {
+        let app = make_test_app();
+        let body = br#"{"hook_event_name":"Notification","session_id":"s1","message":"Claude needs your permission to use Bash"}"#;
+        let evt = parse_hook_payload(body, &app).unwrap();
+        assert_eq!(evt.event, "notification");
+        assert_eq!(evt.status, "warning");
+        assert_eq!(evt.message, "Claude needs your permission to use Bash");
+    }
+
+    #[test]
+    fn test_tool_hooks_carry_outcome_and_latency() {
+        let app = make_test_app();
+        let pre = br#"{"hook_event_name":"PreToolUse","session_id":"s1","tool_name":"Write","tool_use_id":"t1","tool_input":{"file_path":"/a"}}"#;
+        let evt = parse_hook_payload(pre, &app).unwrap();
+        assert_eq!(evt.event, "pre_tool_use");
+        assert_eq!(evt.metadata["toolInput"]["file_path"], "/a");
+
+        let post = br#"{"hook_event_name":"PostToolUse","session_id":"s1","tool_name":"Write","tool_use_id":"t1","tool_response":{"success":false}}"#;
+        let evt = parse_hook_payload(post, &app).unwrap();
+        assert_eq!(evt.event, "post_tool_use");
+        assert_eq!(evt.status, "error");
+        assert_eq!(evt.message, "Write");
+        assert_eq!(evt.metadata["isError"], true);
+        assert!(evt.latency_ms.is_some());
+        assert!(app.state.lock().unwrap().pending_tool_calls.is_empty());
+    }
+
+    #[test]
+    fn test_tool_response_failed_markers() {
+        assert!(!tool_response_failed(None));
+        assert!(!tool_response_failed(Some(&json!("plain output"))));
+        assert!(!tool_response_failed(Some(
+            &json!({"stdout": "ok", "error": null})
+        )));
+        assert!(tool_response_failed(Some(&json!({"is_error": true}))));
+        assert!(tool_response_failed(Some(&json!({"error": "boom"}))));
+    }
+
+    #[test]
+    fn test_hook_payload_requires_name_and_session() {
+        let app = make_test_app();
+        assert!(parse_hook_payload(br#"{"session_id":"s1"}"#, &app).is_err());
+        assert!(parse_hook_payload(br#"{"hook_event_name":"Stop"}"#, &app).is_err());
+        assert!(parse_hook_payload(b"not json", &app).is_err());
+    }
+}
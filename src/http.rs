@@ -5,13 +5,41 @@ use std::net::TcpStream;
 use std::sync::mpsc::{self, Receiver};
 use std::thread;
 use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
+use crate::alerts::{alert_matches_status, apply_alert_action, AlertAction};
+use crate::budgets::budget_status;
+use crate::forecast::spend_forecast;
+use crate::hooks::parse_hook_payload;
+use crate::ingest::parse_events_body;
+use crate::metrics::{render_openmetrics, OPENMETRICS_CONTENT_TYPE};
+use crate::models::model_mix;
+use crate::otel::{parse_otlp_logs, parse_otlp_metrics};
+use crate::projects::{project_detail, project_summaries};
+use crate::sessions::{list_sessions, listing_snapshot, SessionQuery};
 #[cfg(test)]
 use crate::state::broadcast_sse;
-use crate::state::{build_snapshot, get_session_events, get_session_export, ExportAlertRules};
-use crate::types::{App, ParsedRequest};
+use crate::state::{
+    append_event, build_snapshot, get_session_events, get_session_export, ExportAlertRules,
+    MAX_HOUR_BUCKETS,
+};
+use crate::types::{App, ParsedRequest, Snapshot, State};
 use crate::utils::{bytes_response, content_type_for, json_response, now_iso};
 
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// The dashboard snapshot with the spend forecast added to its totals.
+fn app_snapshot(app: &App, state: &State) -> Snapshot {
+    let mut snapshot = build_snapshot(state);
+    snapshot.totals["forecast"] = json!(spend_forecast(
+        state,
+        &app.config.forecast,
+        OffsetDateTime::now_utc()
+    ));
+    snapshot
+}
+
 pub fn parse_request(stream: &mut TcpStream) -> Option<ParsedRequest> {
     let timeout_secs = std::env::var("HTTP_READ_TIMEOUT_SEC")
         .ok()
@@ -42,14 +70,47 @@ pub fn parse_request(stream: &mut TcpStream) -> Option<ParsedRequest> {
     let mut parts = request_line.split_whitespace();
     let method = parts.next()?.to_string();
     let (path, query) = split_path_and_query(parts.next()?);
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = match headers.get("content-length") {
+        Some(value) => value.parse::<usize>().ok()?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_BYTES {
+        return None;
+    }
+    while data.len() < headers_end + content_length {
+        let n = stream.read(&mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    let body = data[headers_end..headers_end + content_length].to_vec();
 
     Some(ParsedRequest {
         method,
         path,
         query,
+        headers,
+        body,
     })
 }
 
+/// `true` when no ingest token is configured or the request carries it.
+fn is_authorized(app: &App, req: &ParsedRequest) -> bool {
+    let Some(token) = app.config.ingest_token.as_deref() else {
+        return true;
+    };
+    req.headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| given == token)
+}
+
 fn split_path_and_query(raw_path: &str) -> (String, HashMap<String, String>) {
     let Some((path, query)) = raw_path.split_once('?') else {
         return (raw_path.to_string(), HashMap::new());
@@ -138,6 +199,41 @@ pub fn spawn_sse_sweeper(app: App) {
     });
 }
 
+/// `/api/alerts/<id>/<action>` → `(id, action)`.
+fn alert_route(path: &str) -> Option<(&str, &str)> {
+    let (id, action) = path.strip_prefix("/api/alerts/")?.split_once('/')?;
+    (!id.is_empty() && !action.is_empty()).then_some((id, action))
+}
+
+/// `/api/projects/<name>` → the percent-decoded project name.
+fn project_route_name(path: &str) -> Option<String> {
+    percent_decode(path.strip_prefix("/api/projects/")?).filter(|name| !name.is_empty())
+}
+
+/// Decodes `%XX` escapes; `None` if the result is not UTF-8.
+fn percent_decode(raw: &str) -> Option<String> {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| raw.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
 fn session_route_id<'a>(path: &'a str, suffix: &str) -> Option<&'a str> {
     path.strip_prefix("/api/sessions/")?.strip_suffix(suffix)
 }
@@ -156,24 +252,119 @@ pub fn handle_client(mut stream: TcpStream, app: App) {
 
     match (req.method.as_str(), req.path.as_str()) {
         ("GET", "/api/health") => {
-            let body = json!({ "ok": true, "now": now_iso() }).to_string();
+            let db_writer = app.db_writer.as_ref().map(|w| w.health());
+            let notifier = app.notifier.as_ref().map(|n| n.health());
+            let collector = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                state.collector_scan.clone()
+            };
+            let body = json!({
+                "ok": true,
+                "now": now_iso(),
+                "dbWriter": db_writer,
+                "notifier": notifier,
+                "collector": collector,
+            })
+            .to_string();
+            let _ = stream.write_all(&json_response("200 OK", &body));
+        }
+        ("GET", "/api/budgets") => {
+            let now = OffsetDateTime::now_utc();
+            let budgets: Vec<_> = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                app.config
+                    .budgets
+                    .iter()
+                    .map(|budget| budget_status(&state, budget, now))
+                    .collect()
+            };
+            let body = json!({
+                "generatedAt": now.format(&Rfc3339).unwrap_or_default(),
+                "budgets": budgets,
+            })
+            .to_string();
             let _ = stream.write_all(&json_response("200 OK", &body));
         }
+        ("GET", "/api/forecast") => {
+            let now = OffsetDateTime::now_utc();
+            let forecast = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                spend_forecast(&state, &app.config.forecast, now)
+            };
+            let mut body = json!(forecast);
+            body["generatedAt"] = json!(now.format(&Rfc3339).unwrap_or_default());
+            let _ = stream.write_all(&json_response("200 OK", &body.to_string()));
+        }
+        ("GET", "/api/notifications") => {
+            let deliveries = app
+                .notifier
+                .as_ref()
+                .map(|n| n.recent_deliveries())
+                .unwrap_or_default();
+            let body = json!({ "deliveries": deliveries }).to_string();
+            let _ = stream.write_all(&json_response("200 OK", &body));
+        }
+        ("GET", "/metrics") => {
+            let sse_clients = app.sse_clients.lock().map(|c| c.len()).unwrap_or(0);
+            let body = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                render_openmetrics(&state, sse_clients, OffsetDateTime::now_utc())
+            };
+            let _ = stream.write_all(&bytes_response(
+                "200 OK",
+                body.as_bytes(),
+                OPENMETRICS_CONTENT_TYPE,
+            ));
+        }
         ("GET", "/api/events") => {
             let snapshot = {
                 let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
-                serde_json::to_string(&build_snapshot(&state)).unwrap_or_else(|_| "{}".to_string())
+                serde_json::to_string(&app_snapshot(&app, &state))
+                    .unwrap_or_else(|_| "{}".to_string())
             };
             let _ = stream.write_all(&json_response("200 OK", &snapshot));
         }
         ("GET", "/api/alerts") => {
+            let status = req.query.get("status").map(String::as_str).unwrap_or("");
             let body = {
                 let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
-                json!({ "alerts": state.alerts.iter().take(50).cloned().collect::<Vec<_>>() })
-                    .to_string()
+                let alerts: Vec<_> = state
+                    .alerts
+                    .iter()
+                    .filter(|alert| alert_matches_status(alert, status))
+                    .take(50)
+                    .cloned()
+                    .collect();
+                json!({ "alerts": alerts }).to_string()
             };
             let _ = stream.write_all(&json_response("200 OK", &body));
         }
+        ("GET", "/api/sessions") => {
+            let query = req
+                .query
+                .iter()
+                .map(|(k, v)| (k.clone(), percent_decode(v).unwrap_or_else(|| v.clone())))
+                .collect();
+            let page = SessionQuery::from_query(&query).and_then(|query| {
+                // Ingest keeps running while stored sessions are scanned.
+                let state = listing_snapshot(&app.state.lock().unwrap_or_else(|e| e.into_inner()));
+                let db = app
+                    .db_reader
+                    .as_ref()
+                    .map(|db| db.lock().unwrap_or_else(|e| e.into_inner()));
+                list_sessions(&state, db.as_deref(), &query, OffsetDateTime::now_utc())
+            });
+            let response = match page {
+                Ok(page) => json_response(
+                    "200 OK",
+                    &serde_json::to_string(&page).unwrap_or_else(|_| "{}".to_string()),
+                ),
+                Err(error) => {
+                    json_response("400 Bad Request", &json!({ "error": error }).to_string())
+                }
+            };
+            let _ = stream.write_all(&response);
+        }
         ("GET", path) if session_route_id(path, "/events").is_some() => {
             let session_id = session_route_id(path, "/events").unwrap_or_default();
             let body = {
@@ -203,10 +394,84 @@ pub fn handle_client(mut stream: TcpStream, app: App) {
                 }
             }
         }
+        ("GET", "/api/models") => {
+            let project = req.query.get("project").and_then(|v| percent_decode(v));
+            let session_id = req.query.get("sessionId").and_then(|v| percent_decode(v));
+            let hours = req
+                .query
+                .get("hours")
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(24)
+                .clamp(1, MAX_HOUR_BUCKETS as i64);
+            let now = OffsetDateTime::now_utc();
+            let since = now - time::Duration::hours(hours - 1);
+            let since_key = since.format(&Rfc3339).unwrap_or_default();
+            let since_key = since_key.get(..13).unwrap_or("");
+            let body = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                let scoped = match (&session_id, &project) {
+                    (Some(id), _) => state
+                        .by_session
+                        .get(id)
+                        .map(|s| ("session", id.clone(), model_mix(&s.by_model, None, ""))),
+                    (None, Some(name)) => state
+                        .by_project
+                        .get(name)
+                        .map(|p| ("project", name.clone(), model_mix(&p.by_model, None, ""))),
+                    (None, None) => Some((
+                        "global",
+                        String::new(),
+                        model_mix(&state.by_model, Some(&state.model_hourly), since_key),
+                    )),
+                };
+                scoped.map(|(scope, name, models)| {
+                    json!({
+                        "generatedAt": now.format(&Rfc3339).unwrap_or_default(),
+                        "scope": scope,
+                        "name": name,
+                        "models": models,
+                    })
+                    .to_string()
+                })
+            };
+            let response = match body {
+                Some(body) => json_response("200 OK", &body),
+                None => json_response(
+                    "404 Not Found",
+                    &json!({ "error": "Scope not found" }).to_string(),
+                ),
+            };
+            let _ = stream.write_all(&response);
+        }
+        ("GET", "/api/projects") => {
+            let body = {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                json!({ "projects": project_summaries(&state, OffsetDateTime::now_utc()) })
+                    .to_string()
+            };
+            let _ = stream.write_all(&json_response("200 OK", &body));
+        }
+        ("GET", path) if path.starts_with("/api/projects/") => {
+            let detail = project_route_name(path).and_then(|name| {
+                let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+                project_detail(&state, &name, OffsetDateTime::now_utc())
+            });
+            let response = match detail {
+                Some(detail) => json_response(
+                    "200 OK",
+                    &serde_json::to_string(&detail).unwrap_or_else(|_| "{}".to_string()),
+                ),
+                None => json_response(
+                    "404 Not Found",
+                    &json!({ "error": "Project not found" }).to_string(),
+                ),
+            };
+            let _ = stream.write_all(&response);
+        }
         ("GET", "/api/stream") => {
             let snapshot = {
                 let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
-                json!({ "type": "snapshot", "payload": build_snapshot(&state) }).to_string()
+                json!({ "type": "snapshot", "payload": app_snapshot(&app, &state) }).to_string()
             };
             let (tx, rx) = mpsc::channel::<String>();
             app.sse_clients
@@ -219,6 +484,102 @@ pub fn handle_client(mut stream: TcpStream, app: App) {
             let resp = serve_static(&app, &req.path);
             let _ = stream.write_all(&resp);
         }
+        ("POST", _) if !is_authorized(&app, &req) => {
+            let _ = stream.write_all(&json_response(
+                "401 Unauthorized",
+                &json!({ "error": "Missing or invalid bearer token" }).to_string(),
+            ));
+        }
+        ("POST", path) if alert_route(path).is_some() => {
+            let (alert_id, action) = alert_route(path).unwrap_or_default();
+            let minutes = req
+                .query
+                .get("minutes")
+                .and_then(|m| m.parse::<i64>().ok())
+                .or_else(|| {
+                    serde_json::from_slice::<serde_json::Value>(&req.body)
+                        .ok()?
+                        .get("minutes")?
+                        .as_i64()
+                });
+            let response = match AlertAction::from_route(action, minutes) {
+                None => json_response(
+                    "404 Not Found",
+                    &json!({ "error": "Unknown alert action" }).to_string(),
+                ),
+                Some(action) => match apply_alert_action(&app, alert_id, action) {
+                    Some(alert) => json_response("200 OK", &json!(alert).to_string()),
+                    None => json_response(
+                        "404 Not Found",
+                        &json!({ "error": "Alert not found" }).to_string(),
+                    ),
+                },
+            };
+            let _ = stream.write_all(&response);
+        }
+        ("POST", "/api/hooks") => match parse_hook_payload(&req.body, &app) {
+            Ok(evt) => {
+                let body = json!({ "accepted": 1, "ids": [evt.id.clone()] }).to_string();
+                append_event(&app, evt);
+                let _ = stream.write_all(&json_response("202 Accepted", &body));
+            }
+            Err(error) => {
+                let _ = stream.write_all(&json_response(
+                    "400 Bad Request",
+                    &json!({ "error": error }).to_string(),
+                ));
+            }
+        },
+        ("POST", "/v1/logs") | ("POST", "/v1/metrics")
+            if req
+                .headers
+                .get("content-type")
+                .is_some_and(|ct| ct.contains("protobuf")) =>
+        {
+            let _ = stream.write_all(&json_response(
+                "415 Unsupported Media Type",
+                &json!({ "error": "Only OTLP/HTTP JSON is supported; set OTEL_EXPORTER_OTLP_PROTOCOL=http/json" })
+                    .to_string(),
+            ));
+        }
+        ("POST", "/v1/logs") | ("POST", "/v1/metrics") => {
+            let parsed = if req.path == "/v1/logs" {
+                parse_otlp_logs(&req.body, &app)
+            } else {
+                parse_otlp_metrics(&req.body, &app)
+            };
+            match parsed {
+                Ok(events) => {
+                    for evt in events {
+                        append_event(&app, evt);
+                    }
+                    // An empty ExportLogsServiceResponse / ExportMetricsServiceResponse means full success.
+                    let _ = stream.write_all(&json_response("200 OK", "{}"));
+                }
+                Err(error) => {
+                    let _ = stream.write_all(&json_response(
+                        "400 Bad Request",
+                        &json!({ "error": error }).to_string(),
+                    ));
+                }
+            }
+        }
+        ("POST", "/api/events") => match parse_events_body(&req.body, &app) {
+            Ok(events) => {
+                let ids: Vec<String> = events.iter().map(|evt| evt.id.clone()).collect();
+                for evt in events {
+                    append_event(&app, evt);
+                }
+                let body = json!({ "accepted": ids.len(), "ids": ids }).to_string();
+                let _ = stream.write_all(&json_response("202 Accepted", &body));
+            }
+            Err(error) => {
+                let _ = stream.write_all(&json_response(
+                    "400 Bad Request",
+                    &json!({ "error": error }).to_string(),
+                ));
+            }
+        },
         _ => {
             let _ = stream.write_all(&json_response(
                 "405 Method Not Allowed",
@@ -231,7 +592,9 @@ pub fn handle_client(mut stream: TcpStream, app: App) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::State;
+    use crate::alerts::STATUS_OPEN;
+    use crate::config::MonitorConfig;
+    use crate::types::{AlertRow, State};
     use std::io::Write;
     use std::net::TcpListener;
     use std::path::PathBuf;
@@ -248,7 +611,10 @@ mod tests {
             sse_clients: Arc::new(Mutex::new(Vec::new())),
             event_seq: Arc::new(AtomicU64::new(1)),
             public_dir: Arc::new(path),
-            db: None,
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
         }
     }
 
@@ -354,6 +720,331 @@ mod tests {
         handle.join().unwrap();
         assert!(resp.contains("200 OK"));
         assert!(resp.contains("\"ok\":true"));
+        assert!(resp.contains("\"dbWriter\":null"));
+        assert!(resp.contains("\"scanMs\""));
+    }
+
+    #[test]
+    fn test_handle_client_notifications_without_notifiers() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/notifications HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert!(resp.contains("{\"deliveries\":[]}"));
+    }
+
+    #[test]
+    fn test_handle_client_forecast() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/forecast HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        let body: serde_json::Value =
+            serde_json::from_str(resp.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["burnRates"].as_array().unwrap().len(), 3);
+        assert_eq!(body["burnRates"][0]["windowHours"], 1);
+        assert_eq!(body["projections"][1]["period"], "weekly");
+        assert!(body["generatedAt"].is_string());
+    }
+
+    #[test]
+    fn test_handle_client_budgets() {
+        let mut app = make_test_app();
+        app.config = Arc::new(MonitorConfig {
+            budgets: serde_json::from_value(json!([
+                { "id": "daily", "scope": "global", "period": "daily", "capUsd": 10.0 }
+            ]))
+            .unwrap(),
+            ..MonitorConfig::default()
+        });
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/budgets HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        let body: serde_json::Value =
+            serde_json::from_str(resp.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["budgets"][0]["id"], "daily");
+        assert_eq!(body["budgets"][0]["spentUsd"], 0.0);
+        assert_eq!(body["budgets"][0]["remainingUsd"], 10.0);
+        assert_eq!(body["budgets"][0]["status"], "ok");
+    }
+
+    #[test]
+    fn test_handle_client_metrics() {
+        let app = make_test_app();
+        crate::state::append_event(
+            &app,
+            crate::types::Event {
+                id: "e1".to_string(),
+                agent_id: "a1".to_string(),
+                event: "tool_call".to_string(),
+                status: "ok".to_string(),
+                latency_ms: None,
+                message: "Bash".to_string(),
+                metadata: json!({ "source": "claude_session" }),
+                timestamp: now_iso(),
+                received_at: now_iso(),
+                model: String::new(),
+                is_sidechain: false,
+                session_id: String::new(),
+                cwd: String::new(),
+                dedup_key: None,
+            },
+        );
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(&addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert!(resp.contains("Content-Type: application/openmetrics-text"));
+        assert!(resp.contains(
+            "claude_monitor_events_total{source=\"claude_session\",event=\"tool_call\",status=\"ok\"} 1"
+        ));
+        assert!(resp.contains("claude_monitor_tool_calls_total{tool=\"Bash\"} 1"));
+        assert!(resp.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_handle_client_sessions_reads_past_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::db::Db::open(&dir.path().join("monitor.db")).unwrap();
+        let mut app = make_test_app();
+        let evt = crate::types::Event {
+            id: "e1".to_string(),
+            agent_id: "a1".to_string(),
+            event: "tool_call".to_string(),
+            status: "ok".to_string(),
+            latency_ms: None,
+            message: "Bash".to_string(),
+            metadata: json!({}),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            received_at: "2025-01-01T00:00:00Z".to_string(),
+            model: String::new(),
+            is_sidechain: false,
+            session_id: "old".to_string(),
+            cwd: "/home/user/web".to_string(),
+            dedup_key: None,
+        };
+        crate::state::append_event(&app, evt.clone());
+        {
+            // Persist the session, then let it fall out of memory.
+            let mut state = app.state.lock().unwrap();
+            db.record_event(
+                &evt,
+                &state.by_agent["a1"],
+                Some(&state.by_session["old"]),
+                None,
+            )
+            .unwrap();
+            *state = State::default();
+        }
+        app.db_reader = Some(Arc::new(Mutex::new(db)));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            "GET /api/sessions?project=web&sort=cost HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        let body: serde_json::Value =
+            serde_json::from_str(resp.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["sessions"][0]["sessionId"], "old");
+        assert_eq!(body["sessions"][0]["inMemory"], false);
+        assert_eq!(body["sessions"][0]["sessionState"], "completed");
+        assert!(body["nextCursor"].is_null());
+
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/sessions?sort=name HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("400 Bad Request"));
+    }
+
+    #[test]
+    fn test_handle_client_models() {
+        let app = make_test_app();
+        crate::state::append_event(
+            &app,
+            crate::types::Event {
+                id: "e1".to_string(),
+                agent_id: "a1".to_string(),
+                event: "token_usage".to_string(),
+                status: "ok".to_string(),
+                latency_ms: None,
+                message: String::new(),
+                metadata: json!({ "costUsd": 0.5, "tokenUsage": { "totalTokens": 10 } }),
+                timestamp: now_iso(),
+                received_at: now_iso(),
+                model: "claude-opus-4".to_string(),
+                is_sidechain: false,
+                session_id: "s1".to_string(),
+                cwd: "/home/user/web".to_string(),
+                dedup_key: None,
+            },
+        );
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(&addr, "GET /api/models HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        let body: serde_json::Value =
+            serde_json::from_str(resp.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["scope"], "global");
+        assert_eq!(body["models"][0]["model"], "claude-opus-4");
+        assert_eq!(body["models"][0]["requests"], 1);
+        assert_eq!(body["models"][0]["costShare"], 1.0);
+        assert_eq!(body["models"][0]["hourlyBuckets"][0]["tokenTotal"], 10);
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            "GET /api/models?project=web HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("\"scope\":\"project\""));
+        assert!(!resp.contains("hourlyBuckets"));
+
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/models?sessionId=missing HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("404 Not Found"));
+    }
+
+    #[test]
+    fn test_handle_client_projects() {
+        let app = make_test_app();
+        crate::state::append_event(
+            &app,
+            crate::types::Event {
+                id: "e1".to_string(),
+                agent_id: "a1".to_string(),
+                event: "tool_call".to_string(),
+                status: "ok".to_string(),
+                latency_ms: None,
+                message: "Bash".to_string(),
+                metadata: json!({}),
+                timestamp: now_iso(),
+                received_at: now_iso(),
+                model: String::new(),
+                is_sidechain: false,
+                session_id: "s1".to_string(),
+                cwd: "/home/user/my project".to_string(),
+                dedup_key: None,
+            },
+        );
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            "GET /api/projects HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert!(resp.contains("\"projectName\":\"my project\""));
+        assert!(resp.contains("\"activeSessions\":1"));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            "GET /api/projects/my%20project HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert!(resp.contains("\"sessionRows\":[{\"sessionId\":\"s1\""));
+
+        let (addr, handle) = spawn_test_server(app);
+        let resp = http_request(
+            &addr,
+            "GET /api/projects/other HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("404 Not Found"));
+    }
+
+    #[test]
+    fn test_handle_client_alert_actions() {
+        let app = make_test_app();
+        for _ in 0..3 {
+            crate::state::append_event(
+                &app,
+                crate::types::Event {
+                    id: "e1".to_string(),
+                    agent_id: "a1".to_string(),
+                    event: "tool_result".to_string(),
+                    status: "error".to_string(),
+                    latency_ms: None,
+                    message: "boom".to_string(),
+                    metadata: json!({}),
+                    timestamp: now_iso(),
+                    received_at: now_iso(),
+                    model: String::new(),
+                    is_sidechain: false,
+                    session_id: "s1".to_string(),
+                    cwd: String::new(),
+                    dedup_key: None,
+                },
+            );
+        }
+        let alert_id = {
+            let state = app.state.lock().unwrap();
+            assert_eq!(state.alerts.len(), 1);
+            assert_eq!(state.alerts[0].count, 3);
+            state.alerts[0].id.clone()
+        };
+        let (tx, rx) = mpsc::channel();
+        app.sse_clients.lock().unwrap().push(tx);
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request(
+                &format!("/api/alerts/{}/snooze", alert_id),
+                "",
+                r#"{"minutes":30}"#,
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        let body: serde_json::Value = serde_json::from_str(response_body(&resp)).unwrap();
+        assert_eq!(body["status"], "snoozed");
+        assert!(body["snoozedUntil"].is_string());
+        assert!(rx.try_recv().unwrap().contains("\"type\":\"alert\""));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request(&format!("/api/alerts/{}/resolve", alert_id), "", ""),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("\"status\":\"resolved\""));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            "GET /api/alerts?status=open HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("\"alerts\":[]"));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(&addr, &post_request("/api/alerts/a999/ack", "", ""));
+        handle.join().unwrap();
+        assert!(resp.contains("404 Not Found"));
     }
 
     #[test]
@@ -363,6 +1054,7 @@ mod tests {
         handle.join().unwrap();
         assert!(resp.contains("200 OK"));
         assert!(resp.contains("generatedAt"));
+        assert!(resp.contains("\"forecast\":{"));
     }
 
     #[test]
@@ -442,15 +1134,204 @@ mod tests {
         handle.join().unwrap();
     }
 
+    fn post_request(path: &str, extra_headers: &str, body: &str) -> String {
+        format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+            path,
+            extra_headers,
+            body.len(),
+            body
+        )
+    }
+
     #[test]
-    fn test_handle_client_post_events_rejected() {
+    fn test_handle_client_post_events_empty_body_rejected() {
         let (addr, handle) = spawn_test_server(make_test_app());
         let resp = http_request(
             &addr,
             "POST /api/events HTTP/1.1\r\nHost: localhost\r\n\r\n",
         );
         handle.join().unwrap();
-        assert!(resp.contains("405 Method Not Allowed"));
+        assert!(resp.contains("400 Bad Request"));
+        assert!(resp.contains("empty body"));
+    }
+
+    #[test]
+    fn test_handle_client_post_single_event() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request(
+                "/api/events",
+                "Content-Type: application/json\r\n",
+                r#"{"event":"deploy","agentId":"ci","status":"warning","message":"slow"}"#,
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("202 Accepted"));
+        let body: serde_json::Value = serde_json::from_str(response_body(&resp)).unwrap();
+        assert_eq!(body["accepted"], 1);
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.recent[0].event, "deploy");
+        assert_eq!(state.recent[0].id, body["ids"][0]);
+        assert!(state.by_source.contains_key("manual"));
+        assert_eq!(state.alerts.len(), 1);
+    }
+
+    #[test]
+    fn test_handle_client_post_ndjson_batch() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app.clone());
+        let batch = "{\"event\":\"a\"}\n{\"event\":\"b\"}\n{\"event\":\"c\"}\n";
+        let resp = http_request(
+            &addr,
+            &post_request(
+                "/api/events",
+                "Content-Type: application/x-ndjson\r\n",
+                batch,
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("202 Accepted"));
+        assert_eq!(app.state.lock().unwrap().recent.len(), 3);
+    }
+
+    #[test]
+    fn test_handle_client_post_invalid_event_rejected() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request("/api/events", "", r#"{"event":"a","status":"fatal"}"#),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("400 Bad Request"));
+        assert!(app.state.lock().unwrap().recent.is_empty());
+    }
+
+    #[test]
+    fn test_handle_client_post_requires_configured_token() {
+        let mut app = make_test_app();
+        app.config = Arc::new(MonitorConfig {
+            ingest_token: Some("s3cret".to_string()),
+            ..MonitorConfig::default()
+        });
+        let body = r#"{"event":"a"}"#;
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(&addr, &post_request("/api/events", "", body));
+        handle.join().unwrap();
+        assert!(resp.contains("401 Unauthorized"));
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request("/api/events", "Authorization: Bearer s3cret\r\n", body),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("202 Accepted"));
+    }
+
+    #[test]
+    fn test_handle_client_alert_action_requires_configured_token() {
+        let mut app = make_test_app();
+        app.config = Arc::new(MonitorConfig {
+            ingest_token: Some("s3cret".to_string()),
+            ..MonitorConfig::default()
+        });
+        app.state.lock().unwrap().alerts.push(AlertRow {
+            id: "a1".to_string(),
+            severity: "error".to_string(),
+            status: STATUS_OPEN.to_string(),
+            ..AlertRow::default()
+        });
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(&addr, &post_request("/api/alerts/a1/ack", "", ""));
+        handle.join().unwrap();
+        assert!(resp.contains("401 Unauthorized"));
+        assert_eq!(app.state.lock().unwrap().alerts[0].status, STATUS_OPEN);
+
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request("/api/alerts/a1/ack", "Authorization: Bearer s3cret\r\n", ""),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert_ne!(app.state.lock().unwrap().alerts[0].status, STATUS_OPEN);
+    }
+
+    #[test]
+    fn test_handle_client_post_stop_hook_completes_session() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request(
+                "/api/hooks",
+                "",
+                r#"{"hook_event_name":"Stop","session_id":"sess-1234","cwd":"/w/p"}"#,
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("202 Accepted"));
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.by_agent["lead-sess-123"].last_event, "stop");
+        assert!(state.by_source.contains_key("claude_hooks"));
+    }
+
+    #[test]
+    fn test_handle_client_post_otlp_metrics_fixture() {
+        let app = make_test_app();
+        let (addr, handle) = spawn_test_server(app.clone());
+        let resp = http_request(
+            &addr,
+            &post_request(
+                "/v1/metrics",
+                "Content-Type: application/json\r\n",
+                include_str!("../tests/fixtures/otlp_metrics.json"),
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("200 OK"));
+        assert_eq!(response_body(&resp), "{}");
+        assert!(app.state.lock().unwrap().cost_total_usd > 0.04);
+    }
+
+    #[test]
+    fn test_handle_client_post_otlp_protobuf_rejected() {
+        let (addr, handle) = spawn_test_server(make_test_app());
+        let resp = http_request(
+            &addr,
+            &post_request(
+                "/v1/logs",
+                "Content-Type: application/x-protobuf\r\n",
+                "\u{0a}",
+            ),
+        );
+        handle.join().unwrap();
+        assert!(resp.contains("415 Unsupported Media Type"));
+    }
+
+    #[test]
+    fn test_parse_request_reads_body_split_across_packets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"POST /api/events HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello")
+                .unwrap();
+            thread::sleep(Duration::from_millis(50));
+            stream.write_all(b"world").unwrap();
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let req = parse_request(&mut stream).unwrap();
+        client.join().unwrap();
+        assert_eq!(req.body, b"helloworld");
+        assert_eq!(req.headers["content-length"], "10");
     }
 
     #[test]
@@ -473,6 +1354,7 @@ mod tests {
             is_sidechain: false,
             session_id: "sess-abc".to_string(),
             cwd: String::new(),
+            dedup_key: None,
         };
         append_event(&app, evt);
 
@@ -522,6 +1404,7 @@ mod tests {
                 is_sidechain: false,
                 session_id: "sess-abc".to_string(),
                 cwd: String::new(),
+                dedup_key: None,
             },
         );
         append_event(
@@ -542,6 +1425,7 @@ mod tests {
                 is_sidechain: false,
                 session_id: "sess-abc".to_string(),
                 cwd: String::new(),
+                dedup_key: None,
             },
         );
         append_event(
@@ -560,6 +1444,7 @@ mod tests {
                 is_sidechain: false,
                 session_id: "sess-abc".to_string(),
                 cwd: String::new(),
+                dedup_key: None,
             },
         );
 
@@ -624,6 +1509,7 @@ mod tests {
                 is_sidechain: false,
                 session_id: "sess-abc".to_string(),
                 cwd: String::new(),
+                dedup_key: None,
             },
         );
         append_event(
@@ -642,6 +1528,7 @@ mod tests {
                 is_sidechain: false,
                 session_id: "sess-abc".to_string(),
                 cwd: String::new(),
+                dedup_key: None,
             },
         );
 
@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::types::{App, Event};
+use crate::utils::now_iso;
+
+const MAX_BATCH_EVENTS: usize = 1_000;
+const VALID_STATUSES: [&str; 3] = ["ok", "warning", "error"];
+
+/// One event as accepted by `POST /api/events`. Only `event` is required;
+/// unknown fields (e.g. `id` or `receivedAt` from an exported event) are ignored.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomingEvent {
+    event: String,
+    agent_id: Option<String>,
+    status: Option<String>,
+    latency_ms: Option<i64>,
+    #[serde(default)]
+    message: String,
+    metadata: Option<Value>,
+    timestamp: Option<String>,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    session_id: String,
+    #[serde(default)]
+    is_sidechain: bool,
+    #[serde(default)]
+    cwd: String,
+    dedup_key: Option<String>,
+}
+
+impl IncomingEvent {
+    fn validate(&self) -> Result<(), String> {
+        if self.event.trim().is_empty() {
+            return Err("event must not be empty".to_string());
+        }
+        if let Some(status) = &self.status {
+            if !VALID_STATUSES.contains(&status.as_str()) {
+                return Err(format!(
+                    "status must be one of ok, warning, error (got {:?})",
+                    status
+                ));
+            }
+        }
+        if !matches!(
+            self.metadata,
+            None | Some(Value::Null) | Some(Value::Object(_))
+        ) {
+            return Err("metadata must be an object".to_string());
+        }
+        if let Some(ts) = &self.timestamp {
+            OffsetDateTime::parse(ts, &Rfc3339)
+                .map_err(|_| format!("timestamp must be RFC 3339 (got {:?})", ts))?;
+        }
+        Ok(())
+    }
+
+    /// Events without `metadata.source` are counted under the `manual` source.
+    fn into_event(self, app: &App) -> Event {
+        let metadata = match self.metadata {
+            Some(Value::Object(map)) => Value::Object(map),
+            _ => json!({}),
+        };
+        Event {
+            id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
+            agent_id: self
+                .agent_id
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| "manual".to_string()),
+            event: self.event,
+            status: self.status.unwrap_or_else(|| "ok".to_string()),
+            latency_ms: self.latency_ms,
+            message: self.message.chars().take(120).collect(),
+            metadata,
+            timestamp: self.timestamp.unwrap_or_else(now_iso),
+            received_at: now_iso(),
+            model: self.model,
+            is_sidechain: self.is_sidechain,
+            session_id: self.session_id,
+            cwd: self.cwd,
+            dedup_key: self.dedup_key,
+        }
+    }
+}
+
+/// Parses a request body holding either one JSON event or an NDJSON batch.
+/// The whole batch is rejected if any line is invalid, so a client can
+/// safely retry it.
+pub fn parse_events_body(body: &[u8], app: &App) -> Result<Vec<Event>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "body must be UTF-8".to_string())?;
+    if text.trim().is_empty() {
+        return Err("empty body".to_string());
+    }
+
+    let incoming = match serde_json::from_str::<IncomingEvent>(text) {
+        Ok(single) => vec![single],
+        Err(_) => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str::<IncomingEvent>(line)
+                    .map_err(|e| format!("line {}: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    if incoming.len() > MAX_BATCH_EVENTS {
+        return Err(format!(
+            "batch of {} events exceeds the limit of {}",
+            incoming.len(),
+            MAX_BATCH_EVENTS
+        ));
+    }
+    for (i, item) in incoming.iter().enumerate() {
+        item.validate().map_err(|e| {
+            if incoming.len() == 1 {
+                e
+            } else {
+                format!("event {}: {}", i + 1, e)
+            }
+        })?;
+    }
+    Ok(incoming
+        .into_iter()
+        .map(|item| item.into_event(app))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorConfig;
+    use crate::types::State;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+
+    fn make_test_app() -> App {
+        App {
+            state: Arc::new(Mutex::new(State::default())),
+            sse_clients: Arc::new(Mutex::new(Vec::new())),
+            event_seq: Arc::new(AtomicU64::new(1)),
+            public_dir: Arc::new(PathBuf::from("public")),
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
+        }
+    }
+
+    #[test]
+    fn test_parse_single_event_fills_defaults() {
+        let app = make_test_app();
+        let events = parse_events_body(br#"{"event":"deploy","message":"v1.2"}"#, &app).unwrap();
+        assert_eq!(events.len(), 1);
+        let evt = &events[0];
+        assert_eq!(evt.id, "e1");
+        assert_eq!(evt.agent_id, "manual");
+        assert_eq!(evt.status, "ok");
+        assert_eq!(evt.metadata, json!({}));
+        assert!(!evt.timestamp.is_empty());
+    }
+
+    #[test]
+    fn test_parse_ndjson_batch() {
+        let app = make_test_app();
+        let body = b"{\"event\":\"a\",\"agentId\":\"ci\"}\n\n{\"event\":\"b\",\"status\":\"error\",\"metadata\":{\"source\":\"ci\"}}\n";
+        let events = parse_events_body(body, &app).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].agent_id, "ci");
+        assert_eq!(events[1].status, "error");
+        assert_eq!(events[1].metadata["source"], "ci");
+        assert_eq!(events[1].id, "e2");
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_line_without_assigning_ids() {
+        let app = make_test_app();
+        let body = b"{\"event\":\"a\"}\n{\"event\":\"b\",\"status\":\"bogus\"}\n";
+        let err = parse_events_body(body, &app).err().unwrap();
+        assert!(err.starts_with("event 2:"), "{}", err);
+        assert_eq!(app.event_seq.load(Ordering::Relaxed), 1);
+
+        let err = parse_events_body(b"{\"event\":\"a\"}\nnot json\n", &app)
+            .err()
+            .unwrap();
+        assert!(err.starts_with("line 2:"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_fields() {
+        let app = make_test_app();
+        assert!(parse_events_body(br#"{"event":""}"#, &app).is_err());
+        assert!(parse_events_body(br#"{"message":"no type"}"#, &app).is_err());
+        assert!(parse_events_body(br#"{"event":"a","metadata":[1]}"#, &app).is_err());
+        assert!(parse_events_body(br#"{"event":"a","timestamp":"yesterday"}"#, &app).is_err());
+        assert!(parse_events_body(b"   ", &app).is_err());
+    }
+}
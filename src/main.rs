@@ -1,18 +1,43 @@
+mod alerts;
+mod budgets;
 mod collector;
+mod config;
 mod db;
+mod db_writer;
+mod forecast;
+mod hooks;
 mod http;
+mod ingest;
+mod metrics;
+mod models;
+mod notify;
+mod otel;
+mod pricing;
+mod projects;
+mod rules;
+mod session_state;
+mod sessions;
 mod state;
 mod types;
 mod utils;
+mod watcher;
 
 use std::path::PathBuf;
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
-use collector::spawn_claude_collector;
+use collector::{spawn_claude_collector, CollectorOptions};
+use config::MonitorConfig;
 use db::Db;
+use db_writer::DbWriter;
 use http::{handle_client, spawn_sse_sweeper};
+use notify::Notifier;
+use rules::spawn_rule_evaluator;
+use state::{
+    flush_dirty_projects, restore_from_db, spawn_project_flusher, spawn_session_state_ticker,
+};
 use types::{App, State};
 use utils::now_iso;
 
@@ -27,10 +52,29 @@ fn main() {
         .ok()
         .and_then(|v| v.parse::<usize>().ok())
         .unwrap_or(25);
+    let dormant_after_sec = std::env::var("CLAUDE_DORMANT_AFTER_SEC")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(24 * 60 * 60);
+    let dormant_recheck_sec = std::env::var("CLAUDE_DORMANT_RECHECK_SEC")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300);
+    let watch = std::env::var("CLAUDE_WATCH")
+        .map(|v| v != "0" && !v.eq_ignore_ascii_case("false"))
+        .unwrap_or(true);
     let retention_days: i64 = std::env::var("MONITOR_RETENTION_DAYS")
         .ok()
         .and_then(|v| v.parse().ok())
         .unwrap_or(90);
+    let db_batch_ms = std::env::var("MONITOR_DB_BATCH_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(250);
+    let db_batch_rows = std::env::var("MONITOR_DB_BATCH_ROWS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(500);
     let claude_home = std::env::var("CLAUDE_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
@@ -39,12 +83,40 @@ fn main() {
                 .unwrap_or_else(|_| ".".to_string());
             PathBuf::from(home).join(".claude")
         });
+    let config_path = std::env::var("MONITOR_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| claude_home.join("monitor.json"));
+    let config = match MonitorConfig::load(&config_path) {
+        Ok(c) => c,
+        Err(e) => match MonitorConfig::fallback(&config_path) {
+            Some(fallback) => {
+                eprintln!(
+                    "[config] failed to load {}: {} — using defaults (ingestToken kept)",
+                    config_path.display(),
+                    e
+                );
+                fallback
+            }
+            None => {
+                eprintln!(
+                    "[config] failed to load {}: {} — refusing to start without knowing ingestToken",
+                    config_path.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        },
+    };
     let listener = std::net::TcpListener::bind(format!("{}:{}", host, port)).expect("bind failed");
 
     let db_path = claude_home.join("monitor.db");
     let db = match Db::open(&db_path) {
         Ok(d) => {
-            println!("[db] opened {}", db_path.display());
+            println!(
+                "[db] opened {} (schema v{})",
+                db_path.display(),
+                d.schema_version().unwrap_or_default()
+            );
             Some(d)
         }
         Err(e) => {
@@ -59,6 +131,7 @@ fn main() {
 
     let mut initial_state = State {
         started_at: now_iso(),
+        session_state_config: config.session_state.clone(),
         ..State::default()
     };
     if let Some(ref db) = db {
@@ -69,23 +142,85 @@ fn main() {
             initial_state.token_total = tokens;
             initial_state.cost_total_usd = cost;
         }
+        if let Ok(usage) = db.query_usage_totals() {
+            initial_state.usage_total = usage;
+        }
+        match restore_from_db(&mut initial_state, db) {
+            Ok(()) => println!(
+                "[db] restored {} sessions, {} agents, {} alerts",
+                initial_state.by_session.len(),
+                initial_state.by_agent.len(),
+                initial_state.alerts.len()
+            ),
+            Err(e) => eprintln!("[db] restore failed: {e}"),
+        }
     }
+    let saved_cursors = match db.as_ref().map(|d| d.load_cursors()) {
+        Some(Ok(cursors)) => cursors,
+        Some(Err(e)) => {
+            eprintln!("[db] failed to load collector cursors: {e}");
+            Default::default()
+        }
+        None => Default::default(),
+    };
+    let next_seq = db
+        .as_ref()
+        .and_then(|d| d.max_id_sequence().ok())
+        .unwrap_or(0)
+        + 1;
+
+    let db_writer = db.as_ref().and_then(|_| match Db::open(&db_path) {
+        Ok(conn) => Some(DbWriter::spawn(conn, db_batch_ms, db_batch_rows).0),
+        Err(e) => {
+            eprintln!("[db] failed to open writer connection: {e}");
+            None
+        }
+    });
+    let db_reader = db.as_ref().and_then(|_| match Db::open(&db_path) {
+        Ok(conn) => Some(Arc::new(Mutex::new(conn))),
+        Err(e) => {
+            eprintln!("[db] failed to open reader connection: {e}");
+            None
+        }
+    });
 
-    let db_arc = db.map(|d| Arc::new(Mutex::new(d)));
+    let notifier = (!config.notifiers.is_empty()).then(|| {
+        Notifier::spawn(
+            config.notifiers.clone(),
+            &config.alert_rules,
+            db_writer.clone(),
+        )
+        .0
+    });
 
     let app = App {
         state: Arc::new(Mutex::new(initial_state)),
         sse_clients: Arc::new(Mutex::new(Vec::new())),
-        event_seq: Arc::new(AtomicU64::new(1)),
+        event_seq: Arc::new(AtomicU64::new(next_seq)),
         public_dir: Arc::new(
             std::env::var("PUBLIC_DIR")
                 .map(PathBuf::from)
                 .unwrap_or_else(|_| PathBuf::from("public")),
         ),
-        db: db_arc.clone(),
+        db_writer,
+        notifier,
+        db_reader,
+        config: Arc::new(config),
     };
+    if let Some(writer) = app.db_writer.clone() {
+        // Commit whatever is still queued before the process goes away.
+        let shutdown_app = app.clone();
+        let handler = ctrlc::set_handler(move || {
+            flush_dirty_projects(&shutdown_app);
+            writer.flush();
+            std::process::exit(0);
+        });
+        if let Err(e) = handler {
+            eprintln!("[db] failed to install shutdown handler: {e}");
+        }
+    }
 
-    if let Some(db_arc) = db_arc {
+    if let Some(db) = db {
         thread::spawn(move || {
             let interval = std::time::Duration::from_secs(24 * 60 * 60);
             loop {
@@ -102,18 +237,34 @@ fn main() {
                 } else {
                     ""
                 };
-                if let Ok(db) = db_arc.lock() {
-                    match db.prune_before(before_hour) {
-                        Ok(n) if n > 0 => println!("[db] pruned {} old buckets", n),
-                        _ => {}
-                    }
+                match db.prune_before(before_hour) {
+                    Ok(n) if n > 0 => println!("[db] pruned {} old buckets", n),
+                    _ => {}
+                }
+                match db.prune_records_before(&before_key) {
+                    Ok(n) if n > 0 => println!("[db] pruned {} old records", n),
+                    _ => {}
                 }
             }
         });
     }
 
-    spawn_claude_collector(app.clone(), claude_home, poll_ms, backfill_lines);
+    spawn_claude_collector(
+        app.clone(),
+        claude_home,
+        CollectorOptions {
+            poll_ms,
+            backfill_lines,
+            watch,
+            dormant_after: Duration::from_secs(dormant_after_sec),
+            dormant_recheck: Duration::from_secs(dormant_recheck_sec),
+        },
+        saved_cursors,
+    );
     spawn_sse_sweeper(app.clone());
+    spawn_rule_evaluator(app.clone());
+    spawn_session_state_ticker(app.clone());
+    spawn_project_flusher(app.clone());
 
     println!("Claude Code Monitor listening on http://{}:{}", host, port);
 
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use time::OffsetDateTime;
+
+use crate::state::session_state_at;
+use crate::types::{State, TokenBreakdown};
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const SESSION_STATES: [&str; 6] = ["active", "busy", "idle", "stuck", "completed", "failed"];
+
+/// Renders the monitor state in the OpenMetrics text format for `GET /metrics`.
+///
+/// Per-project and per-session-state totals are gauges: they are summed over the
+/// sessions currently held in memory, and a session moves between states.
+pub fn render_openmetrics(state: &State, sse_clients: usize, now: OffsetDateTime) -> String {
+    let mut out = String::new();
+
+    family(
+        &mut out,
+        "claude_monitor_events",
+        "counter",
+        "Events received.",
+    );
+    let mut event_counts: Vec<_> = state.event_counts.iter().collect();
+    event_counts.sort();
+    for ((source, event, status), count) in event_counts {
+        sample(
+            &mut out,
+            "claude_monitor_events_total",
+            &[("source", source), ("event", event), ("status", status)],
+            *count as f64,
+        );
+    }
+
+    family(
+        &mut out,
+        "claude_monitor_model_tokens",
+        "counter",
+        "Tokens by model and kind since the monitor started.",
+    );
+    let models: BTreeMap<_, _> = state.by_model.iter().collect();
+    for (model, row) in &models {
+        for (kind, tokens) in token_kinds(&row.usage) {
+            sample(
+                &mut out,
+                "claude_monitor_model_tokens_total",
+                &[("model", model), ("kind", kind)],
+                tokens as f64,
+            );
+        }
+    }
+    family(
+        &mut out,
+        "claude_monitor_model_cost_usd",
+        "counter",
+        "Cost in USD by model since the monitor started.",
+    );
+    for (model, row) in &models {
+        sample(
+            &mut out,
+            "claude_monitor_model_cost_usd_total",
+            &[("model", model)],
+            row.cost_usd,
+        );
+    }
+    family(
+        &mut out,
+        "claude_monitor_model_requests",
+        "counter",
+        "Events that reported usage, by model, since the monitor started.",
+    );
+    for (model, row) in &models {
+        sample(
+            &mut out,
+            "claude_monitor_model_requests_total",
+            &[("model", model)],
+            row.requests as f64,
+        );
+    }
+    family(
+        &mut out,
+        "claude_monitor_model_errors",
+        "counter",
+        "Error events by model since the monitor started.",
+    );
+    for (model, row) in &models {
+        sample(
+            &mut out,
+            "claude_monitor_model_errors_total",
+            &[("model", model)],
+            row.errors as f64,
+        );
+    }
+
+    let mut by_project: BTreeMap<&str, (u64, f64)> = BTreeMap::new();
+    let mut by_state: BTreeMap<String, (u64, u64, f64)> = SESSION_STATES
+        .iter()
+        .map(|s| (s.to_string(), (0, 0, 0.0)))
+        .collect();
+    for session in state.by_session.values() {
+        let project = by_project.entry(&session.project_name).or_default();
+        project.0 += session.token_total;
+        project.1 += session.cost_usd;
+        let totals = by_state
+            .entry(session_state_at(state, session, now))
+            .or_default();
+        totals.0 += 1;
+        totals.1 += session.token_total;
+        totals.2 += session.cost_usd;
+    }
+
+    family(
+        &mut out,
+        "claude_monitor_project_tokens",
+        "gauge",
+        "Tokens of the sessions held in memory, by project.",
+    );
+    for (project, (tokens, _)) in &by_project {
+        sample(
+            &mut out,
+            "claude_monitor_project_tokens",
+            &[("project", project)],
+            *tokens as f64,
+        );
+    }
+    family(
+        &mut out,
+        "claude_monitor_project_cost_usd",
+        "gauge",
+        "Cost in USD of the sessions held in memory, by project.",
+    );
+    for (project, (_, cost)) in &by_project {
+        sample(
+            &mut out,
+            "claude_monitor_project_cost_usd",
+            &[("project", project)],
+            *cost,
+        );
+    }
+
+    family(
+        &mut out,
+        "claude_monitor_sessions",
+        "gauge",
+        "Sessions by current state.",
+    );
+    for (session_state, (count, _, _)) in &by_state {
+        sample(
+            &mut out,
+            "claude_monitor_sessions",
+            &[("state", session_state)],
+            *count as f64,
+        );
+    }
+    family(
+        &mut out,
+        "claude_monitor_session_tokens",
+        "gauge",
+        "Tokens of the sessions held in memory, by session state.",
+    );
+    for (session_state, (_, tokens, _)) in &by_state {
+        sample(
+            &mut out,
+            "claude_monitor_session_tokens",
+            &[("state", session_state)],
+            *tokens as f64,
+        );
+    }
+    family(
+        &mut out,
+        "claude_monitor_session_cost_usd",
+        "gauge",
+        "Cost in USD of the sessions held in memory, by session state.",
+    );
+    for (session_state, (_, _, cost)) in &by_state {
+        sample(
+            &mut out,
+            "claude_monitor_session_cost_usd",
+            &[("state", session_state)],
+            *cost,
+        );
+    }
+
+    family(
+        &mut out,
+        "claude_monitor_tool_calls",
+        "counter",
+        "Tool calls by tool name.",
+    );
+    let tools: BTreeMap<_, _> = state.tool_use_counts.iter().collect();
+    for (tool, count) in tools {
+        sample(
+            &mut out,
+            "claude_monitor_tool_calls_total",
+            &[("tool", tool)],
+            *count as f64,
+        );
+    }
+
+    family(
+        &mut out,
+        "claude_monitor_sse_clients",
+        "gauge",
+        "Connected SSE clients.",
+    );
+    sample(
+        &mut out,
+        "claude_monitor_sse_clients",
+        &[],
+        sse_clients as f64,
+    );
+
+    family(
+        &mut out,
+        "claude_monitor_collector_scan_duration_seconds",
+        "gauge",
+        "Duration of the collector's last full scan of projects/.",
+    );
+    sample(
+        &mut out,
+        "claude_monitor_collector_scan_duration_seconds",
+        &[],
+        state.collector_scan.scan_ms as f64 / 1000.0,
+    );
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn token_kinds(usage: &TokenBreakdown) -> [(&'static str, u64); 4] {
+    [
+        ("input", usage.input_tokens),
+        ("output", usage.output_tokens),
+        ("cache_read", usage.cache_read_input_tokens),
+        ("cache_creation", usage.cache_creation_input_tokens),
+    ]
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, val)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", key, escape_label(val));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{test_rows, AgentRow, ModelUsage, SessionRow};
+    use time::format_description::well_known::Rfc3339;
+
+    fn session(id: &str, project: &str, last_seen: &str, tokens: u64, cost: f64) -> SessionRow {
+        SessionRow {
+            token_total: tokens,
+            ..test_rows::session(id, project, last_seen, cost)
+        }
+    }
+
+    fn agent(id: &str, session_id: &str, last_seen: &str, error: u64) -> AgentRow {
+        AgentRow {
+            total: 3,
+            ok: 3 - error,
+            error,
+            ..test_rows::agent(id, session_id, last_seen)
+        }
+    }
+
+    #[test]
+    fn test_render_openmetrics_families_and_labels() {
+        let now = OffsetDateTime::parse("2025-01-01T00:10:00Z", &Rfc3339).unwrap();
+        let mut state = State::default();
+        state.event_counts.insert(
+            ("claude_session".into(), "tool_call".into(), "ok".into()),
+            4,
+        );
+        state.by_model.insert(
+            "claude-sonnet-4".to_string(),
+            ModelUsage {
+                requests: 3,
+                errors: 1,
+                token_total: 150,
+                cost_usd: 0.25,
+                usage: TokenBreakdown {
+                    input_tokens: 100,
+                    output_tokens: 50,
+                    ..TokenBreakdown::default()
+                },
+            },
+        );
+        for (id, project, last_seen, error) in [
+            ("s1", "web", "2025-01-01T00:09:50Z", 0),
+            ("s2", "web", "2025-01-01T00:05:00Z", 0),
+            ("s3", "api \"v2\"", "2025-01-01T00:09:00Z", 1),
+        ] {
+            state
+                .by_session
+                .insert(id.to_string(), session(id, project, last_seen, 10, 0.5));
+            state.by_agent.insert(
+                format!("agent-{}", id),
+                agent(&format!("agent-{}", id), id, last_seen, error),
+            );
+        }
+        state.tool_use_counts.insert("Bash".to_string(), 7);
+        state.collector_scan.scan_ms = 1_500;
+
+        let text = render_openmetrics(&state, 2, now);
+        assert!(text.ends_with("# EOF\n"));
+        assert!(text.contains(
+            "claude_monitor_events_total{source=\"claude_session\",event=\"tool_call\",status=\"ok\"} 4\n"
+        ));
+        assert!(text.contains(
+            "claude_monitor_model_tokens_total{model=\"claude-sonnet-4\",kind=\"input\"} 100\n"
+        ));
+        assert!(
+            text.contains("claude_monitor_model_cost_usd_total{model=\"claude-sonnet-4\"} 0.25\n")
+        );
+        assert!(text.contains("claude_monitor_model_requests_total{model=\"claude-sonnet-4\"} 3\n"));
+        assert!(text.contains("claude_monitor_model_errors_total{model=\"claude-sonnet-4\"} 1\n"));
+        assert!(text.contains("claude_monitor_project_tokens{project=\"web\"} 20\n"));
+        assert!(text.contains("claude_monitor_project_cost_usd{project=\"api \\\"v2\\\"\"} 0.5\n"));
+        assert!(text.contains("claude_monitor_sessions{state=\"active\"} 1\n"));
+        assert!(text.contains("claude_monitor_sessions{state=\"stuck\"} 1\n"));
+        assert!(text.contains("claude_monitor_sessions{state=\"failed\"} 1\n"));
+        assert!(text.contains("claude_monitor_sessions{state=\"completed\"} 0\n"));
+        assert!(text.contains("claude_monitor_tool_calls_total{tool=\"Bash\"} 7\n"));
+        assert!(text.contains("claude_monitor_sse_clients 2\n"));
+        assert!(text.contains("claude_monitor_collector_scan_duration_seconds 1.5\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::types::{HourBucket, ModelUsage, TokenBreakdown};
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelMixRow {
+    pub model: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub token_total: u64,
+    pub cost_usd: f64,
+    pub usage: TokenBreakdown,
+    /// Fraction (0–1) of the scope's cost spent on this model.
+    pub cost_share: f64,
+    pub token_share: f64,
+    /// Only reported for the global scope.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hourly_buckets: Option<Vec<HourBucket>>,
+}
+
+fn share(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        part / whole
+    } else {
+        0.0
+    }
+}
+
+/// One row per model, highest cost first. `hourly` (model → buckets) is
+/// attached to the matching rows when given.
+pub fn model_mix(
+    models: &HashMap<String, ModelUsage>,
+    hourly: Option<&HashMap<String, Vec<HourBucket>>>,
+    since_key: &str,
+) -> Vec<ModelMixRow> {
+    let cost_total: f64 = models.values().map(|m| m.cost_usd).sum();
+    let token_total: u64 = models.values().map(|m| m.token_total).sum();
+    let mut rows: Vec<ModelMixRow> = models
+        .iter()
+        .map(|(model, row)| ModelMixRow {
+            model: model.clone(),
+            requests: row.requests,
+            errors: row.errors,
+            token_total: row.token_total,
+            cost_usd: row.cost_usd,
+            usage: row.usage,
+            cost_share: share(row.cost_usd, cost_total),
+            token_share: share(row.token_total as f64, token_total as f64),
+            hourly_buckets: hourly.map(|hourly| {
+                hourly
+                    .get(model)
+                    .map(|buckets| {
+                        buckets
+                            .iter()
+                            .filter(|b| b.hour_key.as_str() >= since_key)
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then_with(|| b.token_total.cmp(&a.token_total))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(requests: u64, tokens: u64, cost_usd: f64) -> ModelUsage {
+        ModelUsage {
+            requests,
+            errors: 0,
+            token_total: tokens,
+            cost_usd,
+            usage: TokenBreakdown::default(),
+        }
+    }
+
+    fn bucket(hour_key: &str) -> HourBucket {
+        HourBucket {
+            hour_key: hour_key.to_string(),
+            token_total: 1,
+            cost_usd: 0.1,
+            usage: TokenBreakdown::default(),
+        }
+    }
+
+    #[test]
+    fn test_model_mix_shares_and_order() {
+        let models = HashMap::from([
+            ("claude-sonnet-4".to_string(), usage(8, 800, 1.0)),
+            ("claude-opus-4".to_string(), usage(2, 200, 3.0)),
+        ]);
+        let rows = model_mix(&models, None, "");
+        assert_eq!(rows[0].model, "claude-opus-4");
+        assert!((rows[0].cost_share - 0.75).abs() < 1e-9);
+        assert!((rows[0].token_share - 0.2).abs() < 1e-9);
+        assert!(rows[0].hourly_buckets.is_none());
+    }
+
+    #[test]
+    fn test_model_mix_hourly_since() {
+        let models = HashMap::from([("claude-opus-4".to_string(), usage(1, 0, 0.0))]);
+        let hourly = HashMap::from([(
+            "claude-opus-4".to_string(),
+            vec![bucket("2025-01-01T10"), bucket("2025-01-02T10")],
+        )]);
+        let rows = model_mix(&models, Some(&hourly), "2025-01-02T00");
+        let buckets = rows[0].hourly_buckets.as_ref().unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].hour_key, "2025-01-02T10");
+        assert_eq!(rows[0].cost_share, 0.0);
+    }
+}
@@ -0,0 +1,784 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::alerts::STATUS_OPEN;
+use crate::db::DbWrite;
+use crate::db_writer::DbWriter;
+use crate::rules::AlertRule;
+use crate::types::AlertRow;
+use crate::utils::now_iso;
+
+const QUEUE_CAPACITY: usize = 1_000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const RECENT_DELIVERIES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotifierKind {
+    /// The alert as JSON: `{"type":"alert","alert":{...}}`.
+    Webhook,
+    /// Slack incoming webhook (`{"text": ...}`).
+    Slack,
+    /// Discord webhook (`{"content": ..., "embeds": [...]}`).
+    Discord,
+    /// Local command; the alert JSON goes to its stdin.
+    Exec,
+}
+
+/// One entry of `notifiers` in `monitor.json`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotifierConfig {
+    pub id: String,
+    pub kind: NotifierKind,
+    /// Target of `webhook`, `slack` and `discord` notifiers.
+    #[serde(default)]
+    pub url: String,
+    /// Program and arguments of an `exec` notifier (no shell involved).
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Commands of one `exec` notifier allowed to run at once; further alerts
+    /// are skipped until one finishes.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Alert severities this sink receives.
+    #[serde(default = "default_severities")]
+    pub severities: Vec<String>,
+    /// Minimum gap between two notifications for the same rule (or, for
+    /// event alerts, the same fingerprint). A rule's `cooldownSecs` wins.
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every further retry.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+fn default_severities() -> Vec<String> {
+    vec!["warning".to_string(), "error".to_string()]
+}
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    1_000
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_concurrent() -> usize {
+    2
+}
+
+pub fn validate_notifiers(notifiers: &[NotifierConfig]) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for notifier in notifiers {
+        if notifier.id.trim().is_empty() {
+            return Err("notifier id must not be empty".to_string());
+        }
+        if !ids.insert(notifier.id.as_str()) {
+            return Err(format!("duplicate notifier id {:?}", notifier.id));
+        }
+        if notifier.kind == NotifierKind::Exec {
+            if notifier.command.is_empty() || notifier.command[0].trim().is_empty() {
+                return Err(format!("notifier {:?}: command is required", notifier.id));
+            }
+            if notifier.timeout_secs == 0 || notifier.max_concurrent == 0 {
+                return Err(format!(
+                    "notifier {:?}: timeoutSecs and maxConcurrent must be >= 1",
+                    notifier.id
+                ));
+            }
+        } else if !notifier.url.starts_with("http://") && !notifier.url.starts_with("https://") {
+            return Err(format!("notifier {:?}: url must be http(s)", notifier.id));
+        }
+        if let Some(bad) = notifier
+            .severities
+            .iter()
+            .find(|s| *s != "warning" && *s != "error")
+        {
+            return Err(format!(
+                "notifier {:?}: unknown severity {:?}",
+                notifier.id, bad
+            ));
+        }
+        if notifier.max_attempts == 0 {
+            return Err(format!(
+                "notifier {:?}: maxAttempts must be >= 1",
+                notifier.id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// One row of the delivery log.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationDelivery {
+    pub alert_id: String,
+    pub notifier_id: String,
+    pub kind: NotifierKind,
+    /// `delivered`, `failed`, or `skipped` (exec concurrency limit).
+    pub status: String,
+    pub attempts: u32,
+    pub http_status: Option<u16>,
+    pub exit_code: Option<i32>,
+    pub error: String,
+    pub sent_at: String,
+}
+
+pub fn alert_payload(kind: NotifierKind, alert: &AlertRow) -> Value {
+    match kind {
+        NotifierKind::Webhook | NotifierKind::Exec => json!({ "type": "alert", "alert": alert }),
+        NotifierKind::Slack => json!({ "text": alert_text(alert, true) }),
+        NotifierKind::Discord => json!({
+            "content": alert_text(alert, false),
+            "embeds": [{
+                "title": alert.event,
+                "description": alert.message,
+                "color": if alert.severity == "error" { 0xE5484D } else { 0xF5A524 },
+                "timestamp": alert.last_seen,
+            }],
+        }),
+    }
+}
+
+fn alert_text(alert: &AlertRow, slack: bool) -> String {
+    let bold = |s: &str| {
+        if slack {
+            format!("*{}*", s)
+        } else {
+            format!("**{}**", s)
+        }
+    };
+    let mut text = format!(
+        "[{}] {} — {}",
+        alert.severity.to_uppercase(),
+        bold(&alert.event),
+        alert.message
+    );
+    let subject = [
+        ("agent", alert.agent_id.as_str()),
+        ("session", alert.session_id.as_str()),
+    ]
+    .iter()
+    .filter(|(_, v)| !v.is_empty())
+    .map(|(k, v)| format!("{} {}", k, v))
+    .collect::<Vec<_>>()
+    .join(", ");
+    if !subject.is_empty() {
+        text.push_str(&format!(" ({})", subject));
+    }
+    if alert.count > 1 {
+        text.push_str(&format!(" ×{}", alert.count));
+    }
+    text
+}
+
+/// Posts `alert` to one sink, retrying transport errors, 429 and 5xx with
+/// exponential backoff. Other 4xx responses are not retried.
+pub fn deliver(notifier: &NotifierConfig, alert: &AlertRow) -> NotificationDelivery {
+    let payload = alert_payload(notifier.kind, alert);
+    let mut attempts = 0;
+    let mut http_status = None;
+    let mut error = String::new();
+    let mut delivered = false;
+    while attempts < notifier.max_attempts {
+        if attempts > 0 {
+            let backoff = notifier
+                .backoff_ms
+                .saturating_mul(1 << (attempts - 1).min(16));
+            thread::sleep(Duration::from_millis(backoff));
+        }
+        attempts += 1;
+        let result = ureq::post(&notifier.url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Content-Type", "application/json")
+            .send_string(&payload.to_string());
+        match result {
+            Ok(resp) => {
+                http_status = Some(resp.status());
+                error.clear();
+                delivered = true;
+                break;
+            }
+            Err(ureq::Error::Status(code, _)) => {
+                http_status = Some(code);
+                error = format!("HTTP {}", code);
+                if code != 429 && code < 500 {
+                    break;
+                }
+            }
+            Err(e) => {
+                http_status = None;
+                error = e.to_string();
+            }
+        }
+    }
+    NotificationDelivery {
+        alert_id: alert.id.clone(),
+        notifier_id: notifier.id.clone(),
+        kind: notifier.kind,
+        status: if delivered { "delivered" } else { "failed" }.to_string(),
+        attempts,
+        http_status,
+        exit_code: None,
+        error,
+        sent_at: now_iso(),
+    }
+}
+
+/// Runs an `exec` notifier's command with the alert as JSON on stdin and its
+/// key fields in `ALERT_*` environment variables. The command is killed once
+/// `timeoutSecs` have passed.
+pub fn run_exec(notifier: &NotifierConfig, alert: &AlertRow) -> NotificationDelivery {
+    let outcome = spawn_and_wait(notifier, alert);
+    let (delivered, exit_code, error) = match outcome {
+        Ok(Some(status)) if status.success() => (true, status.code(), String::new()),
+        Ok(Some(status)) => (false, status.code(), format!("exited with {}", status)),
+        Ok(None) => (
+            false,
+            None,
+            format!("timed out after {}s", notifier.timeout_secs),
+        ),
+        Err(e) => (false, None, e.to_string()),
+    };
+    NotificationDelivery {
+        alert_id: alert.id.clone(),
+        notifier_id: notifier.id.clone(),
+        kind: notifier.kind,
+        status: if delivered { "delivered" } else { "failed" }.to_string(),
+        attempts: 1,
+        http_status: None,
+        exit_code,
+        error,
+        sent_at: now_iso(),
+    }
+}
+
+/// `Ok(None)` when the command had to be killed.
+fn spawn_and_wait(
+    notifier: &NotifierConfig,
+    alert: &AlertRow,
+) -> std::io::Result<Option<ExitStatus>> {
+    let (program, args) = notifier
+        .command
+        .split_first()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty command"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .env("ALERT_ID", &alert.id)
+        .env("ALERT_SEVERITY", &alert.severity)
+        .env("ALERT_STATUS", &alert.status)
+        .env("ALERT_EVENT", &alert.event)
+        .env("ALERT_MESSAGE", &alert.message)
+        .env("ALERT_AGENT_ID", &alert.agent_id)
+        .env("ALERT_SESSION_ID", &alert.session_id)
+        .env("ALERT_RULE_ID", alert.rule_id.as_deref().unwrap_or(""))
+        .env("ALERT_COUNT", alert.count.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let body = serde_json::to_vec(alert).unwrap_or_default();
+        // A command that ignores stdin may exit before reading it; that is fine.
+        let _ = stdin.write_all(&body);
+    }
+    let deadline = Instant::now() + Duration::from_secs(notifier.timeout_secs);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(EXEC_POLL_INTERVAL);
+    }
+}
+
+#[derive(Default)]
+struct NotifierStats {
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    suppressed: AtomicU64,
+    skipped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Where delivery outcomes go: counters, the DB log and a short in-memory tail.
+/// Cloned into `exec` worker threads, which finish after `dispatch` returns.
+#[derive(Clone, Default)]
+pub struct DeliveryLog {
+    stats: Arc<NotifierStats>,
+    db_writer: Option<DbWriter>,
+    recent: Arc<Mutex<VecDeque<NotificationDelivery>>>,
+}
+
+impl DeliveryLog {
+    pub fn new(db_writer: Option<DbWriter>) -> Self {
+        Self {
+            db_writer,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&self, delivery: NotificationDelivery) {
+        let counter = match delivery.status.as_str() {
+            "delivered" => &self.stats.delivered,
+            "skipped" => &self.stats.skipped,
+            _ => &self.stats.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if delivery.status != "delivered" {
+            eprintln!(
+                "[notify] {} {} alert {} after {} attempt(s): {}",
+                delivery.notifier_id,
+                delivery.status,
+                delivery.alert_id,
+                delivery.attempts,
+                delivery.error
+            );
+        }
+        if let Some(writer) = &self.db_writer {
+            writer.send(DbWrite::Delivery(Box::new(delivery.clone())));
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.push_front(delivery);
+        recent.truncate(RECENT_DELIVERIES);
+    }
+
+    /// Latest deliveries first.
+    pub fn recent(&self) -> Vec<NotificationDelivery> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().cloned().collect()
+    }
+
+    fn health(&self) -> Value {
+        json!({
+            "delivered": self.stats.delivered.load(Ordering::Relaxed),
+            "failed": self.stats.failed.load(Ordering::Relaxed),
+            "suppressed": self.stats.suppressed.load(Ordering::Relaxed),
+            "skipped": self.stats.skipped.load(Ordering::Relaxed),
+            "dropped": self.stats.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Routes alerts to sinks and applies cooldowns. Lives on the notifier thread.
+pub struct Dispatcher {
+    notifiers: Vec<NotifierConfig>,
+    rule_cooldowns: HashMap<String, u64>,
+    last_sent: HashMap<(String, String), Instant>,
+    /// Commands still running, per `exec` notifier.
+    exec_running: HashMap<String, Arc<AtomicUsize>>,
+    log: DeliveryLog,
+}
+
+impl Dispatcher {
+    pub fn new(notifiers: Vec<NotifierConfig>, rules: &[AlertRule], log: DeliveryLog) -> Self {
+        Self {
+            notifiers,
+            rule_cooldowns: rules
+                .iter()
+                .filter_map(|r| r.cooldown_secs.map(|secs| (r.id.clone(), secs)))
+                .collect(),
+            last_sent: HashMap::new(),
+            exec_running: HashMap::new(),
+            log,
+        }
+    }
+
+    /// Sends `alert` to every sink that takes its severity and is not cooling
+    /// down. HTTP sinks are called in turn; `exec` commands run on their own
+    /// threads, up to `maxConcurrent` per notifier.
+    pub fn dispatch(&mut self, alert: &AlertRow) {
+        let cooldown_key = alert
+            .rule_id
+            .clone()
+            .unwrap_or_else(|| alert.fingerprint.clone());
+        for notifier in &self.notifiers {
+            if !notifier.severities.contains(&alert.severity) {
+                continue;
+            }
+            let cooldown = alert
+                .rule_id
+                .as_ref()
+                .and_then(|id| self.rule_cooldowns.get(id))
+                .copied()
+                .unwrap_or(notifier.cooldown_secs);
+            let key = (notifier.id.clone(), cooldown_key.clone());
+            if self
+                .last_sent
+                .get(&key)
+                .is_some_and(|at| at.elapsed() < Duration::from_secs(cooldown))
+            {
+                self.log.stats.suppressed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            if notifier.kind != NotifierKind::Exec {
+                let delivery = deliver(notifier, alert);
+                if delivery.status == "delivered" {
+                    self.last_sent.insert(key, Instant::now());
+                }
+                self.log.record(delivery);
+                continue;
+            }
+
+            let running = self.exec_running.entry(notifier.id.clone()).or_default();
+            if running.load(Ordering::SeqCst) >= notifier.max_concurrent {
+                self.log.record(NotificationDelivery {
+                    alert_id: alert.id.clone(),
+                    notifier_id: notifier.id.clone(),
+                    kind: notifier.kind,
+                    status: "skipped".to_string(),
+                    attempts: 0,
+                    http_status: None,
+                    exit_code: None,
+                    error: format!(
+                        "{} command(s) still running",
+                        running.load(Ordering::SeqCst)
+                    ),
+                    sent_at: now_iso(),
+                });
+                continue;
+            }
+            running.fetch_add(1, Ordering::SeqCst);
+            self.last_sent.insert(key, Instant::now());
+            let (running, log) = (running.clone(), self.log.clone());
+            let (notifier, alert) = (notifier.clone(), alert.clone());
+            thread::spawn(move || {
+                let delivery = run_exec(&notifier, &alert);
+                running.fetch_sub(1, Ordering::SeqCst);
+                log.record(delivery);
+            });
+        }
+    }
+}
+
+/// Handle to the background thread that sends alert notifications, so a slow
+/// or unreachable webhook never holds up event ingestion.
+#[derive(Clone)]
+pub struct Notifier {
+    tx: SyncSender<AlertRow>,
+    log: DeliveryLog,
+}
+
+impl Notifier {
+    pub fn spawn(
+        notifiers: Vec<NotifierConfig>,
+        rules: &[AlertRule],
+        db_writer: Option<DbWriter>,
+    ) -> (Self, JoinHandle<()>) {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let log = DeliveryLog::new(db_writer);
+        let mut dispatcher = Dispatcher::new(notifiers, rules, log.clone());
+        let handle = thread::spawn(move || {
+            for alert in rx {
+                dispatcher.dispatch(&alert);
+            }
+        });
+        (Self { tx, log }, handle)
+    }
+
+    /// Queues `alert` if it is open; acked, snoozed and resolved alerts stay quiet.
+    pub fn notify(&self, alert: &AlertRow) {
+        if alert.status != STATUS_OPEN {
+            return;
+        }
+        match self.tx.try_send(alert.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.log.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn health(&self) -> Value {
+        self.log.health()
+    }
+
+    pub fn recent_deliveries(&self) -> Vec<NotificationDelivery> {
+        self.log.recent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answers one request per status in `statuses` and returns the bodies received.
+    fn spawn_webhook(statuses: Vec<u16>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn notifier(kind: NotifierKind, url: &str) -> NotifierConfig {
+        NotifierConfig {
+            id: "ops".to_string(),
+            kind,
+            url: url.to_string(),
+            command: Vec::new(),
+            timeout_secs: 10,
+            max_concurrent: 2,
+            severities: default_severities(),
+            cooldown_secs: 300,
+            max_attempts: 3,
+            backoff_ms: 1,
+        }
+    }
+
+    fn alert(severity: &str) -> AlertRow {
+        AlertRow {
+            id: "a1".to_string(),
+            severity: severity.to_string(),
+            agent_id: "lead-abc".to_string(),
+            session_id: "s1".to_string(),
+            event: "tool_result".to_string(),
+            message: "Bash failed".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            fingerprint: "lead-abc|s1|tool_result".to_string(),
+            count: 2,
+            last_seen: "2025-01-01T00:01:00Z".to_string(),
+            status: STATUS_OPEN.to_string(),
+            ..AlertRow::default()
+        }
+    }
+
+    #[test]
+    fn test_deliver_retries_server_errors() {
+        let (url, server) = spawn_webhook(vec![503, 200]);
+        let delivery = deliver(&notifier(NotifierKind::Webhook, &url), &alert("error"));
+        let bodies = server.join().unwrap();
+        assert_eq!(delivery.status, "delivered");
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.http_status, Some(200));
+        let body: Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(body["type"], "alert");
+        assert_eq!(body["alert"]["id"], "a1");
+    }
+
+    #[test]
+    fn test_deliver_gives_up_on_client_error() {
+        let (url, server) = spawn_webhook(vec![404]);
+        let delivery = deliver(&notifier(NotifierKind::Slack, &url), &alert("error"));
+        server.join().unwrap();
+        assert_eq!(delivery.status, "failed");
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.http_status, Some(404));
+    }
+
+    #[test]
+    fn test_slack_and_discord_payloads() {
+        let slack = alert_payload(NotifierKind::Slack, &alert("error"));
+        assert_eq!(
+            slack["text"],
+            "[ERROR] *tool_result* — Bash failed (agent lead-abc, session s1) ×2"
+        );
+        let discord = alert_payload(NotifierKind::Discord, &alert("warning"));
+        assert!(discord["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("[WARNING] **tool_result**"));
+        assert_eq!(discord["embeds"][0]["description"], "Bash failed");
+    }
+
+    #[test]
+    fn test_dispatcher_filters_severity_and_applies_cooldown() {
+        let (url, server) = spawn_webhook(vec![200]);
+        let mut errors_only = notifier(NotifierKind::Discord, &url);
+        errors_only.severities = vec!["error".to_string()];
+        let log = DeliveryLog::default();
+        let mut dispatcher = Dispatcher::new(vec![errors_only], &[], log.clone());
+
+        dispatcher.dispatch(&alert("warning"));
+        assert!(log.recent().is_empty());
+        dispatcher.dispatch(&alert("error"));
+        assert_eq!(log.recent().len(), 1);
+        dispatcher.dispatch(&alert("error"));
+        assert_eq!(log.recent().len(), 1);
+        assert_eq!(log.health()["suppressed"], 1);
+        assert_eq!(log.health()["delivered"], 1);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_rule_cooldown_overrides_notifier() {
+        let (url, server) = spawn_webhook(vec![200, 200]);
+        let rule: AlertRule = serde_json::from_value(json!({
+            "id": "cost", "scope": "session", "metric": "costUsd",
+            "threshold": 1, "cooldownSecs": 0
+        }))
+        .unwrap();
+        let log = DeliveryLog::default();
+        let mut dispatcher = Dispatcher::new(
+            vec![notifier(NotifierKind::Webhook, &url)],
+            &[rule],
+            log.clone(),
+        );
+        let mut rule_alert = alert("warning");
+        rule_alert.rule_id = Some("cost".to_string());
+        dispatcher.dispatch(&rule_alert);
+        dispatcher.dispatch(&rule_alert);
+        assert_eq!(log.recent().len(), 2);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_validate_notifiers() {
+        let ok = notifier(NotifierKind::Slack, "https://hooks.slack.com/services/x");
+        assert!(validate_notifiers(std::slice::from_ref(&ok)).is_ok());
+        assert!(validate_notifiers(&[ok.clone(), ok.clone()]).is_err());
+        let mut bad = ok.clone();
+        bad.url = "ftp://x".to_string();
+        assert!(validate_notifiers(&[bad]).is_err());
+        let mut bad = ok;
+        bad.severities = vec!["info".to_string()];
+        assert!(validate_notifiers(&[bad]).is_err());
+    }
+
+    #[test]
+    fn test_notify_skips_alerts_that_are_not_open() {
+        let (tx, rx) = mpsc::sync_channel(4);
+        let notifier = Notifier {
+            tx,
+            log: DeliveryLog::default(),
+        };
+        let mut acked = alert("error");
+        acked.status = "acked".to_string();
+        notifier.notify(&acked);
+        notifier.notify(&alert("error"));
+        assert_eq!(rx.try_iter().count(), 1);
+    }
+
+    fn exec_notifier(script: &str) -> NotifierConfig {
+        NotifierConfig {
+            id: "desktop".to_string(),
+            kind: NotifierKind::Exec,
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            timeout_secs: 1,
+            max_concurrent: 1,
+            cooldown_secs: 0,
+            ..notifier(NotifierKind::Exec, "")
+        }
+    }
+
+    #[test]
+    fn test_exec_passes_alert_on_stdin_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let script = format!(
+            "cat > {0}; echo \"$ALERT_SEVERITY $ALERT_SESSION_ID $ALERT_COUNT\" >> {0}",
+            out.display()
+        );
+        let delivery = run_exec(&exec_notifier(&script), &alert("error"));
+        assert_eq!(delivery.status, "delivered", "{}", delivery.error);
+        assert_eq!(delivery.exit_code, Some(0));
+        let written = std::fs::read_to_string(&out).unwrap();
+        let stdin = written
+            .strip_suffix("error s1 2\n")
+            .expect("env vars appended after stdin");
+        let stdin: Value = serde_json::from_str(stdin).unwrap();
+        assert_eq!(stdin["id"], "a1");
+        assert_eq!(stdin["message"], "Bash failed");
+    }
+
+    #[test]
+    fn test_exec_reports_exit_code_and_timeout() {
+        let failed = run_exec(&exec_notifier("exit 3"), &alert("error"));
+        assert_eq!(failed.status, "failed");
+        assert_eq!(failed.exit_code, Some(3));
+
+        let started = Instant::now();
+        let timed_out = run_exec(&exec_notifier("sleep 5"), &alert("error"));
+        assert_eq!(timed_out.status, "failed");
+        assert!(timed_out.error.contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_exec_concurrency_limit_skips_extra_alerts() {
+        let log = DeliveryLog::default();
+        let mut dispatcher = Dispatcher::new(vec![exec_notifier("sleep 0.3")], &[], log.clone());
+        let mut second = alert("error");
+        second.fingerprint = "other".to_string();
+        dispatcher.dispatch(&alert("error"));
+        dispatcher.dispatch(&second);
+        let skipped = log.recent();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].status, "skipped");
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while log.recent().len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(log.recent()[0].status, "delivered");
+        assert_eq!(log.health()["skipped"], 1);
+    }
+
+    #[test]
+    fn test_validate_exec_notifier_requires_command() {
+        let mut exec = exec_notifier("true");
+        assert!(validate_notifiers(std::slice::from_ref(&exec)).is_ok());
+        exec.command.clear();
+        assert!(validate_notifiers(&[exec]).is_err());
+    }
+}
@@ -0,0 +1,567 @@
+use serde_json::{json, Map, Value};
+use std::sync::atomic::Ordering;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::db::DbWrite;
+use crate::types::{App, Event, OtelSeries};
+use crate::utils::now_iso;
+
+const CLAUDE_CODE_PREFIX: &str = "claude_code.";
+const AGGREGATION_TEMPORALITY_CUMULATIVE: i64 = 2;
+
+/// Converts an OTLP/HTTP JSON `ExportLogsServiceRequest` into events.
+///
+/// Log records are per request/tool call and go to the timeline only; spend is
+/// counted from the `claude_code.cost.usage` / `claude_code.token.usage`
+/// metrics so that exporting both signals does not count it twice. A session
+/// whose usage already came from the JSONL collector keeps those metrics on
+/// the timeline only (see `append_event`).
+pub fn parse_otlp_logs(body: &[u8], app: &App) -> Result<Vec<Event>, String> {
+    let request: Value =
+        serde_json::from_slice(body).map_err(|e| format!("invalid OTLP JSON: {}", e))?;
+    let mut events = Vec::new();
+    for resource_logs in array(&request, "resourceLogs") {
+        let resource = attributes(resource_logs.get("resource"));
+        for scope_logs in array(resource_logs, "scopeLogs") {
+            for record in array(scope_logs, "logRecords") {
+                let mut attrs = resource.clone();
+                attrs.extend(attributes(Some(record)));
+                let name = attrs
+                    .get("event.name")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .or_else(|| {
+                        record
+                            .get("body")
+                            .and_then(|b| b.get("stringValue"))
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string)
+                    })
+                    .unwrap_or_default();
+                let name = name
+                    .strip_prefix(CLAUDE_CODE_PREFIX)
+                    .unwrap_or(&name)
+                    .to_string();
+                if name.is_empty() {
+                    continue;
+                }
+                let timestamp = attrs
+                    .get("event.timestamp")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .or_else(|| unix_nanos_to_iso(record.get("timeUnixNano")))
+                    .or_else(|| unix_nanos_to_iso(record.get("observedTimeUnixNano")))
+                    .unwrap_or_else(now_iso);
+                events.push(log_event(app, &name, attrs, timestamp));
+            }
+        }
+    }
+    Ok(events)
+}
+
+fn log_event(app: &App, name: &str, attrs: Map<String, Value>, timestamp: String) -> Event {
+    let text = |key: &str| attrs.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let number = |key: &str| attrs.get(key).and_then(as_f64);
+    let latency_ms = number("duration_ms").map(|ms| ms.round() as i64);
+    let model = text("model").to_string();
+    let (status, message) = match name {
+        "api_request" => ("ok", model.clone()),
+        "api_error" => ("error", text("error").to_string()),
+        "tool_result" => {
+            let failed = matches!(attrs.get("success"), Some(Value::Bool(false)))
+                || text("success") == "false";
+            (
+                if failed { "error" } else { "ok" },
+                text("tool_name").to_string(),
+            )
+        }
+        "tool_decision" => {
+            let rejected = text("decision") == "reject";
+            (
+                if rejected { "warning" } else { "ok" },
+                format!("{} {}", text("tool_name"), text("decision")),
+            )
+        }
+        "user_prompt" => ("ok", text("prompt").to_string()),
+        _ => ("ok", name.to_string()),
+    };
+    session_event(
+        app,
+        &attrs,
+        name,
+        status,
+        latency_ms,
+        &message,
+        json!({ "otelAttributes": Value::Object(attrs.clone()) }),
+        timestamp,
+        model,
+    )
+}
+
+/// Converts an OTLP/HTTP JSON `ExportMetricsServiceRequest` into events.
+/// `claude_code.cost.usage` becomes a `cost_update` and `claude_code.token.usage`
+/// a `token_usage`, so both feed the session, agent and hourly totals; other
+/// metrics are kept as `otel_metric` events. Cumulative sums are turned into
+/// deltas against the last value seen for the same series.
+pub fn parse_otlp_metrics(body: &[u8], app: &App) -> Result<Vec<Event>, String> {
+    let request: Value =
+        serde_json::from_slice(body).map_err(|e| format!("invalid OTLP JSON: {}", e))?;
+    let mut events = Vec::new();
+    for resource_metrics in array(&request, "resourceMetrics") {
+        let resource = attributes(resource_metrics.get("resource"));
+        for scope_metrics in array(resource_metrics, "scopeMetrics") {
+            for metric in array(scope_metrics, "metrics") {
+                let name = metric.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                let unit = metric.get("unit").and_then(|v| v.as_str()).unwrap_or("");
+                let (data, cumulative) = if let Some(sum) = metric.get("sum") {
+                    (sum, is_cumulative(sum.get("aggregationTemporality")))
+                } else if let Some(gauge) = metric.get("gauge") {
+                    (gauge, false)
+                } else {
+                    continue;
+                };
+                for point in array(data, "dataPoints") {
+                    let mut attrs = resource.clone();
+                    attrs.extend(attributes(Some(point)));
+                    let Some(mut value) = point
+                        .get("asDouble")
+                        .or_else(|| point.get("asInt"))
+                        .and_then(as_f64)
+                    else {
+                        continue;
+                    };
+                    if cumulative {
+                        value = cumulative_delta(app, name, &attrs, value);
+                    }
+                    if value <= 0.0 {
+                        continue;
+                    }
+                    let timestamp =
+                        unix_nanos_to_iso(point.get("timeUnixNano")).unwrap_or_else(now_iso);
+                    events.push(metric_event(app, name, unit, value, attrs, timestamp));
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+fn metric_event(
+    app: &App,
+    name: &str,
+    unit: &str,
+    value: f64,
+    attrs: Map<String, Value>,
+    timestamp: String,
+) -> Event {
+    let model = attrs
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let otel_attributes = Value::Object(attrs.clone());
+    let (event, message, metadata) = match name {
+        "claude_code.cost.usage" => (
+            "cost_update",
+            format!("${:.4}", value),
+            json!({ "costDelta": value, "otelAttributes": otel_attributes }),
+        ),
+        "claude_code.token.usage" => {
+            let tokens = value.round() as u64;
+            let kind = attrs.get("type").and_then(|v| v.as_str()).unwrap_or("");
+            let key = match kind {
+                "input" => "inputTokens",
+                "output" => "outputTokens",
+                "cacheRead" => "cacheReadInputTokens",
+                "cacheCreation" => "cacheCreationInputTokens",
+                _ => "otherTokens",
+            };
+            let mut token_usage = json!({ key: tokens });
+            // `totalTokens` is input + output everywhere else; cache reads and
+            // writes only show up in the breakdown.
+            if matches!(kind, "input" | "output") {
+                token_usage["totalTokens"] = json!(tokens);
+            }
+            (
+                "token_usage",
+                format!("{} {} tokens", tokens, kind),
+                // The cost arrives separately as `claude_code.cost.usage`; a zero
+                // `costUsd` keeps the pricing table from adding an estimate on top.
+                json!({
+                    "tokenUsage": token_usage,
+                    "costUsd": 0.0,
+                    "otelAttributes": otel_attributes,
+                }),
+            )
+        }
+        _ => (
+            "otel_metric",
+            format!("{} {}{}", name, value, unit),
+            json!({
+                "metric": name,
+                "value": value,
+                "unit": unit,
+                "otelAttributes": otel_attributes,
+            }),
+        ),
+    };
+    session_event(
+        app, &attrs, event, "ok", None, &message, metadata, timestamp, model,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn session_event(
+    app: &App,
+    attrs: &Map<String, Value>,
+    event: &str,
+    status: &str,
+    latency_ms: Option<i64>,
+    message: &str,
+    mut metadata: Value,
+    timestamp: String,
+    model: String,
+) -> Event {
+    let session_id = attrs
+        .get("session.id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    // Telemetry has no agent identity; attribute it to the session's lead agent
+    // like the collector does, or to a shared row when the session is unknown.
+    let agent_id = if session_id.is_empty() {
+        "otel".to_string()
+    } else {
+        format!("lead-{}", session_id.chars().take(8).collect::<String>())
+    };
+    if let Some(map) = metadata.as_object_mut() {
+        map.insert("source".to_string(), json!("otel"));
+        map.insert("sessionId".to_string(), json!(session_id));
+        map.insert("model".to_string(), json!(model));
+    }
+    Event {
+        id: format!("e{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
+        agent_id,
+        event: event.to_string(),
+        status: status.to_string(),
+        latency_ms,
+        message: message.chars().take(120).collect(),
+        metadata,
+        timestamp,
+        received_at: now_iso(),
+        model,
+        is_sidechain: false,
+        session_id,
+        cwd: String::new(),
+        dedup_key: None,
+    }
+}
+
+/// The growth of a cumulative series since its last point. Last values are
+/// persisted, so a series seen before a restart (or before its session left
+/// memory) continues from where it was instead of counting its whole sum again.
+fn cumulative_delta(app: &App, name: &str, attrs: &Map<String, Value>, value: f64) -> f64 {
+    let mut keys: Vec<(&String, &Value)> = attrs.iter().collect();
+    keys.sort_by(|a, b| a.0.cmp(b.0));
+    let series = format!("{}{}", name, json!(keys));
+    let last = OtelSeries {
+        session_id: attrs
+            .get("session.id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        value,
+    };
+    let known = {
+        let state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.otel_cumulative.get(&series).map(|s| s.value)
+    };
+    // Never holds the state lock while waiting for the database.
+    let previous = known.or_else(|| {
+        let db = app.db_reader.as_ref()?;
+        let db = db.lock().unwrap_or_else(|e| e.into_inner());
+        db.load_otel_series(&series)
+            .unwrap_or_else(|e| {
+                eprintln!("[otel] cannot load series {series}: {e}");
+                None
+            })
+            .map(|s| s.value)
+    });
+    if let Some(writer) = &app.db_writer {
+        writer.send(DbWrite::OtelSeries {
+            series: series.clone(),
+            last: last.clone(),
+            updated_at: now_iso(),
+        });
+    }
+    app.state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .otel_cumulative
+        .insert(series, last);
+    match previous {
+        // A smaller value means the exporter restarted and the sum began again.
+        Some(prev) if value >= prev => value - prev,
+        _ => value,
+    }
+}
+
+fn is_cumulative(temporality: Option<&Value>) -> bool {
+    match temporality {
+        Some(Value::Number(n)) => n.as_i64() == Some(AGGREGATION_TEMPORALITY_CUMULATIVE),
+        Some(Value::String(s)) => s == "AGGREGATION_TEMPORALITY_CUMULATIVE",
+        _ => false,
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+}
+
+/// Flattens OTLP `KeyValue` lists into a plain JSON object.
+fn attributes(owner: Option<&Value>) -> Map<String, Value> {
+    let mut out = Map::new();
+    let Some(list) = owner
+        .and_then(|o| o.get("attributes"))
+        .and_then(|a| a.as_array())
+    else {
+        return out;
+    };
+    for kv in list {
+        let Some(key) = kv.get("key").and_then(|k| k.as_str()) else {
+            continue;
+        };
+        if let Some(value) = kv.get("value").and_then(any_value) {
+            out.insert(key.to_string(), value);
+        }
+    }
+    out
+}
+
+fn any_value(value: &Value) -> Option<Value> {
+    let map = value.as_object()?;
+    let (kind, inner) = map.iter().next()?;
+    Some(match kind.as_str() {
+        // int64 values are encoded as strings in OTLP JSON
+        "intValue" => inner
+            .as_str()
+            .and_then(|s| s.parse::<i64>().ok())
+            .map(Value::from)
+            .unwrap_or_else(|| inner.clone()),
+        "arrayValue" => Value::Array(
+            inner
+                .get("values")
+                .and_then(|v| v.as_array())
+                .map(|values| values.iter().filter_map(any_value).collect())
+                .unwrap_or_default(),
+        ),
+        _ => inner.clone(),
+    })
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn unix_nanos_to_iso(value: Option<&Value>) -> Option<String> {
+    let nanos = match value? {
+        Value::String(s) => s.parse::<i128>().ok()?,
+        Value::Number(n) => n.as_u64()? as i128,
+        _ => return None,
+    };
+    if nanos == 0 {
+        return None;
+    }
+    OffsetDateTime::from_unix_timestamp_nanos(nanos)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorConfig;
+    use crate::db::Db;
+    use crate::db_writer::DbWriter;
+    use crate::state::append_event;
+    use crate::types::State;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+
+    const LOGS_FIXTURE: &str = include_str!("../tests/fixtures/otlp_logs.json");
+    const METRICS_FIXTURE: &str = include_str!("../tests/fixtures/otlp_metrics.json");
+
+    fn make_test_app() -> App {
+        App {
+            state: Arc::new(Mutex::new(State::default())),
+            sse_clients: Arc::new(Mutex::new(Vec::new())),
+            event_seq: Arc::new(AtomicU64::new(1)),
+            public_dir: Arc::new(PathBuf::from("public")),
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
+        }
+    }
+
+    #[test]
+    fn test_parse_otlp_logs_fixture() {
+        let app = make_test_app();
+        let events = parse_otlp_logs(LOGS_FIXTURE.as_bytes(), &app).unwrap();
+        let names: Vec<&str> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "user_prompt",
+                "api_request",
+                "tool_decision",
+                "tool_result",
+                "api_error"
+            ]
+        );
+        let request = &events[1];
+        assert_eq!(request.session_id, "9f1c2d3e-aaaa-bbbb-cccc-000000000001");
+        assert_eq!(request.agent_id, "lead-9f1c2d3e");
+        assert_eq!(request.model, "claude-sonnet-4-5");
+        assert_eq!(request.latency_ms, Some(2140));
+        assert_eq!(request.timestamp, "2025-06-01T12:00:03.500Z");
+        assert_eq!(request.metadata["source"], "otel");
+        assert_eq!(request.metadata["otelAttributes"]["input_tokens"], 1200);
+        assert!(request.metadata.get("tokenUsage").is_none());
+        assert_eq!(events[3].status, "error");
+        assert_eq!(events[3].message, "Bash");
+        assert_eq!(events[4].status, "error");
+    }
+
+    #[test]
+    fn test_parse_otlp_metrics_fixture_feeds_totals() {
+        let app = make_test_app();
+        let events = parse_otlp_metrics(METRICS_FIXTURE.as_bytes(), &app).unwrap();
+        for evt in events {
+            append_event(&app, evt);
+        }
+        let state = app.state.lock().unwrap();
+        assert!((state.cost_total_usd - 0.0421).abs() < 1e-9);
+        assert_eq!(state.usage_total.input_tokens, 1200);
+        assert_eq!(state.usage_total.output_tokens, 350);
+        assert_eq!(state.usage_total.cache_read_input_tokens, 8000);
+        assert_eq!(state.token_total, 1550);
+        let session = &state.by_session["9f1c2d3e-aaaa-bbbb-cccc-000000000001"];
+        assert!((session.cost_usd - 0.0421).abs() < 1e-9);
+        assert_eq!(state.by_source["otel"].total, 5);
+        assert!(state.recent.iter().any(|e| e.event == "otel_metric"
+            && e.message.starts_with("claude_code.lines_of_code.count")));
+    }
+
+    #[test]
+    fn test_collector_and_otel_usage_for_one_session_count_once() {
+        let app = make_test_app();
+        let line = r#"{"type":"assistant","message":{"model":"claude-sonnet-4-5","content":[],"usage":{"input_tokens":1200,"output_tokens":350,"cache_read_input_tokens":8000}},"sessionId":"9f1c2d3e-aaaa-bbbb-cccc-000000000001","timestamp":"2025-06-01T12:00:00Z"}"#;
+        for evt in crate::collector::parse_session_line(line, &app) {
+            append_event(&app, evt);
+        }
+        let (tokens, cost) = {
+            let state = app.state.lock().unwrap();
+            (state.token_total, state.cost_total_usd)
+        };
+        assert_eq!(tokens, 1550);
+        assert!(cost > 0.0);
+
+        for evt in parse_otlp_metrics(METRICS_FIXTURE.as_bytes(), &app).unwrap() {
+            append_event(&app, evt);
+        }
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.token_total, tokens);
+        assert_eq!(state.cost_total_usd, cost);
+        assert_eq!(state.usage_total.cache_read_input_tokens, 8000);
+        let session = &state.by_session["9f1c2d3e-aaaa-bbbb-cccc-000000000001"];
+        assert_eq!(session.token_total, tokens);
+        assert_eq!(session.cost_usd, cost);
+        assert_eq!(session.usage_source.as_deref(), Some("claude_session"));
+        let agent = &state.by_agent["lead-9f1c2d3e"];
+        assert_eq!(agent.token_total, tokens);
+        assert_eq!(state.by_source["otel"].total, 5);
+    }
+
+    fn cumulative_cost_body(value: f64) -> String {
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": [{ "key": "session.id", "value": { "stringValue": "s1" } }] },
+                "scopeMetrics": [{ "metrics": [{
+                    "name": "claude_code.cost.usage",
+                    "unit": "USD",
+                    "sum": {
+                        "aggregationTemporality": 2,
+                        "isMonotonic": true,
+                        "dataPoints": [{ "asDouble": value, "timeUnixNano": "1748779200000000000" }]
+                    }
+                }] }]
+            }]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_cumulative_sums_are_converted_to_deltas() {
+        let app = make_test_app();
+        let body = cumulative_cost_body;
+        let first = parse_otlp_metrics(body(0.5).as_bytes(), &app).unwrap();
+        assert_eq!(first[0].metadata["costDelta"], 0.5);
+        let second = parse_otlp_metrics(body(0.75).as_bytes(), &app).unwrap();
+        assert_eq!(second[0].metadata["costDelta"], 0.25);
+        // unchanged sum: nothing new to report
+        assert!(parse_otlp_metrics(body(0.75).as_bytes(), &app)
+            .unwrap()
+            .is_empty());
+        // reset after an exporter restart
+        let reset = parse_otlp_metrics(body(0.1).as_bytes(), &app).unwrap();
+        assert_eq!(reset[0].metadata["costDelta"], 0.1);
+    }
+
+    #[test]
+    fn test_cumulative_series_resume_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor.db");
+        let mut before = make_test_app();
+        let (writer, handle) = DbWriter::spawn(Db::open(&path).unwrap(), 10, 10);
+        before.db_writer = Some(writer.clone());
+        parse_otlp_metrics(cumulative_cost_body(0.5).as_bytes(), &before).unwrap();
+        assert!(writer.flush());
+        drop((before, writer));
+        handle.join().unwrap();
+
+        let mut after = make_test_app();
+        after.db_reader = Some(Arc::new(Mutex::new(Db::open(&path).unwrap())));
+        let events = parse_otlp_metrics(cumulative_cost_body(0.75).as_bytes(), &after).unwrap();
+        assert_eq!(events[0].metadata["costDelta"], 0.25);
+    }
+
+    #[test]
+    fn test_parse_otlp_rejects_invalid_json() {
+        let app = make_test_app();
+        assert!(parse_otlp_logs(b"{", &app).is_err());
+        assert!(parse_otlp_metrics(b"not json", &app).is_err());
+        assert!(parse_otlp_logs(b"{}", &app).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unix_nanos_to_iso() {
+        assert_eq!(
+            unix_nanos_to_iso(Some(&json!("1748779200000000000"))).as_deref(),
+            Some("2025-06-01T12:00:00Z")
+        );
+        assert_eq!(unix_nanos_to_iso(Some(&json!("0"))), None);
+        assert_eq!(unix_nanos_to_iso(None), None);
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// USD per million tokens for one model family.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_read: f64,
+    pub cache_write: f64,
+}
+
+/// Override entry from the config file. Cache prices default to the usual
+/// 0.1x (read) and 1.25x (write) multipliers of the input price.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceOverride {
+    pub input: f64,
+    pub output: f64,
+    pub cache_read: Option<f64>,
+    pub cache_write: Option<f64>,
+}
+
+impl From<PriceOverride> for ModelPrice {
+    fn from(raw: PriceOverride) -> Self {
+        Self {
+            input: raw.input,
+            output: raw.output,
+            cache_read: raw.cache_read.unwrap_or(raw.input * 0.1),
+            cache_write: raw.cache_write.unwrap_or(raw.input * 1.25),
+        }
+    }
+}
+
+const BUILTIN_PRICES: &[(&str, f64, f64, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0, 0.5, 6.25),
+    ("claude-opus-4-1", 15.0, 75.0, 1.5, 18.75),
+    ("claude-opus-4", 15.0, 75.0, 1.5, 18.75),
+    ("claude-3-opus", 15.0, 75.0, 1.5, 18.75),
+    ("claude-sonnet-4", 3.0, 15.0, 0.3, 3.75),
+    ("claude-3-7-sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("claude-3-5-sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("claude-haiku-4-5", 1.0, 5.0, 0.1, 1.25),
+    ("claude-3-5-haiku", 0.8, 4.0, 0.08, 1.0),
+    ("claude-3-haiku", 0.25, 1.25, 0.03, 0.3),
+    ("opus", 15.0, 75.0, 1.5, 18.75),
+    ("sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("haiku", 1.0, 5.0, 0.1, 1.25),
+];
+
+/// Model name → price lookup. Keys are matched as substrings of the
+/// lowercased model name and the longest matching key wins, so
+/// `claude-opus-4-1-20250805` resolves to `claude-opus-4-1` rather than `opus`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "HashMap<String, PriceOverride>")]
+pub struct PricingTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        let prices = BUILTIN_PRICES
+            .iter()
+            .map(|&(key, input, output, cache_read, cache_write)| {
+                (
+                    key.to_string(),
+                    ModelPrice {
+                        input,
+                        output,
+                        cache_read,
+                        cache_write,
+                    },
+                )
+            })
+            .collect();
+        Self { prices }
+    }
+}
+
+impl From<HashMap<String, PriceOverride>> for PricingTable {
+    fn from(overrides: HashMap<String, PriceOverride>) -> Self {
+        Self::default().with_overrides(overrides)
+    }
+}
+
+/// Reads price overrides from a TOML file (`.toml`) or a JSON file (any other
+/// extension), shaped like the `pricing` section of `monitor.json`.
+pub fn load_price_overrides(path: &Path) -> Result<HashMap<String, PriceOverride>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("pricingFile {}: {}", path.display(), e))?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    let parsed = if is_toml {
+        basic_toml::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| format!("pricingFile {}: {}", path.display(), e))
+}
+
+impl PricingTable {
+    pub fn with_overrides(mut self, overrides: HashMap<String, PriceOverride>) -> Self {
+        for (key, raw) in overrides {
+            self.prices.insert(key.to_ascii_lowercase(), raw.into());
+        }
+        self
+    }
+
+    pub fn price_for(&self, model: &str) -> Option<&ModelPrice> {
+        if model.is_empty() {
+            return None;
+        }
+        let model = model.to_ascii_lowercase();
+        self.prices
+            .iter()
+            .filter(|(key, _)| model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    }
+
+    /// Cost of a `tokenUsage` metadata object, or `None` when the model has no price.
+    pub fn cost_for(&self, model: &str, token_usage: &Value) -> Option<f64> {
+        let price = self.price_for(model)?;
+        let count = |key: &str| token_usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as f64;
+        let cost = count("inputTokens") * price.input
+            + count("outputTokens") * price.output
+            + count("cacheReadInputTokens") * price.cache_read
+            + count("cacheCreationInputTokens") * price.cache_write;
+        Some(cost / 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_price_for_prefers_longest_match() {
+        let table = PricingTable::default();
+        let price = table.price_for("claude-opus-4-1-20250805").unwrap();
+        assert_eq!(price.input, 15.0);
+        let price = table.price_for("claude-opus-4-5-20251101").unwrap();
+        assert_eq!(price.input, 5.0);
+    }
+
+    #[test]
+    fn test_price_for_family_fallback() {
+        let table = PricingTable::default();
+        assert_eq!(
+            table.price_for("some-future-sonnet-9").unwrap().output,
+            15.0
+        );
+    }
+
+    #[test]
+    fn test_price_for_unknown_or_empty_model() {
+        let table = PricingTable::default();
+        assert!(table.price_for("<synthetic>").is_none());
+        assert!(table.price_for("").is_none());
+    }
+
+    #[test]
+    fn test_cost_for_includes_cache_tokens() {
+        let table = PricingTable::default();
+        let usage = json!({
+            "inputTokens": 1_000_000,
+            "outputTokens": 100_000,
+            "cacheReadInputTokens": 1_000_000,
+            "cacheCreationInputTokens": 100_000,
+        });
+        let cost = table.cost_for("claude-sonnet-4-5", &usage).unwrap();
+        // 3.00 + 1.50 + 0.30 + 0.375
+        assert!((cost - 5.175).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_overrides_merge_with_builtin_defaults() {
+        let table: PricingTable = serde_json::from_value(json!({
+            "claude-sonnet-4": { "input": 2.0, "output": 10.0 },
+            "My-Local-Model": { "input": 1.0, "output": 1.0, "cacheRead": 0.0, "cacheWrite": 0.0 }
+        }))
+        .unwrap();
+        let sonnet = table.price_for("claude-sonnet-4-5").unwrap();
+        assert_eq!(sonnet.input, 2.0);
+        assert!((sonnet.cache_read - 0.2).abs() < 1e-9);
+        assert!((sonnet.cache_write - 2.5).abs() < 1e-9);
+        assert_eq!(table.price_for("my-local-model").unwrap().cache_read, 0.0);
+        assert_eq!(table.price_for("claude-haiku-4-5").unwrap().input, 1.0);
+    }
+
+    #[test]
+    fn test_load_price_overrides_from_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pricing.toml");
+        std::fs::write(
+            &path,
+            "[claude-sonnet-4]\ninput = 2\noutput = 10.0\n\n[\"my.local-model\"]\ninput = 1.0\noutput = 1.0\ncacheRead = 0.0\n",
+        )
+        .unwrap();
+        let table = PricingTable::default().with_overrides(load_price_overrides(&path).unwrap());
+        assert_eq!(table.price_for("claude-sonnet-4-5").unwrap().input, 2.0);
+        assert_eq!(table.price_for("my.local-model").unwrap().cache_read, 0.0);
+
+        std::fs::write(&path, "[claude-sonnet-4]\ninput = \"cheap\"\n").unwrap();
+        assert!(load_price_overrides(&path).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+use crate::state::session_state_at;
+use crate::types::{HourBucket, ModelUsage, ProjectRow, State, TokenBreakdown, ToolCallStat};
+
+const TOP_TOOLS: usize = 5;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSummary {
+    pub project_name: String,
+    pub last_seen: String,
+    pub sessions: u64,
+    pub agents: u64,
+    /// Sessions of this project still held in memory that are `active`.
+    pub active_sessions: usize,
+    pub total: u64,
+    pub ok: u64,
+    pub warning: u64,
+    pub error: u64,
+    pub token_total: u64,
+    pub cost_usd: f64,
+    pub usage: TokenBreakdown,
+    pub top_tools: Vec<ToolCallStat>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSession {
+    pub session_id: String,
+    pub display_name: String,
+    pub last_seen: String,
+    pub token_total: u64,
+    pub cost_usd: f64,
+    pub state: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDetail {
+    #[serde(flatten)]
+    pub summary: ProjectSummary,
+    /// Every tool used in the project, most used first.
+    pub tools: Vec<ToolCallStat>,
+    /// Sessions still held in memory, most recently seen first.
+    pub session_rows: Vec<ProjectSession>,
+    pub hourly_buckets: Vec<HourBucket>,
+    pub by_model: HashMap<String, ModelUsage>,
+}
+
+fn tool_stats(row: &ProjectRow) -> Vec<ToolCallStat> {
+    let mut tools: Vec<ToolCallStat> = row
+        .tool_use_counts
+        .iter()
+        .map(|(name, count)| ToolCallStat {
+            name: name.clone(),
+            count: *count,
+        })
+        .collect();
+    tools.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    tools
+}
+
+fn project_sessions(state: &State, name: &str, now: OffsetDateTime) -> Vec<ProjectSession> {
+    let mut sessions: Vec<ProjectSession> = state
+        .by_session
+        .values()
+        .filter(|session| session.project_name == name)
+        .map(|session| ProjectSession {
+            session_id: session.session_id.clone(),
+            display_name: session.display_name.clone(),
+            last_seen: session.last_seen.clone(),
+            token_total: session.token_total,
+            cost_usd: session.cost_usd,
+            state: session_state_at(state, session, now),
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    sessions
+}
+
+fn summarize(row: &ProjectRow, sessions: &[ProjectSession]) -> ProjectSummary {
+    let mut top_tools = tool_stats(row);
+    top_tools.truncate(TOP_TOOLS);
+    ProjectSummary {
+        project_name: row.project_name.clone(),
+        last_seen: row.last_seen.clone(),
+        sessions: row.session_count,
+        agents: row.agent_count,
+        active_sessions: sessions.iter().filter(|s| s.state == "active").count(),
+        total: row.total,
+        ok: row.ok,
+        warning: row.warning,
+        error: row.error,
+        token_total: row.token_total,
+        cost_usd: row.cost_usd,
+        usage: row.usage,
+        top_tools,
+    }
+}
+
+/// Every project, highest cost first.
+pub fn project_summaries(state: &State, now: OffsetDateTime) -> Vec<ProjectSummary> {
+    let mut projects: Vec<ProjectSummary> = state
+        .by_project
+        .values()
+        .map(|row| summarize(row, &project_sessions(state, &row.project_name, now)))
+        .collect();
+    projects.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then_with(|| b.token_total.cmp(&a.token_total))
+            .then_with(|| a.project_name.cmp(&b.project_name))
+    });
+    projects
+}
+
+pub fn project_detail(state: &State, name: &str, now: OffsetDateTime) -> Option<ProjectDetail> {
+    let row = state.by_project.get(name)?;
+    let session_rows = project_sessions(state, name, now);
+    Some(ProjectDetail {
+        summary: summarize(row, &session_rows),
+        tools: tool_stats(row),
+        session_rows,
+        hourly_buckets: state.project_hourly.get(name).cloned().unwrap_or_default(),
+        by_model: row.by_model.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SessionRow;
+    use time::format_description::well_known::Rfc3339;
+
+    fn project(name: &str, cost_usd: f64, tools: &[(&str, u64)]) -> ProjectRow {
+        ProjectRow {
+            project_name: name.to_string(),
+            last_seen: "2025-01-15T10:00:00Z".to_string(),
+            total: 3,
+            ok: 2,
+            error: 1,
+            cost_usd,
+            session_count: 1,
+            agent_count: 2,
+            tool_use_counts: tools
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect::<HashMap<_, _>>(),
+            ..ProjectRow::default()
+        }
+    }
+
+    #[test]
+    fn test_project_summaries_sorted_by_cost() {
+        let mut state = State::default();
+        state
+            .by_project
+            .insert("api".to_string(), project("api", 1.0, &[]));
+        state
+            .by_project
+            .insert("web".to_string(), project("web", 4.0, &[]));
+        let now = OffsetDateTime::parse("2025-01-15T10:00:10Z", &Rfc3339).unwrap();
+        let projects = project_summaries(&state, now);
+        assert_eq!(projects[0].project_name, "web");
+        assert_eq!(projects[1].project_name, "api");
+        assert_eq!(projects[0].agents, 2);
+        assert_eq!(projects[0].error, 1);
+    }
+
+    #[test]
+    fn test_project_detail_lists_sessions_and_tools() {
+        let mut state = State::default();
+        state.by_project.insert(
+            "web".to_string(),
+            project("web", 4.0, &[("Bash", 3), ("Read", 7), ("Edit", 1)]),
+        );
+        state.by_session.insert(
+            "s1".to_string(),
+            SessionRow {
+                session_id: "s1".to_string(),
+                last_seen: "2025-01-15T10:00:00Z".to_string(),
+                token_total: 10,
+                cost_usd: 4.0,
+                display_name: "web #s1".to_string(),
+                project_name: "web".to_string(),
+                short_session_id: "s1".to_string(),
+                ..SessionRow::default()
+            },
+        );
+        let now = OffsetDateTime::parse("2025-01-15T10:00:10Z", &Rfc3339).unwrap();
+        let detail = project_detail(&state, "web", now).unwrap();
+        assert_eq!(detail.session_rows.len(), 1);
+        assert_eq!(detail.session_rows[0].session_id, "s1");
+        assert_eq!(detail.tools[0].name, "Read");
+        assert_eq!(detail.summary.top_tools.len(), 3);
+        assert!(project_detail(&state, "missing", now).is_none());
+    }
+}
@@ -0,0 +1,685 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration as StdDuration;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::alerts::{broadcast_alert, raise_alert};
+use crate::budgets::evaluate_budgets;
+use crate::db::DbWrite;
+use crate::state::{agent_state_windows, session_agent_rows, session_state_at, MAX_HOUR_BUCKETS};
+use crate::types::{AlertRow, App, Event, State};
+
+const RULE_EVAL_INTERVAL_SECS: u64 = 10;
+/// Window used by `tokensPerMinute` rules that do not set one.
+const DEFAULT_RATE_WINDOW_SECS: u64 = 300;
+const MAX_RULE_SAMPLES: usize = 100_000;
+/// Longest `windowSecs`: the hours of history held in memory.
+const MAX_WINDOW_SECS: u64 = MAX_HOUR_BUCKETS as u64 * 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleScope {
+    Session,
+    Agent,
+    Project,
+    Global,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleMetric {
+    CostUsd,
+    Tokens,
+    Warnings,
+    Errors,
+    IdleSeconds,
+    ToolCalls,
+    TokensPerMinute,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Comparator {
+    #[serde(rename = ">")]
+    Gt,
+    #[default]
+    #[serde(rename = ">=")]
+    Gte,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Lte,
+}
+
+impl Comparator {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Gt => value > threshold,
+            Self::Gte => value >= threshold,
+            Self::Lt => value < threshold,
+            Self::Lte => value <= threshold,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Gt => ">",
+            Self::Gte => ">=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+        }
+    }
+}
+
+/// One entry of `alertRules` in `monitor.json`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    pub id: String,
+    pub scope: RuleScope,
+    pub metric: RuleMetric,
+    #[serde(default)]
+    pub op: Comparator,
+    pub threshold: f64,
+    /// Only count events received in the last `windowSecs`. Without it the
+    /// metric is the running total held in memory.
+    pub window_secs: Option<u64>,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+    pub message: Option<String>,
+    /// Overrides the notifier cooldown for alerts raised by this rule.
+    pub cooldown_secs: Option<u64>,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+impl AlertRule {
+    fn window(&self) -> Option<u64> {
+        match (self.metric, self.window_secs) {
+            (RuleMetric::IdleSeconds, _) => None,
+            (RuleMetric::TokensPerMinute, None) => Some(DEFAULT_RATE_WINDOW_SECS),
+            (_, window) => window,
+        }
+    }
+}
+
+/// Rejects rules that could never be evaluated sensibly.
+pub fn validate_rules(rules: &[AlertRule]) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for rule in rules {
+        if rule.id.trim().is_empty() {
+            return Err("alert rule id must not be empty".to_string());
+        }
+        if !ids.insert(rule.id.as_str()) {
+            return Err(format!("duplicate alert rule id {:?}", rule.id));
+        }
+        if rule.severity != "warning" && rule.severity != "error" {
+            return Err(format!(
+                "alert rule {:?}: severity must be warning or error",
+                rule.id
+            ));
+        }
+        if !rule.threshold.is_finite() {
+            return Err(format!(
+                "alert rule {:?}: threshold must be a number",
+                rule.id
+            ));
+        }
+        if rule
+            .window_secs
+            .is_some_and(|secs| secs == 0 || secs > MAX_WINDOW_SECS)
+        {
+            return Err(format!(
+                "alert rule {:?}: windowSecs must be between 1 and {}",
+                rule.id, MAX_WINDOW_SECS
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Per-event figures kept for windowed rules.
+#[derive(Clone, Debug)]
+pub struct RuleSample {
+    pub at: OffsetDateTime,
+    pub agent_id: String,
+    pub session_id: String,
+    pub project: String,
+    pub tokens: u64,
+    pub cost_usd: f64,
+    pub status: String,
+    pub tool_call: bool,
+}
+
+/// Remembers `evt` for windowed rules; a no-op when no rule has a window.
+pub fn record_rule_sample(
+    state: &mut State,
+    rules: &[AlertRule],
+    evt: &Event,
+    tokens: u64,
+    cost_usd: f64,
+) {
+    let Some(max_window) = rules.iter().filter_map(AlertRule::window).max() else {
+        return;
+    };
+    let Ok(at) = OffsetDateTime::parse(&evt.received_at, &Rfc3339) else {
+        return;
+    };
+    let project = state
+        .by_session
+        .get(&evt.session_id)
+        .map(|s| s.project_name.clone())
+        .unwrap_or_default();
+    state.rule_samples.push_back(RuleSample {
+        at,
+        agent_id: evt.agent_id.clone(),
+        session_id: evt.session_id.clone(),
+        project,
+        tokens,
+        cost_usd,
+        status: evt.status.clone(),
+        tool_call: evt.event == "tool_call",
+    });
+    let cutoff = at - Duration::seconds(max_window as i64);
+    while state
+        .rule_samples
+        .front()
+        .is_some_and(|s| s.at < cutoff || state.rule_samples.len() > MAX_RULE_SAMPLES)
+    {
+        state.rule_samples.pop_front();
+    }
+}
+
+/// The entity a rule value was measured on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Subject {
+    agent_id: String,
+    session_id: String,
+    project: String,
+}
+
+impl Subject {
+    fn key(&self, scope: RuleScope) -> &str {
+        match scope {
+            RuleScope::Session => &self.session_id,
+            RuleScope::Agent => &self.agent_id,
+            RuleScope::Project => &self.project,
+            RuleScope::Global => "",
+        }
+    }
+
+    fn label(&self, scope: RuleScope) -> String {
+        match scope {
+            RuleScope::Session => {
+                format!(
+                    "session {}",
+                    self.session_id.chars().take(8).collect::<String>()
+                )
+            }
+            RuleScope::Agent => format!("agent {}", self.agent_id),
+            RuleScope::Project => format!("project {}", self.project),
+            RuleScope::Global => "all sessions".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    tokens: u64,
+    cost_usd: f64,
+    warnings: u64,
+    errors: u64,
+    tool_calls: u64,
+}
+
+impl Tally {
+    fn value(&self, metric: RuleMetric, window_secs: u64) -> f64 {
+        match metric {
+            RuleMetric::CostUsd => self.cost_usd,
+            RuleMetric::Tokens => self.tokens as f64,
+            RuleMetric::Warnings => self.warnings as f64,
+            RuleMetric::Errors => self.errors as f64,
+            RuleMetric::ToolCalls => self.tool_calls as f64,
+            RuleMetric::TokensPerMinute => self.tokens as f64 * 60.0 / window_secs.max(1) as f64,
+            RuleMetric::IdleSeconds => 0.0,
+        }
+    }
+}
+
+fn subject_for(scope: RuleScope, agent_id: &str, session_id: &str, project: &str) -> Subject {
+    match scope {
+        RuleScope::Session => Subject {
+            session_id: session_id.to_string(),
+            project: project.to_string(),
+            ..Subject::default()
+        },
+        RuleScope::Agent => Subject {
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            project: project.to_string(),
+        },
+        RuleScope::Project => Subject {
+            project: project.to_string(),
+            ..Subject::default()
+        },
+        RuleScope::Global => Subject::default(),
+    }
+}
+
+/// Values of `rule`'s metric for every subject in its scope.
+fn measure(state: &State, rule: &AlertRule, now: OffsetDateTime) -> Vec<(Subject, f64)> {
+    if rule.metric == RuleMetric::IdleSeconds {
+        return measure_idle(state, rule.scope, now);
+    }
+    let mut tallies: HashMap<Subject, Tally> = HashMap::new();
+    match rule.window() {
+        Some(window) => {
+            let cutoff = now - Duration::seconds(window as i64);
+            for sample in state.rule_samples.iter().filter(|s| s.at >= cutoff) {
+                let subject = subject_for(
+                    rule.scope,
+                    &sample.agent_id,
+                    &sample.session_id,
+                    &sample.project,
+                );
+                let tally = tallies.entry(subject).or_default();
+                tally.tokens += sample.tokens;
+                tally.cost_usd += sample.cost_usd;
+                tally.warnings += u64::from(sample.status == "warning");
+                tally.errors += u64::from(sample.status == "error");
+                tally.tool_calls += u64::from(sample.tool_call);
+            }
+        }
+        None => {
+            for agent in state.by_agent.values() {
+                let project = state
+                    .by_session
+                    .get(&agent.session_id)
+                    .map(|s| s.project_name.as_str())
+                    .unwrap_or("");
+                let subject = subject_for(rule.scope, &agent.agent_id, &agent.session_id, project);
+                let tally = tallies.entry(subject).or_default();
+                tally.warnings += agent.warning;
+                tally.errors += agent.error;
+                tally.tool_calls += agent.tool_use_counts.values().sum::<u64>();
+                if rule.scope == RuleScope::Agent {
+                    tally.tokens += agent.token_total;
+                    tally.cost_usd += agent.cost_usd;
+                }
+            }
+            match rule.scope {
+                RuleScope::Agent => {}
+                RuleScope::Global => {
+                    let tally = tallies.entry(Subject::default()).or_default();
+                    tally.tokens = state.token_total;
+                    tally.cost_usd = state.cost_total_usd;
+                }
+                RuleScope::Session | RuleScope::Project => {
+                    for session in state.by_session.values() {
+                        let subject =
+                            subject_for(rule.scope, "", &session.session_id, &session.project_name);
+                        let tally = tallies.entry(subject).or_default();
+                        tally.tokens += session.token_total;
+                        tally.cost_usd += session.cost_usd;
+                    }
+                }
+            }
+        }
+    }
+    let window = rule.window().unwrap_or(0);
+    tallies
+        .into_iter()
+        .filter(|(subject, _)| {
+            rule.scope == RuleScope::Global || !subject.key(rule.scope).is_empty()
+        })
+        .map(|(subject, tally)| {
+            let value = tally.value(rule.metric, window);
+            (subject, value)
+        })
+        .collect()
+}
+
+/// Seconds since the last activity of sessions that have not completed; a
+/// project or the whole monitor is idle for as long as its freshest open session.
+fn measure_idle(state: &State, scope: RuleScope, now: OffsetDateTime) -> Vec<(Subject, f64)> {
+    let elapsed = |last_seen: &str| {
+        OffsetDateTime::parse(last_seen, &Rfc3339)
+            .ok()
+            .map(|at| (now - at).whole_seconds().max(0) as f64)
+    };
+    let mut idle: HashMap<Subject, f64> = HashMap::new();
+    if scope == RuleScope::Agent {
+        for agent in state.by_agent.values() {
+            let open = match state.by_session.get(&agent.session_id) {
+                Some(session) => session_state_at(state, session, now) != "completed",
+                None => !agent_state_windows(state, agent).is_terminal_hint(&agent.last_event),
+            };
+            if let (true, Some(secs)) = (open, elapsed(&agent.last_seen)) {
+                let subject = subject_for(scope, &agent.agent_id, &agent.session_id, "");
+                idle.insert(subject, secs);
+            }
+        }
+    } else {
+        for session in state.by_session.values() {
+            if session_agent_rows(state, session).is_empty()
+                || session_state_at(state, session, now) == "completed"
+            {
+                continue;
+            }
+            let Some(secs) = elapsed(&session.last_seen) else {
+                continue;
+            };
+            let subject = subject_for(scope, "", &session.session_id, &session.project_name);
+            idle.entry(subject)
+                .and_modify(|v| *v = v.min(secs))
+                .or_insert(secs);
+        }
+    }
+    idle.into_iter()
+        .filter(|(subject, _)| scope == RuleScope::Global || !subject.key(scope).is_empty())
+        .collect()
+}
+
+fn rule_fingerprint(rule: &AlertRule, subject: &Subject) -> String {
+    format!("rule:{}|{}", rule.id, subject.key(rule.scope))
+}
+
+fn format_value(metric: RuleMetric, value: f64) -> String {
+    match metric {
+        RuleMetric::CostUsd => format!("${:.4}", value),
+        RuleMetric::TokensPerMinute => format!("{:.0}", value),
+        _ => format!("{}", value),
+    }
+}
+
+fn rule_message(rule: &AlertRule, subject: &Subject, value: f64) -> String {
+    let window = match rule.window() {
+        Some(secs) => format!(", last {}s", secs),
+        None => String::new(),
+    };
+    let detail = format!(
+        "{:?} {} {} {} ({}{})",
+        rule.metric,
+        format_value(rule.metric, value),
+        rule.op.symbol(),
+        rule.threshold,
+        subject.label(rule.scope),
+        window
+    );
+    match &rule.message {
+        Some(message) => format!("{}: {}", message, detail),
+        None => detail,
+    }
+}
+
+/// Evaluates every rule against `state`. A subject that starts breaching a
+/// rule raises (or bumps) its alert; it stays quiet while the breach lasts and
+/// can raise again once it has recovered. Returns the alerts raised.
+pub fn evaluate_rules(
+    state: &mut State,
+    rules: &[AlertRule],
+    now: OffsetDateTime,
+    mut new_id: impl FnMut() -> String,
+) -> Vec<AlertRow> {
+    let now_iso = now.format(&Rfc3339).unwrap_or_default();
+    let mut firing = HashSet::new();
+    let mut raised = Vec::new();
+    for rule in rules {
+        let mut breaches: Vec<(Subject, f64)> = measure(state, rule, now)
+            .into_iter()
+            .filter(|(_, value)| rule.op.holds(*value, rule.threshold))
+            .collect();
+        breaches.sort_by(|a, b| a.0.cmp(&b.0));
+        for (subject, value) in breaches {
+            let fingerprint = rule_fingerprint(rule, &subject);
+            if !state.rules_firing.contains(&fingerprint) {
+                let draft = AlertRow {
+                    severity: rule.severity.clone(),
+                    agent_id: subject.agent_id.clone(),
+                    session_id: subject.session_id.clone(),
+                    event: rule.id.clone(),
+                    message: rule_message(rule, &subject, value)
+                        .chars()
+                        .take(160)
+                        .collect(),
+                    created_at: now_iso.clone(),
+                    fingerprint: fingerprint.clone(),
+                    rule_id: Some(rule.id.clone()),
+                    ..AlertRow::default()
+                };
+                raised.push(raise_alert(state, draft, &mut new_id));
+            }
+            firing.insert(fingerprint);
+        }
+    }
+    state.rules_firing = firing;
+    raised
+}
+
+/// Evaluates the configured rules and budgets every few seconds, independent
+/// of any connected dashboard.
+pub fn spawn_rule_evaluator(app: App) {
+    if app.config.alert_rules.is_empty() && app.config.budgets.is_empty() {
+        return;
+    }
+    thread::spawn(move || loop {
+        thread::sleep(StdDuration::from_secs(RULE_EVAL_INTERVAL_SECS));
+        let raised = {
+            let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+            let now = OffsetDateTime::now_utc();
+            let new_id = || format!("a{}", app.event_seq.fetch_add(1, Ordering::Relaxed));
+            let mut raised = evaluate_rules(&mut state, &app.config.alert_rules, now, new_id);
+            raised.extend(evaluate_budgets(
+                &mut state,
+                &app.config.budgets,
+                now,
+                new_id,
+            ));
+            raised
+        };
+        for alert in raised {
+            if let Some(writer) = &app.db_writer {
+                writer.send(DbWrite::Alert(Box::new(alert.clone())));
+            }
+            broadcast_alert(&app, &alert);
+            if let Some(notifier) = &app.notifier {
+                notifier.notify(&alert);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_rows::{agent, session};
+    use serde_json::json;
+
+    fn at(iso: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(iso, &Rfc3339).unwrap()
+    }
+
+    fn rule(value: serde_json::Value) -> AlertRule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn state_with_sessions(sessions: &[(&str, &str, &str, f64)]) -> State {
+        let mut state = State::default();
+        for (id, project, last_seen, cost) in sessions {
+            state
+                .by_session
+                .insert(id.to_string(), session(id, project, last_seen, *cost));
+            let agent_id = format!("agent-{}", id);
+            state
+                .by_agent
+                .insert(agent_id.clone(), agent(&agent_id, id, last_seen));
+        }
+        state
+    }
+
+    fn sample_event(agent_id: &str, session_id: &str, status: &str, received_at: &str) -> Event {
+        Event {
+            id: "e1".to_string(),
+            agent_id: agent_id.to_string(),
+            event: "assistant_message".to_string(),
+            status: status.to_string(),
+            latency_ms: None,
+            message: String::new(),
+            metadata: json!({}),
+            timestamp: received_at.to_string(),
+            received_at: received_at.to_string(),
+            model: String::new(),
+            is_sidechain: false,
+            session_id: session_id.to_string(),
+            cwd: String::new(),
+            dedup_key: None,
+        }
+    }
+
+    #[test]
+    fn test_rule_config_parses_and_validates() {
+        let rules: Vec<AlertRule> = serde_json::from_value(json!([
+            {"id": "cost", "scope": "session", "metric": "costUsd", "op": ">", "threshold": 5, "severity": "error"},
+            {"id": "burn", "scope": "global", "metric": "tokensPerMinute", "threshold": 50000}
+        ]))
+        .unwrap();
+        assert_eq!(rules[0].op, Comparator::Gt);
+        assert_eq!(rules[1].op, Comparator::Gte);
+        assert_eq!(rules[1].severity, "warning");
+        assert_eq!(rules[1].window(), Some(DEFAULT_RATE_WINDOW_SECS));
+        assert!(validate_rules(&rules).is_ok());
+
+        let dup = vec![rules[0].clone(), rules[0].clone()];
+        assert!(validate_rules(&dup).is_err());
+        let mut bad = rules[0].clone();
+        bad.severity = "info".to_string();
+        assert!(validate_rules(&[bad]).is_err());
+        let mut huge_window = rules[1].clone();
+        huge_window.window_secs = Some(u64::MAX);
+        assert!(validate_rules(&[huge_window.clone()]).is_err());
+        huge_window.window_secs = Some(MAX_WINDOW_SECS);
+        assert!(validate_rules(&[huge_window]).is_ok());
+    }
+
+    #[test]
+    fn test_session_cost_rule_raises_once_per_breach() {
+        let mut state = state_with_sessions(&[
+            ("s1", "web", "2025-01-01T00:00:00Z", 6.0),
+            ("s2", "web", "2025-01-01T00:00:00Z", 1.0),
+        ]);
+        let rules = vec![rule(json!({
+            "id": "session-cost", "scope": "session", "metric": "costUsd",
+            "op": ">", "threshold": 5, "severity": "error"
+        }))];
+        let mut seq = 0;
+        let mut next_id = || {
+            seq += 1;
+            format!("a{}", seq)
+        };
+        let now = at("2025-01-01T00:00:05Z");
+
+        let raised = evaluate_rules(&mut state, &rules, now, &mut next_id);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].session_id, "s1");
+        assert_eq!(raised[0].severity, "error");
+        assert_eq!(raised[0].rule_id.as_deref(), Some("session-cost"));
+        assert_eq!(raised[0].fingerprint, "rule:session-cost|s1");
+
+        assert!(evaluate_rules(&mut state, &rules, now, &mut next_id).is_empty());
+
+        state.by_session.get_mut("s1").unwrap().cost_usd = 1.0;
+        assert!(evaluate_rules(&mut state, &rules, now, &mut next_id).is_empty());
+        state.by_session.get_mut("s1").unwrap().cost_usd = 7.0;
+        let again = evaluate_rules(&mut state, &rules, now, &mut next_id);
+        assert_eq!(again[0].id, "a1");
+        assert_eq!(again[0].count, 2);
+        assert_eq!(state.alerts.len(), 1);
+    }
+
+    #[test]
+    fn test_project_scope_sums_sessions() {
+        let mut state = state_with_sessions(&[
+            ("s1", "web", "2025-01-01T00:00:00Z", 3.0),
+            ("s2", "web", "2025-01-01T00:00:00Z", 3.0),
+            ("s3", "api", "2025-01-01T00:00:00Z", 3.0),
+        ]);
+        let rules = vec![rule(json!({
+            "id": "project-cost", "scope": "project", "metric": "costUsd", "threshold": 5
+        }))];
+        let raised = evaluate_rules(&mut state, &rules, at("2025-01-01T00:00:05Z"), || {
+            "a1".to_string()
+        });
+        assert_eq!(raised.len(), 1);
+        assert!(
+            raised[0].message.contains("project web"),
+            "{}",
+            raised[0].message
+        );
+    }
+
+    #[test]
+    fn test_windowed_error_rule_uses_recent_samples() {
+        let mut state = state_with_sessions(&[("s1", "web", "2025-01-01T00:10:00Z", 0.0)]);
+        let rules = vec![rule(json!({
+            "id": "agent-errors", "scope": "agent", "metric": "errors",
+            "threshold": 2, "windowSecs": 60
+        }))];
+        for received_at in [
+            "2025-01-01T00:00:00Z",
+            "2025-01-01T00:09:30Z",
+            "2025-01-01T00:09:50Z",
+        ] {
+            let evt = sample_event("agent-s1", "s1", "error", received_at);
+            record_rule_sample(&mut state, &rules, &evt, 0, 0.0);
+        }
+        assert_eq!(state.rule_samples.len(), 2);
+
+        let raised = evaluate_rules(&mut state, &rules, at("2025-01-01T00:10:00Z"), || {
+            "a1".to_string()
+        });
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].agent_id, "agent-s1");
+        assert_eq!(raised[0].session_id, "s1");
+
+        let raised = evaluate_rules(
+            &mut state,
+            &rules,
+            at("2025-01-01T00:10:45Z"),
+            || unreachable!(),
+        );
+        assert!(raised.is_empty());
+        assert!(state.rules_firing.is_empty());
+    }
+
+    #[test]
+    fn test_idle_rule_skips_completed_sessions() {
+        let mut state = state_with_sessions(&[
+            ("s1", "web", "2025-01-01T00:00:00Z", 0.0),
+            ("s2", "web", "2025-01-01T00:09:00Z", 0.0),
+        ]);
+        state.by_agent.get_mut("agent-s1").unwrap().last_event = "stop".to_string();
+        let rules = vec![rule(json!({
+            "id": "idle", "scope": "session", "metric": "idleSeconds", "threshold": 45
+        }))];
+        let raised = evaluate_rules(&mut state, &rules, at("2025-01-01T00:10:00Z"), || {
+            "a1".to_string()
+        });
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].session_id, "s2");
+    }
+
+    #[test]
+    fn test_record_rule_sample_noop_without_windowed_rules() {
+        let mut state = State::default();
+        let rules = vec![rule(json!({
+            "id": "cost", "scope": "global", "metric": "costUsd", "threshold": 1
+        }))];
+        let evt = sample_event("a", "s", "ok", "2025-01-01T00:00:00Z");
+        record_rule_sample(&mut state, &rules, &evt, 10, 0.1);
+        assert!(state.rule_samples.is_empty());
+    }
+}
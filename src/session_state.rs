@@ -0,0 +1,359 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::types::{AgentRow, PendingToolCall};
+
+const DEFAULT_TERMINAL_HINTS: [&str; 13] = [
+    "done",
+    "complete",
+    "completed",
+    "finish",
+    "finished",
+    "session_complete",
+    "session_completed",
+    "session_end",
+    "stop",
+    "stopped",
+    "subagent_stop",
+    "exit",
+    "exited",
+];
+
+/// `agentTypes` key of the main conversation.
+pub const MAIN_AGENT: &str = "main";
+/// `agentTypes` key of every sidechain (Task subagent). Any other key is a
+/// Task `subagent_type` and applies on top of this one.
+pub const SUBAGENT: &str = "subagent";
+
+/// The `agentTypes` keys that apply to `agent`, most general first.
+pub fn agent_type_keys(agent: &AgentRow) -> Vec<&str> {
+    if !agent.is_sidechain {
+        return vec![MAIN_AGENT];
+    }
+    let mut keys = vec![SUBAGENT];
+    if !agent.subagent_type.is_empty() {
+        keys.push(agent.subagent_type.as_str());
+    }
+    keys
+}
+
+/// Settings of one project or agent type; unset fields inherit.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StateWindowOverride {
+    pub active_window_secs: Option<u64>,
+    pub stuck_window_secs: Option<u64>,
+    pub completed_window_secs: Option<u64>,
+    /// Replaces the inherited list.
+    pub terminal_hints: Option<Vec<String>>,
+    pub busy_while_tool_running: Option<bool>,
+}
+
+/// The `sessionState` section of `monitor.json`: how long after its last
+/// event a session stops being `active`, becomes `stuck` or is assumed
+/// `completed`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionStateConfig {
+    pub active_window_secs: u64,
+    pub stuck_window_secs: u64,
+    /// Quiet sessions are `completed` after this long even without a
+    /// terminal event.
+    pub completed_window_secs: u64,
+    /// Last events (case-insensitive, spaces and dashes as `_`) that mark a
+    /// session as finished once it has been quiet for the stuck window.
+    pub terminal_hints: Vec<String>,
+    /// Report `busy` instead of `idle` / `stuck` while a tool call has no
+    /// result yet.
+    pub busy_while_tool_running: bool,
+    /// Tool calls still without a result after this long no longer count
+    /// as in flight.
+    pub tool_call_timeout_secs: u64,
+    /// Keyed by project name (the last `cwd` component).
+    pub projects: HashMap<String, StateWindowOverride>,
+    /// Keyed by `main`, `subagent` or a subagent's Task `subagent_type`;
+    /// applied after the project override.
+    pub agent_types: HashMap<String, StateWindowOverride>,
+}
+
+impl Default for SessionStateConfig {
+    fn default() -> Self {
+        Self {
+            active_window_secs: 30,
+            stuck_window_secs: 120,
+            completed_window_secs: 900,
+            terminal_hints: DEFAULT_TERMINAL_HINTS
+                .iter()
+                .map(|hint| hint.to_string())
+                .collect(),
+            busy_while_tool_running: false,
+            tool_call_timeout_secs: 3600,
+            projects: HashMap::new(),
+            agent_types: HashMap::new(),
+        }
+    }
+}
+
+/// Effective settings for one agent (or, merged, for one session).
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateWindows {
+    pub active_secs: i64,
+    pub stuck_secs: i64,
+    pub completed_secs: i64,
+    terminal_hints: Vec<String>,
+    pub busy_while_tool_running: bool,
+}
+
+fn normalize_event_hint(value: &str) -> String {
+    value.trim().to_ascii_lowercase().replace([' ', '-'], "_")
+}
+
+fn secs(value: u64) -> i64 {
+    value.min(i64::MAX as u64) as i64
+}
+
+impl StateWindows {
+    fn apply(&mut self, patch: &StateWindowOverride) {
+        if let Some(value) = patch.active_window_secs {
+            self.active_secs = secs(value);
+        }
+        if let Some(value) = patch.stuck_window_secs {
+            self.stuck_secs = secs(value);
+        }
+        if let Some(value) = patch.completed_window_secs {
+            self.completed_secs = secs(value);
+        }
+        if let Some(hints) = &patch.terminal_hints {
+            self.terminal_hints = hints.iter().map(|h| normalize_event_hint(h)).collect();
+        }
+        if let Some(busy) = patch.busy_while_tool_running {
+            self.busy_while_tool_running = busy;
+        }
+    }
+
+    /// Settings for a session with several agents: the most patient agent
+    /// wins, so a session is only stuck once every agent would be.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.active_secs = self.active_secs.max(other.active_secs);
+        self.stuck_secs = self.stuck_secs.max(other.stuck_secs);
+        self.completed_secs = self.completed_secs.max(other.completed_secs);
+        for hint in other.terminal_hints {
+            if !self.terminal_hints.contains(&hint) {
+                self.terminal_hints.push(hint);
+            }
+        }
+        self.busy_while_tool_running |= other.busy_while_tool_running;
+        self
+    }
+
+    pub fn is_terminal_hint(&self, last_event: &str) -> bool {
+        self.terminal_hints
+            .contains(&normalize_event_hint(last_event))
+    }
+
+    fn validate(&self, context: &str) -> Result<(), String> {
+        if self.active_secs == 0 {
+            return Err(format!("{}: activeWindowSecs must be positive", context));
+        }
+        if self.active_secs > self.stuck_secs || self.stuck_secs > self.completed_secs {
+            return Err(format!(
+                "{}: windows must satisfy activeWindowSecs <= stuckWindowSecs <= completedWindowSecs",
+                context
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SessionStateConfig {
+    fn global_windows(&self) -> StateWindows {
+        StateWindows {
+            active_secs: secs(self.active_window_secs),
+            stuck_secs: secs(self.stuck_window_secs),
+            completed_secs: secs(self.completed_window_secs),
+            terminal_hints: self
+                .terminal_hints
+                .iter()
+                .map(|h| normalize_event_hint(h))
+                .collect(),
+            busy_while_tool_running: self.busy_while_tool_running,
+        }
+    }
+
+    /// Global settings, then the project's override, then those of the
+    /// agent types in order (see [`agent_type_keys`]).
+    pub fn windows_for(&self, project: &str, agent_types: &[&str]) -> StateWindows {
+        let mut windows = self.global_windows();
+        if let Some(patch) = self.projects.get(project) {
+            windows.apply(patch);
+        }
+        for patch in agent_types.iter().filter_map(|t| self.agent_types.get(*t)) {
+            windows.apply(patch);
+        }
+        windows
+    }
+
+    /// Whether a tool call of `agent_id` (or, without one, of `session_id`)
+    /// started recently enough to still be running.
+    pub fn tool_call_in_flight<'a>(
+        &self,
+        pending: impl IntoIterator<Item = &'a PendingToolCall>,
+        session_id: &str,
+        agent_id: Option<&str>,
+        now: OffsetDateTime,
+    ) -> bool {
+        let timeout = secs(self.tool_call_timeout_secs);
+        pending.into_iter().any(|call| {
+            let owned = match agent_id {
+                Some(agent_id) => call.agent_id == agent_id,
+                None => !session_id.is_empty() && call.session_id == session_id,
+            };
+            owned
+                && OffsetDateTime::parse(&call.started_at, &Rfc3339)
+                    .is_ok_and(|started| (now - started).whole_seconds() < timeout)
+        })
+    }
+}
+
+/// Rejects windows that would make states unreachable, for the global
+/// settings and every override combination.
+pub fn validate_session_state(config: &SessionStateConfig) -> Result<(), String> {
+    if config.tool_call_timeout_secs == 0 {
+        return Err("sessionState: toolCallTimeoutSecs must be positive".to_string());
+    }
+    if config.agent_types.contains_key("") {
+        return Err("sessionState.agentTypes: keys must not be empty".to_string());
+    }
+    let mut agent_types = vec![vec![], vec![MAIN_AGENT], vec![SUBAGENT]];
+    for key in config.agent_types.keys() {
+        if key != MAIN_AGENT && key != SUBAGENT {
+            agent_types.push(vec![SUBAGENT, key.as_str()]);
+        }
+    }
+    config.windows_for("", &[]).validate("sessionState")?;
+    for keys in agent_types.iter().filter(|keys| !keys.is_empty()) {
+        config.windows_for("", keys).validate(&format!(
+            "sessionState.agentTypes.{:?}",
+            keys.last().unwrap_or(&"")
+        ))?;
+    }
+    for project in config.projects.keys() {
+        for keys in &agent_types {
+            config
+                .windows_for(project, keys)
+                .validate(&format!("sessionState.projects.{:?}", project))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(value: serde_json::Value) -> SessionStateConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn pending(agent_id: &str, session_id: &str, started_at: &str) -> PendingToolCall {
+        PendingToolCall {
+            tool_name: "Bash".to_string(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            started_at: started_at.to_string(),
+            subagent_type: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_windows_for_applies_project_then_agent_type() {
+        let config = config(serde_json::json!({
+            "stuckWindowSecs": 300,
+            "projects": { "refactor": { "stuckWindowSecs": 600, "terminalHints": ["Handoff"] } },
+            "agentTypes": { "subagent": { "stuckWindowSecs": 450 } }
+        }));
+        let main = config.windows_for("refactor", &[MAIN_AGENT]);
+        assert_eq!(main.stuck_secs, 600);
+        assert_eq!(main.active_secs, 30);
+        assert!(main.is_terminal_hint("handoff"));
+        assert!(!main.is_terminal_hint("stop"));
+
+        let sub = config.windows_for("refactor", &[SUBAGENT]);
+        assert_eq!(sub.stuck_secs, 450);
+        assert_eq!(config.windows_for("web", &[]).stuck_secs, 300);
+        assert!(config
+            .windows_for("web", &[])
+            .is_terminal_hint("Session End"));
+    }
+
+    #[test]
+    fn test_merge_keeps_most_patient_windows() {
+        let config = config(serde_json::json!({
+            "agentTypes": { "subagent": { "stuckWindowSecs": 600, "busyWhileToolRunning": true } }
+        }));
+        let merged = config
+            .windows_for("", &[MAIN_AGENT])
+            .merge(config.windows_for("", &[SUBAGENT]));
+        assert_eq!(merged.stuck_secs, 600);
+        assert_eq!(merged.completed_secs, 900);
+        assert!(merged.busy_while_tool_running);
+    }
+
+    #[test]
+    fn test_subagent_type_overrides_apply_after_subagent() {
+        let config = config(serde_json::json!({
+            "agentTypes": {
+                "subagent": { "stuckWindowSecs": 300, "completedWindowSecs": 1800 },
+                "refactor": { "stuckWindowSecs": 1200 }
+            }
+        }));
+        let mut agent = AgentRow {
+            is_sidechain: true,
+            subagent_type: "refactor".to_string(),
+            ..AgentRow::default()
+        };
+        let windows = config.windows_for("", &agent_type_keys(&agent));
+        assert_eq!((windows.stuck_secs, windows.completed_secs), (1200, 1800));
+        agent.subagent_type = "Explore".to_string();
+        assert_eq!(
+            config.windows_for("", &agent_type_keys(&agent)).stuck_secs,
+            300
+        );
+        agent.is_sidechain = false;
+        assert_eq!(agent_type_keys(&agent), vec![MAIN_AGENT]);
+        assert!(validate_session_state(&config).is_ok());
+    }
+
+    #[test]
+    fn test_tool_call_in_flight_respects_owner_and_timeout() {
+        let config = SessionStateConfig::default();
+        let now = OffsetDateTime::parse("2025-01-01T02:00:00Z", &Rfc3339).unwrap();
+        let calls = [
+            pending("a1", "s1", "2025-01-01T01:55:00Z"),
+            pending("a2", "s2", "2025-01-01T00:30:00Z"),
+        ];
+        assert!(config.tool_call_in_flight(&calls, "s1", None, now));
+        assert!(config.tool_call_in_flight(&calls, "", Some("a1"), now));
+        assert!(!config.tool_call_in_flight(&calls, "s1", Some("a3"), now));
+        assert!(!config.tool_call_in_flight(&calls, "s2", None, now));
+    }
+
+    #[test]
+    fn test_validate_session_state_rejects_unordered_windows() {
+        assert!(validate_session_state(&SessionStateConfig::default()).is_ok());
+        let bad_project = config(serde_json::json!({
+            "projects": { "web": { "stuckWindowSecs": 1000 } }
+        }));
+        assert!(validate_session_state(&bad_project).is_err());
+        let bad_global = config(serde_json::json!({ "activeWindowSecs": 0 }));
+        assert!(validate_session_state(&bad_global).is_err());
+        let bad_subagent_type = config(serde_json::json!({
+            "agentTypes": { "refactor": { "stuckWindowSecs": 1000 } }
+        }));
+        assert!(validate_session_state(&bad_subagent_type).is_err());
+        let empty_key = config(serde_json::json!({ "agentTypes": { "": {} } }));
+        assert!(validate_session_state(&empty_key).is_err());
+    }
+}
@@ -0,0 +1,662 @@
+use rusqlite::types::Value;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::db::{Db, SessionFilter, SessionOrder};
+use crate::state::{session_risk_for_export, ExportAlertRules};
+use crate::types::{AgentRow, SessionRow, State};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+/// Stored sessions read per query when a filter or the sort needs derived
+/// state.
+const SCAN_BATCH: usize = 500;
+const CURSOR_SEPARATOR: char = '\u{1f}';
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionSort {
+    LastSeen,
+    Cost,
+    Tokens,
+    Attention,
+}
+
+/// Filters, order and page of a `GET /api/sessions` request.
+#[derive(Clone, Debug)]
+pub struct SessionQuery {
+    pub states: HashSet<String>,
+    pub project: Option<String>,
+    pub model: Option<String>,
+    pub needs_attention: Option<bool>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub min_cost: Option<f64>,
+    pub search: Option<String>,
+    pub sort: SessionSort,
+    pub ascending: bool,
+    pub cursor: Option<String>,
+    pub limit: usize,
+    pub rules: ExportAlertRules,
+}
+
+fn non_empty(query: &HashMap<String, String>, key: &str) -> Option<String> {
+    query
+        .get(key)
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Normalizes an RFC 3339 bound to the UTC form `last_seen` is stored in.
+fn time_bound(query: &HashMap<String, String>, key: &str) -> Result<Option<String>, String> {
+    let Some(raw) = non_empty(query, key) else {
+        return Ok(None);
+    };
+    let parsed = OffsetDateTime::parse(&raw, &Rfc3339)
+        .map_err(|_| format!("{} must be an RFC 3339 timestamp", key))?;
+    Ok(Some(
+        parsed
+            .to_offset(time::UtcOffset::UTC)
+            .format(&Rfc3339)
+            .unwrap_or_default(),
+    ))
+}
+
+impl SessionQuery {
+    pub fn from_query(query: &HashMap<String, String>) -> Result<Self, String> {
+        let sort = match non_empty(query, "sort").as_deref() {
+            None | Some("lastSeen") => SessionSort::LastSeen,
+            Some("cost") => SessionSort::Cost,
+            Some("tokens") => SessionSort::Tokens,
+            Some("attention") => SessionSort::Attention,
+            Some(other) => return Err(format!("unknown sort {:?}", other)),
+        };
+        let ascending = match non_empty(query, "order").as_deref() {
+            None | Some("desc") => false,
+            Some("asc") => true,
+            Some(other) => return Err(format!("unknown order {:?}", other)),
+        };
+        let needs_attention = match non_empty(query, "needsAttention").as_deref() {
+            None => None,
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            Some(_) => return Err("needsAttention must be true or false".to_string()),
+        };
+        let min_cost = match non_empty(query, "minCost") {
+            None => None,
+            Some(raw) => Some(
+                raw.parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or("minCost must be a number")?,
+            ),
+        };
+        let limit = match non_empty(query, "limit") {
+            None => DEFAULT_PAGE_SIZE,
+            Some(raw) => raw
+                .parse::<usize>()
+                .map_err(|_| "limit must be a positive integer".to_string())?
+                .clamp(1, MAX_PAGE_SIZE),
+        };
+        Ok(Self {
+            states: non_empty(query, "state")
+                .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
+                .unwrap_or_default(),
+            project: non_empty(query, "project"),
+            model: non_empty(query, "model"),
+            needs_attention,
+            since: time_bound(query, "since")?,
+            until: time_bound(query, "until")?,
+            min_cost,
+            search: non_empty(query, "q").map(|q| q.to_lowercase()),
+            sort,
+            ascending,
+            cursor: non_empty(query, "cursor"),
+            limit,
+            rules: ExportAlertRules::from_query(query),
+        })
+    }
+
+    /// Whether stored sessions can be filtered, ordered and paged on their
+    /// columns alone.
+    fn column_only(&self) -> bool {
+        self.states.is_empty()
+            && self.model.is_none()
+            && self.needs_attention.is_none()
+            && self.search.is_none()
+            && self.sort != SessionSort::Attention
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionListRow {
+    /// Carries the risk recomputed for the request's thresholds.
+    #[serde(flatten)]
+    pub summary: SessionRow,
+    /// False for sessions only found in the database.
+    pub in_memory: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPage {
+    pub sessions: Vec<SessionListRow>,
+    /// Sessions matching the filters, across all pages.
+    pub total: usize,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum SortValue {
+    Text(String),
+    Number(f64),
+}
+
+fn sort_value(row: &SessionListRow, sort: SessionSort) -> SortValue {
+    match sort {
+        SessionSort::LastSeen => SortValue::Text(row.summary.last_seen.clone()),
+        SessionSort::Cost => SortValue::Number(row.summary.cost_usd),
+        SessionSort::Tokens => SortValue::Number(row.summary.token_total as f64),
+        SessionSort::Attention => SortValue::Number(row.summary.risk.needs_attention_rank as f64),
+    }
+}
+
+/// Page order: the sort value in the requested direction, then session id.
+fn compare(a: (&SortValue, &str), b: (&SortValue, &str), ascending: bool) -> Ordering {
+    let primary = match (a.0, b.0) {
+        (SortValue::Text(x), SortValue::Text(y)) => x.cmp(y),
+        (SortValue::Number(x), SortValue::Number(y)) => x.total_cmp(y),
+        _ => Ordering::Equal,
+    };
+    let primary = if ascending {
+        primary
+    } else {
+        primary.reverse()
+    };
+    primary.then_with(|| a.1.cmp(b.1))
+}
+
+fn encode_cursor(value: &SortValue, session_id: &str) -> String {
+    let value = match value {
+        SortValue::Text(text) => text.clone(),
+        SortValue::Number(number) => number.to_string(),
+    };
+    format!("{}{}{}", value, CURSOR_SEPARATOR, session_id)
+        .bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_cursor(cursor: &str, sort: SessionSort) -> Option<(SortValue, String)> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let text = String::from_utf8(bytes).ok()?;
+    let (value, session_id) = text.split_once(CURSOR_SEPARATOR)?;
+    let value = match sort {
+        SessionSort::LastSeen => SortValue::Text(value.to_string()),
+        _ => SortValue::Number(value.parse().ok()?),
+    };
+    Some((value, session_id.to_string()))
+}
+
+fn matches(row: &SessionListRow, agents: &[&AgentRow], query: &SessionQuery) -> bool {
+    let summary = &row.summary;
+    if !query.states.is_empty() && !query.states.contains(&row.summary.risk.session_state) {
+        return false;
+    }
+    if query
+        .project
+        .as_ref()
+        .is_some_and(|p| &summary.project_name != p)
+    {
+        return false;
+    }
+    if let Some(model) = &query.model {
+        let uses_model = summary.by_model.keys().any(|m| m.contains(model.as_str()))
+            || agents.iter().any(|a| a.model.contains(model.as_str()));
+        if !uses_model {
+            return false;
+        }
+    }
+    if query
+        .needs_attention
+        .is_some_and(|wanted| row.summary.risk.needs_attention != wanted)
+    {
+        return false;
+    }
+    if query.since.as_ref().is_some_and(|s| &summary.last_seen < s)
+        || query.until.as_ref().is_some_and(|u| &summary.last_seen > u)
+    {
+        return false;
+    }
+    if query.min_cost.is_some_and(|min| summary.cost_usd < min) {
+        return false;
+    }
+    if let Some(search) = &query.search {
+        if !summary.display_name.to_lowercase().contains(search) {
+            return false;
+        }
+    }
+    true
+}
+
+/// The state and request a listing is built from.
+struct Listing<'a> {
+    state: &'a State,
+    db: Option<&'a Db>,
+    query: &'a SessionQuery,
+    cursor: Option<(SortValue, String)>,
+    now: OffsetDateTime,
+    memory_agents: HashMap<&'a str, Vec<&'a AgentRow>>,
+}
+
+impl Listing<'_> {
+    /// Rows of `sessions` that pass the filters, with state and risk derived
+    /// from their agents (in memory, else stored).
+    fn rows(
+        &self,
+        sessions: Vec<SessionRow>,
+        in_memory: bool,
+    ) -> rusqlite::Result<Vec<(SortValue, SessionListRow)>> {
+        let stored_agents = match self.db {
+            Some(db) => {
+                let ids: Vec<&str> = sessions.iter().map(|s| s.session_id.as_str()).collect();
+                db.load_agents_for_sessions(&ids)?
+            }
+            None => Vec::new(),
+        };
+        let mut agents_by_session: HashMap<&str, Vec<&AgentRow>> = HashMap::new();
+        for agent in stored_agents
+            .iter()
+            .filter(|a| !self.state.by_agent.contains_key(&a.agent_id))
+        {
+            agents_by_session
+                .entry(agent.session_id.as_str())
+                .or_default()
+                .push(agent);
+        }
+        Ok(sessions
+            .into_iter()
+            .filter_map(|mut summary| {
+                let mut agents = agents_by_session
+                    .get(summary.session_id.as_str())
+                    .cloned()
+                    .unwrap_or_default();
+                if let Some(memory) = self.memory_agents.get(summary.session_id.as_str()) {
+                    agents.extend(memory);
+                }
+                summary.risk = session_risk_for_export(
+                    self.state,
+                    &summary,
+                    &agents,
+                    self.now,
+                    self.query.rules,
+                );
+                let row = SessionListRow { summary, in_memory };
+                matches(&row, &agents, self.query).then(|| (sort_value(&row, self.query.sort), row))
+            })
+            .collect())
+    }
+
+    fn after_cursor(&self, (value, row): &(SortValue, SessionListRow)) -> bool {
+        self.cursor.as_ref().is_none_or(|(cursor, session_id)| {
+            compare(
+                (value, &row.summary.session_id),
+                (cursor, session_id),
+                self.query.ascending,
+            ) == Ordering::Greater
+        })
+    }
+
+    /// Matching sessions in total, and the ones after the cursor that can
+    /// still make the page.
+    fn collect(&self) -> rusqlite::Result<(usize, Vec<(SortValue, SessionListRow)>)> {
+        let memory = self.state.by_session.values().cloned().collect();
+        let mut rows = self.rows(memory, true)?;
+        let mut total = rows.len();
+        rows.retain(|row| self.after_cursor(row));
+        if let Some(db) = self.db {
+            let (stored_total, stored) = self.stored(db)?;
+            total += stored_total;
+            rows.extend(stored);
+        }
+        Ok((total, rows))
+    }
+
+    /// Sessions only found in the database. Column filters, order and limit
+    /// run in SQLite; filters on derived state scan the matches in batches,
+    /// keeping only the best rows for the page.
+    fn stored(&self, db: &Db) -> rusqlite::Result<(usize, Vec<(SortValue, SessionListRow)>)> {
+        let query = self.query;
+        let filter = SessionFilter {
+            since: query.since.as_deref(),
+            until: query.until.as_deref(),
+            project: query.project.as_deref(),
+            min_cost: query.min_cost,
+            exclude: self.state.by_session.keys().map(String::as_str).collect(),
+        };
+        let order = match query.sort {
+            _ if !query.column_only() => None,
+            SessionSort::LastSeen => Some(SessionOrder::LastSeen),
+            SessionSort::Cost => Some(SessionOrder::Cost),
+            SessionSort::Tokens => Some(SessionOrder::Tokens),
+            SessionSort::Attention => None,
+        };
+        if let Some(order) = order {
+            let after = self
+                .cursor
+                .as_ref()
+                .map(|(value, session_id)| (sql_value(value), session_id.as_str()));
+            let page = db.page_sessions(&filter, order, query.ascending, after, query.limit + 1)?;
+            return Ok((db.count_sessions(&filter)?, self.rows(page, false)?));
+        }
+
+        let mut total = 0;
+        let mut page = Vec::new();
+        let mut last_id: Option<String> = None;
+        loop {
+            let after = last_id
+                .as_deref()
+                .map(|id| (Value::Text(id.to_string()), id));
+            let batch =
+                db.page_sessions(&filter, SessionOrder::SessionId, true, after, SCAN_BATCH)?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            last_id = Some(last.session_id.clone());
+            let done = batch.len() < SCAN_BATCH;
+            for row in self.rows(batch, false)? {
+                total += 1;
+                if self.after_cursor(&row) {
+                    page.push(row);
+                }
+            }
+            sort_rows(&mut page, query.ascending);
+            page.truncate(query.limit + 1);
+            if done {
+                break;
+            }
+        }
+        Ok((total, page))
+    }
+}
+
+fn sql_value(value: &SortValue) -> Value {
+    match value {
+        SortValue::Text(text) => Value::Text(text.clone()),
+        SortValue::Number(number) => Value::Real(*number),
+    }
+}
+
+fn sort_rows(rows: &mut [(SortValue, SessionListRow)], ascending: bool) {
+    rows.sort_by(|a, b| {
+        compare(
+            (&a.0, &a.1.summary.session_id),
+            (&b.0, &b.1.summary.session_id),
+            ascending,
+        )
+    });
+}
+
+/// The parts of `state` a listing reads. Listing from this copy lets the
+/// caller release the state lock before stored sessions are scanned.
+pub fn listing_snapshot(state: &State) -> State {
+    State {
+        by_session: state.by_session.clone(),
+        by_agent: state.by_agent.clone(),
+        pending_tool_calls: state.pending_tool_calls.clone(),
+        session_state_config: state.session_state_config.clone(),
+        ..State::default()
+    }
+}
+
+/// Lists sessions held in memory together with those only stored in `db`;
+/// a session in memory wins over its stored copy.
+pub fn list_sessions(
+    state: &State,
+    db: Option<&Db>,
+    query: &SessionQuery,
+    now: OffsetDateTime,
+) -> Result<SessionPage, String> {
+    let cursor = match &query.cursor {
+        Some(raw) => Some(decode_cursor(raw, query.sort).ok_or("invalid cursor")?),
+        None => None,
+    };
+    let mut memory_agents: HashMap<&str, Vec<&AgentRow>> = HashMap::new();
+    for agent in state.by_agent.values() {
+        memory_agents
+            .entry(agent.session_id.as_str())
+            .or_default()
+            .push(agent);
+    }
+    let listing = Listing {
+        state,
+        db,
+        query,
+        cursor,
+        now,
+        memory_agents,
+    };
+    let (total, mut rows) = listing.collect().unwrap_or_else(|e| {
+        eprintln!("[db] failed to list stored sessions: {e}");
+        Listing {
+            db: None,
+            ..listing
+        }
+        .collect()
+        .unwrap_or_default()
+    });
+
+    sort_rows(&mut rows, query.ascending);
+    let next_cursor = (rows.len() > query.limit)
+        .then(|| &rows[query.limit - 1])
+        .map(|(value, row)| encode_cursor(value, &row.summary.session_id));
+    rows.truncate(query.limit);
+    Ok(SessionPage {
+        sessions: rows.into_iter().map(|(_, row)| row).collect(),
+        total,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbWrite;
+    use crate::types::{test_rows, ModelUsage};
+    use std::path::Path;
+
+    fn at(iso: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(iso, &Rfc3339).unwrap()
+    }
+
+    fn session(id: &str, project: &str, last_seen: &str, cost_usd: f64) -> SessionRow {
+        SessionRow {
+            token_total: (cost_usd * 1000.0) as u64,
+            display_name: format!("{} fix {}", project, id),
+            ..test_rows::session(id, project, last_seen, cost_usd)
+        }
+    }
+
+    fn agent(session_id: &str, last_seen: &str, error: u64) -> AgentRow {
+        AgentRow {
+            total: 1 + error,
+            error,
+            model: "claude-sonnet-4".to_string(),
+            ..test_rows::agent(&format!("agent-{}", session_id), session_id, last_seen)
+        }
+    }
+
+    fn query(pairs: &[(&str, &str)]) -> SessionQuery {
+        let map = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        SessionQuery::from_query(&map).unwrap()
+    }
+
+    /// Three sessions in memory; `s0` and an older copy of `s1` only stored.
+    fn fixture() -> (State, Db) {
+        let mut state = State::default();
+        for (id, project, last_seen, cost, error) in [
+            ("s1", "web", "2025-01-15T10:00:00Z", 0.2, 0),
+            ("s2", "api", "2025-01-15T09:00:00Z", 2.0, 1),
+            ("s3", "web", "2025-01-15T08:00:00Z", 0.1, 0),
+        ] {
+            state
+                .by_session
+                .insert(id.to_string(), session(id, project, last_seen, cost));
+            state
+                .by_agent
+                .insert(format!("agent-{}", id), agent(id, last_seen, error));
+        }
+        let mut old = session("s0", "web", "2025-01-01T10:00:00Z", 5.0);
+        old.by_model
+            .insert("claude-opus-4".to_string(), ModelUsage::default());
+        let db = Db::open(Path::new(":memory:")).unwrap();
+        db.upsert_session(&old).unwrap();
+        db.upsert_session(&session("s1", "web", "2025-01-14T00:00:00Z", 0.0))
+            .unwrap();
+        db.upsert_agent(&agent("s0", "2025-01-01T10:00:00Z", 0))
+            .unwrap();
+        (state, db)
+    }
+
+    fn list(state: &State, db: &Db, pairs: &[(&str, &str)]) -> SessionPage {
+        let now = at("2025-01-15T10:00:10Z");
+        list_sessions(state, Some(db), &query(pairs), now).unwrap()
+    }
+
+    fn ids(page: &SessionPage) -> Vec<&str> {
+        page.sessions
+            .iter()
+            .map(|s| s.summary.session_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_list_sessions_merges_stored_and_sorts() {
+        let (state, db) = fixture();
+        let page = list(&state, &db, &[]);
+        assert_eq!(ids(&page), vec!["s1", "s2", "s3", "s0"]);
+        assert!(page.sessions[0].in_memory);
+        assert!(!page.sessions[3].in_memory);
+        assert_eq!(page.sessions[0].summary.last_seen, "2025-01-15T10:00:00Z");
+        assert_eq!(page.sessions[0].summary.risk.session_state, "active");
+        assert_eq!(page.sessions[3].summary.risk.session_state, "completed");
+
+        let page = list(&state, &db, &[("sort", "cost")]);
+        assert_eq!(ids(&page), vec!["s0", "s2", "s1", "s3"]);
+    }
+
+    #[test]
+    fn test_list_sessions_filters() {
+        let (state, db) = fixture();
+        let cases = [
+            (vec![("project", "web")], vec!["s1", "s3", "s0"]),
+            (vec![("state", "failed")], vec!["s2"]),
+            (vec![("state", "active,completed")], vec!["s1", "s3", "s0"]),
+            (vec![("model", "opus")], vec!["s0"]),
+            (vec![("needsAttention", "true")], vec!["s2", "s0"]),
+            (
+                vec![("since", "2025-01-15T08:30:00+00:00")],
+                vec!["s1", "s2"],
+            ),
+            (vec![("until", "2025-01-15T08:00:00Z")], vec!["s3", "s0"]),
+            (vec![("minCost", "1")], vec!["s2", "s0"]),
+            (vec![("q", "API FIX")], vec!["s2"]),
+        ];
+        let snapshot = listing_snapshot(&state);
+        for (pairs, expected) in cases {
+            let page = list(&state, &db, &pairs);
+            assert_eq!(ids(&page), expected, "{:?}", pairs);
+            assert_eq!(ids(&list(&snapshot, &db, &pairs)), expected, "{:?}", pairs);
+            assert_eq!(page.total, expected.len());
+        }
+    }
+
+    #[test]
+    fn test_list_sessions_cursor_pagination() {
+        let (state, db) = fixture();
+        for (sort, expected) in [
+            ("attention", vec!["s2", "s0", "s1", "s3"]),
+            ("lastSeen", vec!["s1", "s2", "s3", "s0"]),
+        ] {
+            let mut seen = Vec::new();
+            let mut cursor: Option<String> = None;
+            loop {
+                let mut pairs = vec![("sort", sort), ("limit", "3")];
+                if let Some(cursor) = &cursor {
+                    pairs.push(("cursor", cursor.as_str()));
+                }
+                let page = list(&state, &db, &pairs);
+                assert_eq!(page.total, 4);
+                seen.extend(ids(&page).into_iter().map(str::to_string));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected, "{}", sort);
+        }
+    }
+
+    #[test]
+    fn test_list_sessions_pages_every_stored_session() {
+        let state = State::default();
+        let db = Db::open(Path::new(":memory:")).unwrap();
+        let writes: Vec<DbWrite> = (0..SCAN_BATCH + 20)
+            .map(|i| {
+                let id = format!("s{:04}", i);
+                let cost = if i % 2 == 0 { 1.0 } else { 0.0 };
+                let row = session(&id, "web", "2025-01-01T10:00:00Z", cost);
+                DbWrite::Session(Box::new(row))
+            })
+            .collect();
+        db.apply_batch(&writes).unwrap();
+
+        for pairs in [vec![("minCost", "1")], vec![("minCost", "1"), ("q", "fix")]] {
+            let mut seen = HashSet::new();
+            let mut cursor: Option<String> = None;
+            loop {
+                let mut pairs = pairs.clone();
+                pairs.extend([("sort", "cost"), ("limit", "200")]);
+                if let Some(cursor) = &cursor {
+                    pairs.push(("cursor", cursor.as_str()));
+                }
+                let page = list(&state, &db, &pairs);
+                assert_eq!(page.total, SCAN_BATCH / 2 + 10, "{:?}", pairs);
+                seen.extend(ids(&page).into_iter().map(str::to_string));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(seen.len(), SCAN_BATCH / 2 + 10, "{:?}", pairs);
+        }
+    }
+
+    #[test]
+    fn test_session_query_rejects_bad_values() {
+        let parse = |pairs: &[(&str, &str)]| {
+            let map: HashMap<String, String> = pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            SessionQuery::from_query(&map)
+        };
+        assert!(parse(&[("sort", "name")]).is_err());
+        assert!(parse(&[("since", "yesterday")]).is_err());
+        assert!(parse(&[("limit", "-1")]).is_err());
+        assert_eq!(parse(&[("limit", "5000")]).unwrap().limit, MAX_PAGE_SIZE);
+        let (state, db) = fixture();
+        let bad_cursor = query(&[("cursor", "zz")]);
+        assert!(list_sessions(&state, Some(&db), &bad_cursor, OffsetDateTime::now_utc()).is_err());
+    }
+}
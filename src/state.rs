@@ -1,19 +1,30 @@
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration as StdDuration;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+use crate::alerts::{broadcast_alert, record_alert, MAX_ALERTS, STATUS_OPEN};
+use crate::db::{Db, DbWrite};
+use crate::pricing::PricingTable;
+use crate::rules::record_rule_sample;
+use crate::session_state::{agent_type_keys, StateWindows};
 use crate::types::{
-    AgentRow, AlertRow, App, Event, HourBucket, SessionExport, SessionExportAlert,
-    SessionExportContext, SessionExportRisk, SessionRow, Snapshot, SourceRow, State, ToolCallStat,
-    WorkflowRow,
+    AgentRow, AlertRow, App, Event, HourBucket, ModelUsage, ProjectRow, SeenKeys, SessionExport,
+    SessionExportAlert, SessionExportContext, SessionExportRisk, SessionRow, SessionStateChange,
+    Snapshot, SourceRow, State, TokenBreakdown, ToolCallStat, WorkflowRow,
 };
 use crate::utils::now_iso;
 
-const ACTIVE_WINDOW_SECS: i64 = 30;
-const STUCK_WINDOW_SECS: i64 = 120;
-const COMPLETED_FALLBACK_WINDOW_SECS: i64 = 900;
+/// Hours of history kept in memory (31 days).
+pub const MAX_HOUR_BUCKETS: usize = 744;
+pub const SCOPE_PROJECT: &str = "project";
+pub const SCOPE_MODEL: &str = "model";
+const SESSION_STATE_TICK_SECS: u64 = 5;
+const PROJECT_FLUSH_SECS: u64 = 5;
+const MAX_PENDING_SUBAGENT_TYPES: usize = 200;
 const ALERT_WARNING_COUNT_THRESHOLD: u64 = 1;
 const ALERT_COST_USD_THRESHOLD: f64 = 0.5;
 const ALERT_TOKEN_TOTAL_THRESHOLD: u64 = 20_000;
@@ -87,26 +98,31 @@ fn elapsed_secs_from(last_seen: &str, now: OffsetDateTime) -> Option<i64> {
     Some((now - parsed).whole_seconds())
 }
 
-fn is_terminal_event_hint(last_event: &str) -> bool {
-    matches!(
-        last_event
-            .trim()
-            .to_ascii_lowercase()
-            .replace([' ', '-'], "_")
-            .as_str(),
-        "done"
-            | "complete"
-            | "completed"
-            | "finish"
-            | "finished"
-            | "session_complete"
-            | "session_completed"
-            | "session_end"
-            | "stop"
-            | "stopped"
-            | "exit"
-            | "exited"
-    )
+/// Configured windows and terminal hints for one agent, by the project of
+/// its session and by its agent type.
+pub fn agent_state_windows(state: &State, agent: &AgentRow) -> StateWindows {
+    let project = state
+        .by_session
+        .get(&agent.session_id)
+        .map(|session| session.project_name.as_str())
+        .unwrap_or_default();
+    state
+        .session_state_config
+        .windows_for(project, &agent_type_keys(agent))
+}
+
+/// Windows for a session, merged over its agents.
+fn session_state_windows(
+    state: &State,
+    summary: &SessionRow,
+    agents: &[&AgentRow],
+) -> StateWindows {
+    let config = &state.session_state_config;
+    agents
+        .iter()
+        .map(|agent| config.windows_for(&summary.project_name, &agent_type_keys(agent)))
+        .reduce(StateWindows::merge)
+        .unwrap_or_else(|| config.windows_for(&summary.project_name, &[]))
 }
 
 pub fn workflow_row(state: &State, role_id: &str) -> WorkflowRow {
@@ -117,18 +133,27 @@ fn workflow_row_at(state: &State, role_id: &str, now: OffsetDateTime) -> Workflo
     if let Some(row) = state.by_agent.get(role_id) {
         let elapsed = elapsed_secs_from(&row.last_seen, now);
 
+        let windows = agent_state_windows(state, row);
+        let busy = windows.busy_while_tool_running
+            && state.session_state_config.tool_call_in_flight(
+                state.pending_tool_calls.values(),
+                &row.session_id,
+                Some(&row.agent_id),
+                now,
+            );
         let status = if row.error > 0 {
             "blocked"
         } else if row.total == 0 {
             "idle"
         } else {
             match elapsed {
-                Some(s) if s < ACTIVE_WINDOW_SECS => "running",
-                Some(s) if is_terminal_event_hint(&row.last_event) && s >= STUCK_WINDOW_SECS => {
+                Some(s) if s < windows.active_secs => "running",
+                Some(_) if busy => "running",
+                Some(s) if windows.is_terminal_hint(&row.last_event) && s >= windows.stuck_secs => {
                     "completed"
                 }
-                Some(s) if s >= COMPLETED_FALLBACK_WINDOW_SECS => "completed",
-                Some(s) if s >= STUCK_WINDOW_SECS => "at-risk",
+                Some(s) if s >= windows.completed_secs => "completed",
+                Some(s) if s >= windows.stuck_secs => "at-risk",
                 Some(_) => "idle",
                 None => "idle",
             }
@@ -164,7 +189,7 @@ pub fn get_session_events(state: &State, session_id: &str) -> Vec<Event> {
         .unwrap_or_default()
 }
 
-fn session_agent_rows<'a>(state: &'a State, summary: &'a SessionRow) -> Vec<&'a AgentRow> {
+pub fn session_agent_rows<'a>(state: &'a State, summary: &'a SessionRow) -> Vec<&'a AgentRow> {
     summary
         .agent_ids
         .iter()
@@ -173,16 +198,35 @@ fn session_agent_rows<'a>(state: &'a State, summary: &'a SessionRow) -> Vec<&'a
         .collect()
 }
 
+/// Current state of one session (`active / busy / idle / stuck / completed / failed`).
+pub fn session_state_at(state: &State, summary: &SessionRow, now: OffsetDateTime) -> String {
+    session_state_for_export(state, summary, &session_agent_rows(state, summary), now)
+}
+
+/// `agents` may come from outside `state` (e.g. the database); `state` only
+/// supplies the configured windows and the tool calls still in flight.
 fn session_state_for_export(
+    state: &State,
     summary: &SessionRow,
     agents: &[&AgentRow],
     now: OffsetDateTime,
 ) -> String {
     let total: u64 = agents.iter().map(|agent| agent.total).sum();
     let error: u64 = agents.iter().map(|agent| agent.error).sum();
+    let windows = session_state_windows(state, summary, agents);
+    // A subagent finishing does not end the session while a lead agent exists.
+    let has_lead = agents.iter().any(|agent| !agent.is_sidechain);
     let has_terminal_hint = agents
         .iter()
-        .any(|agent| is_terminal_event_hint(&agent.last_event));
+        .filter(|agent| !has_lead || !agent.is_sidechain)
+        .any(|agent| windows.is_terminal_hint(&agent.last_event));
+    let busy = windows.busy_while_tool_running
+        && state.session_state_config.tool_call_in_flight(
+            state.pending_tool_calls.values(),
+            &summary.session_id,
+            None,
+            now,
+        );
     let elapsed = elapsed_secs_from(&summary.last_seen, now);
 
     if error > 0 {
@@ -191,23 +235,25 @@ fn session_state_for_export(
         "idle".to_string()
     } else {
         match elapsed {
-            Some(s) if s < ACTIVE_WINDOW_SECS => "active".to_string(),
-            Some(s) if has_terminal_hint && s >= STUCK_WINDOW_SECS => "completed".to_string(),
-            Some(s) if s >= COMPLETED_FALLBACK_WINDOW_SECS => "completed".to_string(),
-            Some(s) if s >= STUCK_WINDOW_SECS => "stuck".to_string(),
+            Some(s) if s < windows.active_secs => "active".to_string(),
+            Some(_) if busy => "busy".to_string(),
+            Some(s) if has_terminal_hint && s >= windows.stuck_secs => "completed".to_string(),
+            Some(s) if s >= windows.completed_secs => "completed".to_string(),
+            Some(s) if s >= windows.stuck_secs => "stuck".to_string(),
             Some(_) => "idle".to_string(),
             None => "idle".to_string(),
         }
     }
 }
 
-fn session_risk_for_export(
+pub fn session_risk_for_export(
+    state: &State,
     summary: &SessionRow,
     agents: &[&AgentRow],
     now: OffsetDateTime,
     rules: ExportAlertRules,
 ) -> SessionExportRisk {
-    let session_state = session_state_for_export(summary, agents, now);
+    let session_state = session_state_for_export(state, summary, agents, now);
     let warning: u64 = agents.iter().map(|agent| agent.warning).sum();
     let is_cost_spike = summary.cost_usd >= rules.cost_usd_threshold
         || summary.token_total >= rules.token_total_threshold;
@@ -245,6 +291,124 @@ fn session_risk_for_export(
     }
 }
 
+/// Recomputes the state and risk stored on one session, returning the change
+/// when its state moved.
+pub fn refresh_session_state(
+    state: &mut State,
+    session_id: &str,
+    now: OffsetDateTime,
+) -> Option<SessionStateChange> {
+    let summary = state.by_session.get(session_id)?;
+    let risk = session_risk_for_export(
+        state,
+        summary,
+        &session_agent_rows(state, summary),
+        now,
+        ExportAlertRules::default(),
+    );
+    let session = state.by_session.get_mut(session_id)?;
+    let previous = std::mem::replace(&mut session.risk, risk);
+    (previous.session_state != session.risk.session_state).then(|| SessionStateChange {
+        session_id: session_id.to_string(),
+        previous_state: Some(previous.session_state).filter(|s| !s.is_empty()),
+        risk: session.risk.clone(),
+        changed_at: now.format(&Rfc3339).unwrap_or_default(),
+    })
+}
+
+/// [`refresh_session_state`] for every session in memory. `stuck` and
+/// `completed` are reached by time alone, so this runs on a timer.
+pub fn refresh_session_states(state: &mut State, now: OffsetDateTime) -> Vec<SessionStateChange> {
+    let session_ids: Vec<String> = state.by_session.keys().cloned().collect();
+    session_ids
+        .iter()
+        .filter_map(|session_id| refresh_session_state(state, session_id, now))
+        .collect()
+}
+
+pub fn broadcast_session_state(app: &App, change: &SessionStateChange) {
+    let payload = json!({ "type": "session_state_changed", "payload": change }).to_string();
+    broadcast_sse(app, format!("data: {}\n\n", payload));
+}
+
+/// The derived alert a session's export lists for a move into `stuck` or
+/// `failed`, as sent to the notifiers. Its fingerprint keys the notifier
+/// cooldown per session and state.
+fn session_state_alert(state: &State, change: &SessionStateChange) -> Option<AlertRow> {
+    let summary = state.by_session.get(&change.session_id)?;
+    let derived = derived_export_alert(summary, &change.risk, &change.risk.session_state)?;
+    Some(AlertRow {
+        id: derived.id.clone(),
+        severity: derived.severity,
+        agent_id: derived.agent_id,
+        session_id: derived.session_id,
+        event: derived.event,
+        message: derived.message,
+        created_at: change.changed_at.clone(),
+        fingerprint: derived.id,
+        count: 1,
+        last_seen: change.changed_at.clone(),
+        status: STATUS_OPEN.to_string(),
+        ..AlertRow::default()
+    })
+}
+
+/// Writes the project rows changed since the last flush. Project totals move
+/// on every event, so they are persisted on a timer instead.
+pub fn flush_dirty_projects(app: &App) {
+    let Some(writer) = &app.db_writer else {
+        return;
+    };
+    let projects: Vec<ProjectRow> = {
+        let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+        let dirty = std::mem::take(&mut state.dirty_projects);
+        dirty
+            .iter()
+            .filter_map(|name| state.by_project.get(name).cloned())
+            .collect()
+    };
+    for project in projects {
+        writer.send(DbWrite::Project(Box::new(project)));
+    }
+}
+
+pub fn spawn_project_flusher(app: App) {
+    thread::spawn(move || loop {
+        thread::sleep(StdDuration::from_secs(PROJECT_FLUSH_SECS));
+        flush_dirty_projects(&app);
+    });
+}
+
+/// Moves sessions into `stuck` / `completed` as time passes, persisting and
+/// broadcasting each transition.
+pub fn spawn_session_state_ticker(app: App) {
+    thread::spawn(move || loop {
+        thread::sleep(StdDuration::from_secs(SESSION_STATE_TICK_SECS));
+        let (changes, notices) = {
+            let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+            let changes = refresh_session_states(&mut state, OffsetDateTime::now_utc());
+            if let Some(writer) = &app.db_writer {
+                for change in &changes {
+                    if let Some(session) = state.by_session.get(&change.session_id) {
+                        writer.send(DbWrite::Session(Box::new(session.clone())));
+                    }
+                }
+            }
+            let notices: Vec<AlertRow> = changes
+                .iter()
+                .filter_map(|change| session_state_alert(&state, change))
+                .collect();
+            (changes, notices)
+        };
+        if let Some(notifier) = &app.notifier {
+            notices.iter().for_each(|notice| notifier.notify(notice));
+        }
+        for change in &changes {
+            broadcast_session_state(&app, change);
+        }
+    });
+}
+
 fn derived_export_alert(
     summary: &SessionRow,
     _risk: &SessionExportRisk,
@@ -338,14 +502,16 @@ pub fn get_session_export(
     session_id: &str,
     rules: Option<ExportAlertRules>,
 ) -> Option<SessionExport> {
-    let summary = state.by_session.get(session_id)?.clone();
+    let mut summary = state.by_session.get(session_id)?.clone();
     let agents = session_agent_rows(state, &summary);
     let risk = session_risk_for_export(
+        state,
         &summary,
         &agents,
         OffsetDateTime::now_utc(),
         rules.unwrap_or_default(),
     );
+    summary.risk = risk.clone();
     let alerts = linked_alerts_for_export(&state.alerts, &summary, &risk);
     Some(SessionExport {
         exported_at: now_iso(),
@@ -372,6 +538,8 @@ pub fn build_snapshot(state: &State) -> Snapshot {
             "tokenTotal": 0,
             "costTotalUsd": state.cost_total_usd,
             "sessions": state.by_session.len(),
+            "usage": state.usage_total,
+            "duplicatesSkipped": state.duplicates_skipped,
         }),
         |mut acc, row| {
             acc["total"] = json!(acc["total"].as_u64().unwrap_or(0) + row.total);
@@ -481,13 +649,132 @@ pub fn generate_session_display_name(
     format!("Session {}", short_session_id)
 }
 
-pub fn append_event(app: &App, evt: Event) {
-    {
+/// `Event::model`, falling back to `metadata.model` for events that only carry it there.
+fn event_model(evt: &Event) -> &str {
+    if evt.model.is_empty() {
+        evt.metadata
+            .get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or("")
+    } else {
+        evt.model.as_str()
+    }
+}
+
+/// Fills `metadata.costUsd` on `token_usage` events that do not already carry a cost.
+fn price_token_usage(pricing: &PricingTable, evt: &mut Event) {
+    if evt.event != "token_usage" || evt.metadata.get("costUsd").is_some() {
+        return;
+    }
+    let Some(usage) = evt.metadata.get("tokenUsage") else {
+        return;
+    };
+    if let Some(cost) = pricing.cost_for(event_model(evt), usage) {
+        if let Some(metadata) = evt.metadata.as_object_mut() {
+            metadata.insert("costUsd".to_string(), json!(cost));
+        }
+    }
+}
+
+fn event_source(evt: &Event) -> &str {
+    evt.metadata
+        .get("source")
+        .and_then(|v| v.as_str())
+        .unwrap_or("manual")
+}
+
+/// OTel metrics and the local feeds (session JSONL, hooks, manual ingest)
+/// describe the same spend; a session counts usage from whichever reported
+/// it first and keeps the other on the timeline only.
+fn same_usage_feed(claimed: &str, source: &str) -> bool {
+    (claimed == "otel") == (source == "otel")
+}
+
+fn event_cost_delta(evt: &Event, pricing: &PricingTable) -> f64 {
+    let source = evt.metadata.get("source").and_then(|v| v.as_str());
+    let key = match evt.event.as_str() {
+        // stats-cache.json diffs are account-wide and unattributed; spend of
+        // priced models is already counted per session from their token_usage
+        // events, so only models without a price fall back to the diff.
+        "cost_update" if source == Some("stats_cache") => {
+            return evt
+                .metadata
+                .get("modelCostDeltas")
+                .and_then(|v| v.as_object())
+                .map(|deltas| {
+                    deltas
+                        .iter()
+                        .filter(|(model, _)| pricing.price_for(model).is_none())
+                        .filter_map(|(_, delta)| delta.as_f64())
+                        .filter(|&d| d > 0.0)
+                        .sum()
+                })
+                .unwrap_or(0.0)
+        }
+        "cost_update" => "costDelta",
+        "token_usage" => "costUsd",
+        _ => return 0.0,
+    };
+    evt.metadata
+        .get(key)
+        .and_then(|v| v.as_f64())
+        .filter(|&d| d > 0.0)
+        .unwrap_or(0.0)
+}
+
+pub fn append_event(app: &App, mut evt: Event) {
+    price_token_usage(&app.config.pricing, &mut evt);
+    let (persisted, alert, state_change, state_notice) = {
         let mut state = app.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(key) = &evt.dedup_key {
+            if !state.seen_event_keys.insert(key) {
+                state.duplicates_skipped += 1;
+                return;
+            }
+        }
         state.recent.insert(0, evt.clone());
         if state.recent.len() > 200 {
             state.recent.truncate(200);
         }
+        let usage_source = event_source(&evt).to_string();
+        let usage_counted = state
+            .by_session
+            .get(&evt.session_id)
+            .and_then(|session| session.usage_source.as_deref())
+            .is_none_or(|claimed| same_usage_feed(claimed, &usage_source));
+        let agent_is_new = !state.by_agent.contains_key(&evt.agent_id);
+        // Subagent hooks name their own type; a Task result names the type
+        // of the subagent it ran, which may not have been seen yet.
+        let mut own_subagent_type = evt
+            .metadata
+            .get("subagentType")
+            .and_then(|v| v.as_str())
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        if let Some(subagent_id) = evt
+            .metadata
+            .get("subagentId")
+            .and_then(|v| v.as_str())
+            .filter(|id| *id != evt.agent_id)
+        {
+            if let Some(subagent_type) = own_subagent_type.take() {
+                let state = &mut *state;
+                match state.by_agent.get_mut(subagent_id) {
+                    Some(agent) => agent.subagent_type = subagent_type,
+                    None if state.pending_subagent_types.len() < MAX_PENDING_SUBAGENT_TYPES => {
+                        state
+                            .pending_subagent_types
+                            .insert(subagent_id.to_string(), subagent_type);
+                    }
+                    None => {}
+                }
+            }
+        }
+        if agent_is_new {
+            if let Some(learned) = state.pending_subagent_types.remove(&evt.agent_id) {
+                own_subagent_type.get_or_insert(learned);
+            }
+        }
 
         let row = state
             .by_agent
@@ -495,26 +782,19 @@ pub fn append_event(app: &App, evt: Event) {
             .or_insert(AgentRow {
                 agent_id: evt.agent_id.clone(),
                 last_seen: evt.received_at.clone(),
-                total: 0,
-                ok: 0,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: evt.event.clone(),
-                latency_ms: None,
                 model: evt.model.clone(),
                 is_sidechain: evt.is_sidechain,
                 session_id: evt.session_id.clone(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             });
 
         row.last_seen = evt.received_at.clone();
         row.total += 1;
         row.last_event = evt.event.clone();
-        row.latency_ms = evt.latency_ms;
+        if evt.latency_ms.is_some() {
+            row.latency_ms = evt.latency_ms;
+        }
         if !evt.model.is_empty() {
             row.model = evt.model.clone();
         }
@@ -522,6 +802,9 @@ pub fn append_event(app: &App, evt: Event) {
         if !evt.session_id.is_empty() {
             row.session_id = evt.session_id.clone();
         }
+        if let Some(subagent_type) = own_subagent_type {
+            row.subagent_type = subagent_type;
+        }
         if (evt.event == "user_message" || evt.event == "user_request")
             && !evt.message.is_empty()
             && !row.display_name_from_user
@@ -555,20 +838,18 @@ pub fn append_event(app: &App, evt: Event) {
             *row.tool_use_counts.entry(evt.message.clone()).or_insert(0) += 1;
         }
 
-        let token_total = evt
-            .metadata
-            .get("tokenUsage")
-            .and_then(|v| v.get("totalTokens"))
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-        let cost_delta = if evt.event == "cost_update" {
-            evt.metadata
-                .get("costDelta")
-                .and_then(|v| v.as_f64())
-                .filter(|&d| d > 0.0)
-                .unwrap_or(0.0)
+        let (token_total, cost_delta, usage) = if usage_counted {
+            (
+                evt.metadata
+                    .get("tokenUsage")
+                    .and_then(|v| v.get("totalTokens"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0),
+                event_cost_delta(&evt, &app.config.pricing),
+                TokenBreakdown::from_token_usage(evt.metadata.get("tokenUsage")),
+            )
         } else {
-            0.0
+            (0, 0.0, TokenBreakdown::default())
         };
 
         if token_total > 0 {
@@ -577,6 +858,17 @@ pub fn append_event(app: &App, evt: Event) {
         if cost_delta > 0.0 {
             row.cost_usd += cost_delta;
         }
+        row.usage.add(&usage);
+        // Usage belongs to the model that reported it; an error without a
+        // model goes to the model the agent was last seen with.
+        let is_request = token_total > 0 || cost_delta > 0.0 || !usage.is_empty();
+        let is_error = evt.status == "error";
+        let model_key = match event_model(&evt) {
+            _ if !is_request && !is_error => String::new(),
+            "" if is_request => "unknown".to_string(),
+            "" => row.model.clone(),
+            model => model.to_string(),
+        };
 
         // row is no longer used — update state-level accumulators
         if token_total > 0 {
@@ -585,36 +877,71 @@ pub fn append_event(app: &App, evt: Event) {
         if cost_delta > 0.0 {
             state.cost_total_usd += cost_delta;
         }
+        state.usage_total.add(&usage);
+        let project_name = state
+            .by_session
+            .get(&evt.session_id)
+            .map(|s| s.project_name.clone())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| extract_project_name(&evt.cwd).to_string());
+        if !model_key.is_empty() {
+            state
+                .by_model
+                .entry(model_key.clone())
+                .or_default()
+                .add_event(token_total, cost_delta, &usage, is_error);
+        }
 
-        if (token_total > 0 || cost_delta > 0.0) && evt.received_at.len() >= 13 {
-            let hour_key = &evt.received_at[..13];
-            // rev() scan is O(1) in normal operation (latest bucket matches);
-            // worst-case O(744) for backfilled events, acceptable for bounded vec.
-            if let Some(bucket) = state
-                .hourly_buckets
-                .iter_mut()
-                .rev()
-                .find(|b| b.hour_key == hour_key)
-            {
-                bucket.token_total += token_total;
-                bucket.cost_usd += cost_delta;
-            } else {
-                state.hourly_buckets.push(HourBucket {
-                    hour_key: hour_key.to_string(),
-                    token_total,
-                    cost_usd: cost_delta,
+        // Persisted per hour so global per-model totals survive a restart.
+        let mut scoped_delta = ModelUsage::default();
+        scoped_delta.add_event(token_total, cost_delta, &usage, is_error);
+        if !is_request && is_error && !model_key.is_empty() && evt.received_at.len() >= 13 {
+            if let Some(writer) = &app.db_writer {
+                writer.send(DbWrite::ScopedBucket {
+                    scope: SCOPE_MODEL.to_string(),
+                    name: model_key.clone(),
+                    hour_key: evt.received_at[..13].to_string(),
+                    delta: scoped_delta.clone(),
                 });
-                if state.hourly_buckets.len() > 744 {
-                    state.hourly_buckets.remove(0);
+            }
+        }
+        if is_request && evt.received_at.len() >= 13 {
+            let hour_key = &evt.received_at[..13];
+            bump_hour_bucket(
+                &mut state.hourly_buckets,
+                hour_key,
+                token_total,
+                cost_delta,
+                &usage,
+            );
+            // Budgets and the model mix report need usage per project and model.
+            let scoped = [
+                (SCOPE_PROJECT, project_name.clone()),
+                (SCOPE_MODEL, model_key.clone()),
+            ];
+            for (scope, name) in scoped.into_iter().filter(|(_, name)| !name.is_empty()) {
+                let series = match scope {
+                    SCOPE_PROJECT => state.project_hourly.entry(name.clone()).or_default(),
+                    _ => state.model_hourly.entry(name.clone()).or_default(),
+                };
+                bump_hour_bucket(series, hour_key, token_total, cost_delta, &usage);
+                if let Some(writer) = &app.db_writer {
+                    writer.send(DbWrite::ScopedBucket {
+                        scope: scope.to_string(),
+                        name,
+                        hour_key: hour_key.to_string(),
+                        delta: scoped_delta.clone(),
+                    });
                 }
             }
 
-            if let Some(db_arc) = &app.db {
-                if let Ok(db) = db_arc.lock() {
-                    if let Err(e) = db.upsert_bucket(hour_key, token_total, cost_delta) {
-                        eprintln!("[db] upsert_bucket error: {e}");
-                    }
-                }
+            if let Some(writer) = &app.db_writer {
+                writer.send(DbWrite::Bucket {
+                    hour_key: hour_key.to_string(),
+                    tokens: token_total,
+                    cost: cost_delta,
+                    usage,
+                });
             }
         }
 
@@ -625,9 +952,15 @@ pub fn append_event(app: &App, evt: Event) {
                 .or_insert(0) += 1;
         }
 
+        // Sessions and agents joining `project_name` with this event.
+        let mut joined = (0, u64::from(agent_is_new && evt.session_id.is_empty()));
         if !evt.session_id.is_empty() {
             let short_id: String = evt.session_id.chars().take(8).collect();
             let project = extract_project_name(&evt.cwd).to_string();
+            let had_project = state
+                .by_session
+                .get(&evt.session_id)
+                .is_some_and(|s| !s.project_name.is_empty());
             let session = state
                 .by_session
                 .entry(evt.session_id.clone())
@@ -636,13 +969,10 @@ pub fn append_event(app: &App, evt: Event) {
                     SessionRow {
                         session_id: evt.session_id.clone(),
                         last_seen: evt.received_at.clone(),
-                        token_total: 0,
-                        cost_usd: 0.0,
-                        agent_ids: vec![],
                         display_name,
                         project_name: project.clone(),
                         short_session_id: short_id.clone(),
-                        display_name_locked: false,
+                        ..SessionRow::default()
                     }
                 });
             session.last_seen = evt.received_at.clone();
@@ -652,12 +982,29 @@ pub fn append_event(app: &App, evt: Event) {
             if cost_delta > 0.0 {
                 session.cost_usd += cost_delta;
             }
-            if !session.agent_ids.contains(&evt.agent_id) {
+            session.usage.add(&usage);
+            if session.usage_source.is_none() && (token_total > 0 || cost_delta > 0.0) {
+                session.usage_source = Some(usage_source.clone());
+            }
+            if !model_key.is_empty() {
+                session
+                    .by_model
+                    .entry(model_key.clone())
+                    .or_default()
+                    .add_event(token_total, cost_delta, &usage, is_error);
+            }
+            let agent_joined = !session.agent_ids.contains(&evt.agent_id);
+            if agent_joined {
                 session.agent_ids.push(evt.agent_id.clone());
             }
             if session.project_name.is_empty() && !project.is_empty() {
                 session.project_name = project.clone();
             }
+            if !had_project && !session.project_name.is_empty() {
+                joined = (1, session.agent_ids.len() as u64);
+            } else if agent_joined {
+                joined.1 = 1;
+            }
             if (evt.event == "user_message" || evt.event == "user_request")
                 && !evt.message.is_empty()
                 && !session.display_name_locked
@@ -696,17 +1043,62 @@ pub fn append_event(app: &App, evt: Event) {
                 {
                     state.events_by_session.remove(&oldest_key);
                     state.by_session.remove(&oldest_key);
+                    state
+                        .otel_cumulative
+                        .retain(|_, series| series.session_id != oldest_key);
                 }
             }
         }
 
-        let source = evt
-            .metadata
-            .get("source")
-            .and_then(|v| v.as_str())
-            .unwrap_or("manual")
-            .to_string();
+        let state_change =
+            refresh_session_state(&mut state, &evt.session_id, OffsetDateTime::now_utc());
+
+        if !project_name.is_empty() {
+            let row = state
+                .by_project
+                .entry(project_name.clone())
+                .or_insert_with(|| ProjectRow {
+                    project_name: project_name.clone(),
+                    ..ProjectRow::default()
+                });
+            row.last_seen = evt.received_at.clone();
+            row.total += 1;
+            match evt.status.as_str() {
+                "error" => row.error += 1,
+                "warning" => row.warning += 1,
+                _ => row.ok += 1,
+            }
+            row.token_total += token_total;
+            row.cost_usd += cost_delta;
+            row.usage.add(&usage);
+            row.session_count += joined.0;
+            row.agent_count += joined.1;
+            if evt.event == "tool_call" {
+                *row.tool_use_counts.entry(evt.message.clone()).or_insert(0) += 1;
+            }
+            if !model_key.is_empty() {
+                row.by_model
+                    .entry(model_key.clone())
+                    .or_default()
+                    .add_event(token_total, cost_delta, &usage, is_error);
+            }
+            state.dirty_projects.insert(project_name.clone());
+        }
+
+        record_rule_sample(
+            &mut state,
+            &app.config.alert_rules,
+            &evt,
+            token_total,
+            cost_delta,
+        );
+
+        let source = usage_source;
 
+        *state
+            .event_counts
+            .entry((source.clone(), evt.event.clone(), evt.status.clone()))
+            .or_insert(0) += 1;
         let source_row = state.by_source.entry(source.clone()).or_insert(SourceRow {
             source,
             total: 0,
@@ -724,31 +1116,119 @@ pub fn append_event(app: &App, evt: Event) {
             _ => source_row.ok += 1,
         }
 
-        if evt.status == "warning" || evt.status == "error" {
-            state.alerts.insert(
-                0,
-                AlertRow {
-                    id: format!("a{}", app.event_seq.fetch_add(1, Ordering::Relaxed)),
-                    severity: evt.status.clone(),
-                    agent_id: evt.agent_id.clone(),
-                    session_id: evt.session_id.clone(),
-                    event: evt.event.clone(),
-                    message: if evt.message.is_empty() {
-                        "No message".to_string()
-                    } else {
-                        evt.message.clone()
-                    },
-                    created_at: evt.received_at.clone(),
-                },
-            );
-            if state.alerts.len() > 120 {
-                state.alerts.truncate(120);
-            }
+        let raises_alert = evt.status == "warning" || evt.status == "error";
+        let alert = raises_alert.then(|| {
+            record_alert(&mut state, &evt, || {
+                format!("a{}", app.event_seq.fetch_add(1, Ordering::Relaxed))
+            })
+        });
+
+        let persisted = app.db_writer.as_ref().and_then(|_| {
+            let agent = state.by_agent.get(&evt.agent_id)?.clone();
+            let session = state.by_session.get(&evt.session_id).cloned();
+            Some((agent, session))
+        });
+        let state_notice = state_change
+            .as_ref()
+            .and_then(|change| session_state_alert(&state, change));
+        (persisted, alert, state_change, state_notice)
+    };
+
+    if let Some(notifier) = &app.notifier {
+        for notice in alert.iter().chain(&state_notice) {
+            notifier.notify(notice);
         }
     }
+    if let (Some(writer), Some((agent, session))) = (&app.db_writer, persisted) {
+        writer.send(DbWrite::Record {
+            event: Box::new(evt.clone()),
+            agent: Box::new(agent),
+            session: session.map(Box::new),
+            alert: alert.clone().map(Box::new),
+        });
+    }
 
     let payload = json!({ "type": "event", "payload": evt }).to_string();
     broadcast_sse(app, format!("data: {}\n\n", payload));
+    if let Some(change) = &state_change {
+        broadcast_session_state(app, change);
+    }
+    if let Some(alert) = &alert {
+        broadcast_alert(app, alert);
+    }
+}
+
+/// Adds to the bucket for `hour_key`, keeping at most [`MAX_HOUR_BUCKETS`] hours.
+fn bump_hour_bucket(
+    buckets: &mut Vec<HourBucket>,
+    hour_key: &str,
+    tokens: u64,
+    cost: f64,
+    usage: &TokenBreakdown,
+) {
+    // rev() scan is O(1) in normal operation (latest bucket matches);
+    // worst-case O(744) for backfilled events, acceptable for bounded vec.
+    if let Some(bucket) = buckets.iter_mut().rev().find(|b| b.hour_key == hour_key) {
+        bucket.token_total += tokens;
+        bucket.cost_usd += cost;
+        bucket.usage.add(usage);
+    } else {
+        buckets.push(HourBucket {
+            hour_key: hour_key.to_string(),
+            token_total: tokens,
+            cost_usd: cost,
+            usage: *usage,
+        });
+        if buckets.len() > MAX_HOUR_BUCKETS {
+            buckets.remove(0);
+        }
+    }
+}
+
+/// Rebuilds the in-memory windows (recent feed, sessions, agents, alerts) from
+/// the persisted tables so a restart does not lose session timelines.
+pub fn restore_from_db(state: &mut State, db: &Db) -> rusqlite::Result<()> {
+    state.recent = db.load_recent_events(200)?;
+    for session in db.load_sessions(200)? {
+        let events = db.load_session_events(&session.session_id, 500)?;
+        state
+            .events_by_session
+            .insert(session.session_id.clone(), events);
+        state.by_session.insert(session.session_id.clone(), session);
+    }
+    for agent in db.load_agents(500)? {
+        state.by_agent.insert(agent.agent_id.clone(), agent);
+    }
+    // Totals cover every stored agent, not only those held in memory.
+    state.tool_use_counts = db.load_tool_use_totals()?;
+    state.alerts = db.load_alerts(MAX_ALERTS)?;
+    // Budget periods are at most a month; older fingerprints cannot match.
+    let budget_since = OffsetDateTime::now_utc() - time::Duration::days(32);
+    state.budgets_fired = db.load_alert_fingerprints(
+        "budget:",
+        &budget_since.format(&Rfc3339).unwrap_or_default(),
+    )?;
+    for project in db.load_projects()? {
+        state
+            .by_project
+            .insert(project.project_name.clone(), project);
+    }
+    let since = OffsetDateTime::now_utc() - time::Duration::hours(MAX_HOUR_BUCKETS as i64);
+    let since_key = since.format(&Rfc3339).unwrap_or_default();
+    for (scope, name, bucket) in db.query_scoped_since(since_key.get(..13).unwrap_or(""))? {
+        let series = match scope.as_str() {
+            SCOPE_PROJECT => state.project_hourly.entry(name).or_default(),
+            SCOPE_MODEL => state.model_hourly.entry(name).or_default(),
+            _ => continue,
+        };
+        series.push(bucket);
+    }
+    state.by_model = db.load_model_totals()?;
+    for key in db.load_dedup_keys(SeenKeys::capacity())? {
+        state.seen_event_keys.insert(&key);
+    }
+    refresh_session_states(state, OffsetDateTime::now_utc());
+    Ok(())
 }
 
 pub fn broadcast_sse(app: &App, message: String) {
@@ -759,6 +1239,8 @@ pub fn broadcast_sse(app: &App, message: String) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::MonitorConfig;
+    use crate::db_writer::DbWriter;
     use serde_json::json;
     use std::path::PathBuf;
     use std::sync::atomic::AtomicU64;
@@ -769,13 +1251,8 @@ mod tests {
         crate::types::SessionRow {
             session_id: session_id.to_string(),
             last_seen: last_seen.to_string(),
-            token_total: 0,
-            cost_usd: 0.0,
-            agent_ids: vec![],
-            display_name: String::new(),
-            project_name: String::new(),
             short_session_id: session_id.chars().take(8).collect(),
-            display_name_locked: false,
+            ..crate::types::SessionRow::default()
         }
     }
 
@@ -785,7 +1262,10 @@ mod tests {
             sse_clients: Arc::new(Mutex::new(Vec::new())),
             event_seq: Arc::new(AtomicU64::new(1)),
             public_dir: Arc::new(PathBuf::from("public")),
-            db: None,
+            db_writer: None,
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
         }
     }
 
@@ -809,6 +1289,7 @@ mod tests {
             is_sidechain: false,
             session_id: String::new(),
             cwd: String::new(),
+            dedup_key: None,
         }
     }
 
@@ -838,18 +1319,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "heartbeat".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 10초 후 → running
@@ -871,17 +1342,8 @@ mod tests {
                 total: 3,
                 ok: 1,
                 warning: 2,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "warn".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // warning만으로는 at-risk가 아니다
@@ -899,19 +1361,10 @@ mod tests {
                 agent_id: "agent-1".to_string(),
                 last_seen: "2025-01-01T00:00:00Z".to_string(),
                 total: 2,
-                ok: 0,
                 warning: 1,
                 error: 1,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "error".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // blocked는 시간 무관
@@ -928,20 +1381,8 @@ mod tests {
             AgentRow {
                 agent_id: "agent-1".to_string(),
                 last_seen: last_seen.to_string(),
-                total: 0,
-                ok: 0,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "-".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 3분 경과해도 total=0이면 idle
@@ -951,6 +1392,16 @@ mod tests {
         assert_eq!(row.status, "idle");
     }
 
+    #[test]
+    fn test_default_terminal_hints_recognize_hook_stops() {
+        let windows = State::default().session_state_config.windows_for("", &[]);
+        assert!(windows.is_terminal_hint("stop"));
+        assert!(windows.is_terminal_hint("subagent_stop"));
+        assert!(windows.is_terminal_hint("session_end"));
+        assert!(!windows.is_terminal_hint("post_tool_use"));
+        assert!(!windows.is_terminal_hint("notification"));
+    }
+
     #[test]
     fn test_workflow_row_completed_when_terminal_hint_over_2min() {
         let mut state = State::default();
@@ -962,18 +1413,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "done".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 3분 경과 + terminal hint → completed
@@ -993,18 +1434,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "assistant_message".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         let now = parse_time(last_seen) + time::Duration::seconds(180);
@@ -1023,18 +1454,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "assistant_message".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         let now = parse_time(last_seen) + time::Duration::seconds(960);
@@ -1053,18 +1474,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "msg".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 60초 경과 → idle
@@ -1084,18 +1495,8 @@ mod tests {
                 last_seen: last_seen.to_string(),
                 total: 3,
                 ok: 3,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "ping".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 5초 경과 → running
@@ -1117,16 +1518,8 @@ mod tests {
                 ok: 3,
                 warning: 1,
                 error: 1,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "err".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // 10분 경과해도 error면 blocked
@@ -1147,17 +1540,8 @@ mod tests {
                 total: 5,
                 ok: 3,
                 warning: 2,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "done".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         // warning이 있어도 terminal hint + 장기 무응답이면 completed
@@ -1194,12 +1578,7 @@ mod tests {
                 cost_usd: 0.5,
                 last_event: "test".to_string(),
                 latency_ms: Some(42),
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         state.by_agent.insert(
@@ -1209,18 +1588,10 @@ mod tests {
                 last_seen: now_iso(),
                 total: 5,
                 ok: 5,
-                warning: 0,
-                error: 0,
                 token_total: 50,
                 cost_usd: 0.1,
                 last_event: "ok".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         let snap = build_snapshot(&state);
@@ -1313,6 +1684,170 @@ mod tests {
         assert!((row.cost_usd - 0.05).abs() < 1e-9);
     }
 
+    #[test]
+    fn test_append_event_prices_token_usage_per_session() {
+        let app = make_test_app();
+        let mut evt = make_test_event_with_session(
+            "ok",
+            "token_usage",
+            "a1",
+            "sess-1",
+            json!({
+                "tokenUsage": {
+                    "inputTokens": 1_000_000,
+                    "outputTokens": 0,
+                    "totalTokens": 1_000_000
+                }
+            }),
+        );
+        evt.model = "claude-sonnet-4-5-20250929".to_string();
+        append_event(&app, evt);
+        let state = app.state.lock().unwrap();
+        assert!((state.by_session["sess-1"].cost_usd - 3.0).abs() < 1e-9);
+        assert!((state.by_agent["a1"].cost_usd - 3.0).abs() < 1e-9);
+        assert!((state.cost_total_usd - 3.0).abs() < 1e-9);
+        assert!((state.hourly_buckets[0].cost_usd - 3.0).abs() < 1e-9);
+        assert_eq!(state.recent[0].metadata["costUsd"], 3.0);
+    }
+
+    #[test]
+    fn test_append_event_keeps_explicit_token_cost() {
+        let app = make_test_app();
+        let mut evt = make_test_event(
+            "ok",
+            "token_usage",
+            "a1",
+            json!({ "costUsd": 0.25, "tokenUsage": { "inputTokens": 1_000_000, "totalTokens": 1_000_000 } }),
+        );
+        evt.model = "claude-opus-4-1".to_string();
+        append_event(&app, evt);
+        let state = app.state.lock().unwrap();
+        assert!((state.cost_total_usd - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_append_event_unpriced_model_has_no_cost() {
+        let app = make_test_app();
+        let mut evt = make_test_event(
+            "ok",
+            "token_usage",
+            "a1",
+            json!({ "tokenUsage": { "inputTokens": 500, "totalTokens": 500 } }),
+        );
+        evt.model = "<synthetic>".to_string();
+        append_event(&app, evt);
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.cost_total_usd, 0.0);
+        assert!(state.recent[0].metadata.get("costUsd").is_none());
+    }
+
+    #[test]
+    fn test_append_event_counts_stats_cache_cost_only_for_unpriced_models() {
+        let app = make_test_app();
+        append_event(
+            &app,
+            make_test_event(
+                "ok",
+                "cost_update",
+                "lead",
+                json!({
+                    "source": "stats_cache",
+                    "costDelta": 1.75,
+                    "modelCostDeltas": { "claude-sonnet-4-5": 1.5, "my-local-model": 0.25 }
+                }),
+            ),
+        );
+        let state = app.state.lock().unwrap();
+        assert!((state.cost_total_usd - 0.25).abs() < 1e-9);
+        assert!((state.by_agent["lead"].cost_usd - 0.25).abs() < 1e-9);
+        assert_eq!(state.by_source["stats_cache"].total, 1);
+    }
+
+    #[test]
+    fn test_append_event_aggregates_usage_breakdown() {
+        let app = make_test_app();
+        let meta = json!({
+            "tokenUsage": {
+                "inputTokens": 100,
+                "outputTokens": 50,
+                "cacheReadInputTokens": 300,
+                "cacheCreationInputTokens": 100,
+                "totalTokens": 150
+            }
+        });
+        append_event(
+            &app,
+            make_test_event_with_session("ok", "token_usage", "a1", "sess-1", meta.clone()),
+        );
+        append_event(
+            &app,
+            make_test_event_with_session("ok", "token_usage", "a1", "sess-1", meta),
+        );
+        let state = app.state.lock().unwrap();
+        let session = &state.by_session["sess-1"].usage;
+        assert_eq!(session.input_tokens, 200);
+        assert_eq!(session.cache_read_input_tokens, 600);
+        assert_eq!(session.cache_creation_input_tokens, 200);
+        assert!((session.cache_hit_ratio - 0.6).abs() < 1e-9);
+        assert_eq!(state.by_agent["a1"].usage.output_tokens, 100);
+        assert_eq!(state.usage_total.cache_read_input_tokens, 600);
+        assert_eq!(
+            state.hourly_buckets[0].usage.cache_creation_input_tokens,
+            200
+        );
+        // totalTokens keeps its input + output meaning
+        assert_eq!(state.token_total, 300);
+
+        let snap = build_snapshot(&state);
+        assert_eq!(snap.totals["usage"]["cacheReadInputTokens"], 600);
+        assert!((snap.totals["usage"]["cacheHitRatio"].as_f64().unwrap() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_append_event_counts_by_model_and_event_type() {
+        let app = make_test_app();
+        let mut evt = make_test_event(
+            "ok",
+            "token_usage",
+            "a1",
+            json!({ "source": "claude_session", "costUsd": 0.5, "tokenUsage": { "inputTokens": 40, "totalTokens": 40 } }),
+        );
+        evt.model = "claude-opus-4".to_string();
+        append_event(&app, evt);
+        append_event(
+            &app,
+            make_test_event(
+                "ok",
+                "token_usage",
+                "a1",
+                json!({ "source": "claude_session", "model": "claude-haiku-4", "tokenUsage": { "totalTokens": 0 } }),
+            ),
+        );
+        append_event(
+            &app,
+            make_test_event("error", "tool_result", "a1", json!({})),
+        );
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.by_model["claude-opus-4"].token_total, 40);
+        assert_eq!(state.by_model["claude-opus-4"].usage.input_tokens, 40);
+        assert!((state.by_model["claude-opus-4"].cost_usd - 0.5).abs() < 1e-9);
+        assert!(!state.by_model.contains_key("claude-haiku-4"));
+        assert_eq!(state.by_model["claude-opus-4"].requests, 1);
+        // The error event carries no model; it is charged to the agent's model.
+        assert_eq!(state.by_model["claude-opus-4"].errors, 1);
+        let key = |source: &str, event: &str, status: &str| {
+            (source.to_string(), event.to_string(), status.to_string())
+        };
+        assert_eq!(
+            state.event_counts[&key("claude_session", "token_usage", "ok")],
+            2
+        );
+        assert_eq!(
+            state.event_counts[&key("manual", "tool_result", "error")],
+            1
+        );
+    }
+
     #[test]
     fn test_append_event_source_tracking() {
         let app = make_test_app();
@@ -1363,6 +1898,20 @@ mod tests {
         assert_eq!(app.sse_clients.lock().unwrap().len(), 0);
     }
 
+    #[test]
+    fn test_append_event_keeps_last_known_latency() {
+        let app = make_test_app();
+        let mut evt = make_test_event("ok", "tool_result", "a1", json!({}));
+        evt.latency_ms = Some(120);
+        append_event(&app, evt);
+        append_event(
+            &app,
+            make_test_event("ok", "assistant_message", "a1", json!({})),
+        );
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.by_agent["a1"].latency_ms, Some(120));
+    }
+
     #[test]
     fn test_append_event_tracks_tool_use_counts() {
         let app = make_test_app();
@@ -1505,18 +2054,9 @@ mod tests {
                 last_seen: "2025-01-01T00:00:00Z".to_string(),
                 total: 1,
                 ok: 1,
-                warning: 0,
-                error: 0,
-                token_total: 0,
-                cost_usd: 0.0,
                 last_event: "ping".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
-                session_id: String::new(),
-                tool_use_counts: std::collections::HashMap::new(),
                 display_name: "Fix login bug".to_string(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         let row = workflow_row(&state, "agent-1");
@@ -1530,7 +2070,7 @@ mod tests {
         let result = elapsed_secs_from(&iso, now);
         assert!(result.is_some());
         let secs = result.unwrap();
-        assert!(secs >= 0 && secs < 2);
+        assert!((0..2).contains(&secs));
     }
 
     #[test]
@@ -1563,6 +2103,7 @@ mod tests {
             is_sidechain: false,
             session_id: session_id.to_string(),
             cwd: String::new(),
+            dedup_key: None,
         }
     }
 
@@ -1788,18 +2329,8 @@ mod tests {
                     last_seen: last_seen.to_string(),
                     total: 1,
                     ok: 1,
-                    warning: 0,
-                    error: 0,
-                    token_total: 0,
-                    cost_usd: 0.0,
                     last_event: "ping".to_string(),
-                    latency_ms: None,
-                    model: String::new(),
-                    is_sidechain: false,
-                    session_id: String::new(),
-                    tool_use_counts: std::collections::HashMap::new(),
-                    display_name: String::new(),
-                    display_name_from_user: false,
+                    ..AgentRow::default()
                 },
             );
         }
@@ -1860,9 +2391,91 @@ mod tests {
             is_sidechain,
             session_id: String::new(),
             cwd: cwd.to_string(),
+            dedup_key: None,
         }
     }
 
+    #[test]
+    fn test_append_event_aggregates_by_project() {
+        let app = make_test_app();
+        let mut first = make_event_with_cwd("tool_call", "a1", "Bash", "/home/user/web", false);
+        first.session_id = "s1".to_string();
+        first.metadata = json!({ "tokenUsage": { "totalTokens": 100 }, "costUsd": 0.5 });
+        append_event(&app, first);
+        let mut second = make_event_with_cwd(
+            "tool_call",
+            "a2",
+            "Bash",
+            "/home/user/web/.claude/worktrees/x/feature",
+            true,
+        );
+        second.session_id = "s1".to_string();
+        second.status = "error".to_string();
+        append_event(&app, second);
+        append_event(
+            &app,
+            make_event_with_cwd("assistant_message", "a3", "hi", "/home/user/api", false),
+        );
+
+        let state = app.state.lock().unwrap();
+        let web = &state.by_project["web"];
+        assert_eq!(web.total, 2);
+        assert_eq!(web.error, 1);
+        assert_eq!(web.token_total, 100);
+        assert_eq!(web.session_count, 1);
+        assert_eq!(web.agent_count, 2);
+        assert_eq!(web.tool_use_counts["Bash"], 2);
+        assert_eq!(state.by_project["api"].total, 1);
+        assert_eq!(state.by_project["api"].agent_count, 1);
+    }
+
+    #[test]
+    fn test_session_joining_project_late_counts_its_agents() {
+        let app = make_test_app();
+        let mut first = make_event_with_cwd("assistant_message", "a1", "", "", false);
+        first.session_id = "s1".to_string();
+        append_event(&app, first);
+        for agent_id in ["a1", "a2", "a2"] {
+            let mut evt = make_event_with_cwd("tool_call", agent_id, "Bash", "/w/web", false);
+            evt.session_id = "s1".to_string();
+            append_event(&app, evt);
+        }
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.by_project["web"].session_count, 1);
+        assert_eq!(state.by_project["web"].agent_count, 2);
+    }
+
+    #[test]
+    fn test_append_event_counts_models_per_session_and_project() {
+        let app = make_test_app();
+        for (model, tokens) in [
+            ("claude-opus-4", 100),
+            ("claude-sonnet-4", 30),
+            ("claude-sonnet-4", 20),
+        ] {
+            let mut evt = make_event_with_cwd("token_usage", "a1", "", "/home/user/web", false);
+            evt.session_id = "s1".to_string();
+            evt.model = model.to_string();
+            evt.metadata = json!({
+                "costUsd": 0.1,
+                "tokenUsage": { "inputTokens": tokens, "totalTokens": tokens }
+            });
+            append_event(&app, evt);
+        }
+
+        let state = app.state.lock().unwrap();
+        let session = &state.by_session["s1"].by_model;
+        assert_eq!(session["claude-sonnet-4"].requests, 2);
+        assert_eq!(session["claude-sonnet-4"].token_total, 50);
+        assert_eq!(session["claude-sonnet-4"].usage.input_tokens, 50);
+        let project = &state.by_project["web"].by_model;
+        assert_eq!(project["claude-opus-4"].requests, 1);
+        assert_eq!(
+            state.model_hourly["claude-sonnet-4"][0].usage.input_tokens,
+            50
+        );
+    }
+
     #[test]
     fn test_display_name_cwd_fallback() {
         let app = make_test_app();
@@ -2049,6 +2662,7 @@ mod tests {
             is_sidechain: false,
             session_id: String::new(),
             cwd: String::new(),
+            dedup_key: None,
         }
     }
 
@@ -2136,8 +2750,10 @@ mod tests {
 
     #[test]
     fn test_snapshot_includes_started_at() {
-        let mut state = State::default();
-        state.started_at = "2025-01-01T00:00:00Z".to_string();
+        let state = State {
+            started_at: "2025-01-01T00:00:00Z".to_string(),
+            ..State::default()
+        };
         let snap = build_snapshot(&state);
         assert_eq!(snap.started_at, "2025-01-01T00:00:00Z");
     }
@@ -2149,6 +2765,7 @@ mod tests {
             hour_key: "2025-01-01T14".to_string(),
             token_total: 500,
             cost_usd: 0.10,
+            usage: TokenBreakdown::default(),
         });
         let snap = build_snapshot(&state);
         assert_eq!(snap.hourly_buckets.len(), 1);
@@ -2168,19 +2785,14 @@ mod tests {
             total: 3,
             ok: 2,
             warning: 1,
-            error: 0,
             token_total: 25_000,
             cost_usd: 0.8,
             last_event: "heartbeat".to_string(),
-            latency_ms: None,
-            model: String::new(),
-            is_sidechain: false,
             session_id: "sess-1".to_string(),
-            tool_use_counts: std::collections::HashMap::new(),
-            display_name: String::new(),
-            display_name_from_user: false,
+            ..AgentRow::default()
         };
         let risk = session_risk_for_export(
+            &State::default(),
             &summary,
             &[&agent],
             parse_time("2025-01-01T00:12:30Z"),
@@ -2211,19 +2823,14 @@ mod tests {
             total: 3,
             ok: 2,
             warning: 1,
-            error: 0,
             token_total: 25_000,
             cost_usd: 0.8,
             last_event: "heartbeat".to_string(),
-            latency_ms: None,
-            model: String::new(),
-            is_sidechain: false,
             session_id: "sess-1".to_string(),
-            tool_use_counts: std::collections::HashMap::new(),
-            display_name: String::new(),
-            display_name_from_user: false,
+            ..AgentRow::default()
         };
         let risk = session_risk_for_export(
+            &State::default(),
             &summary,
             &[&agent],
             parse_time("2025-01-01T00:12:30Z"),
@@ -2250,17 +2857,11 @@ mod tests {
                 total: 3,
                 ok: 2,
                 warning: 1,
-                error: 0,
                 token_total: 25_000,
                 cost_usd: 0.8,
                 last_event: "heartbeat".to_string(),
-                latency_ms: None,
-                model: String::new(),
-                is_sidechain: false,
                 session_id: "sess-1".to_string(),
-                tool_use_counts: std::collections::HashMap::new(),
-                display_name: String::new(),
-                display_name_from_user: false,
+                ..AgentRow::default()
             },
         );
         state.alerts.push(AlertRow {
@@ -2271,6 +2872,7 @@ mod tests {
             event: "tool_warning".to_string(),
             message: "warn".to_string(),
             created_at: "2025-01-01T00:10:01Z".to_string(),
+            ..AlertRow::default()
         });
         state.alerts.push(AlertRow {
             id: "alert-2".to_string(),
@@ -2280,6 +2882,7 @@ mod tests {
             event: "tool_warning".to_string(),
             message: "wrong session".to_string(),
             created_at: "2025-01-01T00:10:02Z".to_string(),
+            ..AlertRow::default()
         });
 
         let mut summary = make_test_session_row("sess-1", "2025-01-01T00:10:00Z");
@@ -2288,6 +2891,7 @@ mod tests {
         summary.agent_ids = vec!["a1".to_string()];
         let agents = session_agent_rows(&state, &summary);
         let risk = session_risk_for_export(
+            &state,
             &summary,
             &agents,
             parse_time("2025-01-01T00:12:30Z"),
@@ -2306,30 +2910,173 @@ mod tests {
             .any(|alert| alert.derived_reason.as_deref() == Some("cost_spike")));
     }
 
-    #[test]
-    fn test_append_event_persists_bucket_to_db() {
-        use crate::db::Db;
-        let dir = tempfile::tempdir().unwrap();
-        let db = Db::open(&dir.path().join("test.db")).unwrap();
-        let db_arc = Arc::new(Mutex::new(db));
+    fn make_test_app_with_db(dir: &tempfile::TempDir) -> (App, Db) {
+        let path = dir.path().join("test.db");
+        let reader = Db::open(&path).unwrap();
+        let (writer, _handle) = DbWriter::spawn(Db::open(&path).unwrap(), 60_000, 1_000);
         let app = App {
             state: Arc::new(Mutex::new(State::default())),
             sse_clients: Arc::new(Mutex::new(Vec::new())),
             event_seq: Arc::new(std::sync::atomic::AtomicU64::new(1)),
             public_dir: Arc::new(PathBuf::from("public")),
-            db: Some(db_arc.clone()),
+            db_writer: Some(writer),
+            notifier: None,
+            db_reader: None,
+            config: Arc::new(MonitorConfig::default()),
         };
+        (app, reader)
+    }
+
+    #[test]
+    fn test_append_event_persists_bucket_to_db() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
         let meta = json!({ "tokenUsage": { "totalTokens": 100 } });
         let evt = make_event_with_received_at("msg", "2025-01-01T14:00:00Z", meta);
         append_event(&app, evt);
+        assert!(app.db_writer.as_ref().unwrap().flush());
 
-        let db = db_arc.lock().unwrap();
         let rows = db.query_since("").unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].hour_key, "2025-01-01T14");
         assert_eq!(rows[0].token_total, 100);
     }
 
+    #[test]
+    fn test_project_rows_are_persisted_on_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
+        for id in ["e1", "e2"] {
+            let mut evt = make_event_with_cwd("tool_call", "a1", "Bash", "/x/proj", false);
+            evt.session_id = "sess-1".to_string();
+            evt.id = id.to_string();
+            append_event(&app, evt);
+        }
+        assert!(app.db_writer.as_ref().unwrap().flush());
+        assert!(db.load_projects().unwrap().is_empty());
+
+        flush_dirty_projects(&app);
+        assert!(app.db_writer.as_ref().unwrap().flush());
+        let projects = db.load_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].total, 2);
+        assert_eq!(projects[0].session_count, 1);
+        assert!(app.state.lock().unwrap().dirty_projects.is_empty());
+    }
+
+    #[test]
+    fn test_restore_from_db_rebuilds_global_model_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
+        let meta = json!({ "tokenUsage": { "totalTokens": 100 }, "costUsd": 0.5 });
+        let mut usage = make_event_with_received_at("token_usage", "2025-01-01T14:00:00Z", meta);
+        usage.model = "claude-opus-4".to_string();
+        append_event(&app, usage);
+        let mut failure =
+            make_event_with_received_at("api_error", "2025-01-01T15:00:00Z", json!({}));
+        failure.model = "claude-opus-4".to_string();
+        failure.status = "error".to_string();
+        append_event(&app, failure);
+        assert!(app.db_writer.as_ref().unwrap().flush());
+
+        let mut restored = State::default();
+        restore_from_db(&mut restored, &db).unwrap();
+        let opus = &restored.by_model["claude-opus-4"];
+        assert_eq!(opus.requests, 1);
+        assert_eq!(opus.errors, 1);
+        assert_eq!(opus.token_total, 100);
+        assert!((opus.cost_usd - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_restore_from_db_rebuilds_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
+        let mut first = make_event_with_cwd("user_message", "a1", "Fix login", "/x/proj", false);
+        first.session_id = "sess-1".to_string();
+        first.id = "e1".to_string();
+        append_event(&app, first);
+        let mut tool = make_event_with_cwd("tool_call", "a1", "Bash", "/x/proj", false);
+        tool.session_id = "sess-1".to_string();
+        tool.id = "e2".to_string();
+        append_event(&app, tool);
+        let mut failure = make_event_with_cwd("tool_result", "a1", "Bash", "/x/proj", false);
+        failure.session_id = "sess-1".to_string();
+        failure.id = "e3".to_string();
+        failure.status = "error".to_string();
+        append_event(&app, failure);
+        assert!(app.db_writer.as_ref().unwrap().flush());
+
+        let mut restored = State::default();
+        restore_from_db(&mut restored, &db).unwrap();
+        assert_eq!(restored.recent.len(), 3);
+        assert_eq!(restored.recent[0].id, "e3");
+        let session = &restored.by_session["sess-1"];
+        assert_eq!(session.display_name, "Fix login");
+        assert!(session.display_name_locked);
+        assert_eq!(session.project_name, "proj");
+        assert_eq!(restored.events_by_session["sess-1"].len(), 3);
+        assert_eq!(restored.events_by_session["sess-1"][0].cwd, "/x/proj");
+        let agent = &restored.by_agent["a1"];
+        assert_eq!(agent.total, 3);
+        assert_eq!(agent.error, 1);
+        assert!(agent.display_name_from_user);
+        assert_eq!(restored.tool_use_counts["Bash"], 1);
+        assert_eq!(restored.alerts.len(), 1);
+        assert_eq!(restored.alerts[0].severity, "error");
+    }
+
+    #[test]
+    fn test_restore_from_db_counts_tools_of_every_stored_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
+        for i in 0..502 {
+            let agent_id = format!("a{}", i);
+            append_event(
+                &app,
+                make_event_with_cwd("tool_call", &agent_id, "Bash", "/x/proj", false),
+            );
+        }
+        assert!(app.db_writer.as_ref().unwrap().flush());
+
+        let mut restored = State::default();
+        restore_from_db(&mut restored, &db).unwrap();
+        assert_eq!(restored.by_agent.len(), 500);
+        assert_eq!(restored.tool_use_counts["Bash"], 502);
+    }
+
+    #[test]
+    fn test_append_event_skips_duplicate_dedup_key() {
+        let app = make_test_app();
+        let meta = json!({ "tokenUsage": { "totalTokens": 100 } });
+        let mut evt = make_event_with_received_at("token_usage", "2025-01-01T14:00:00Z", meta);
+        evt.dedup_key = Some("u-1#usage".to_string());
+        append_event(&app, evt.clone());
+        append_event(&app, evt);
+        let state = app.state.lock().unwrap();
+        assert_eq!(state.recent.len(), 1);
+        assert_eq!(state.token_total, 100);
+        assert_eq!(state.duplicates_skipped, 1);
+        let snapshot = build_snapshot(&state);
+        assert_eq!(snapshot.totals["duplicatesSkipped"], 1);
+    }
+
+    #[test]
+    fn test_seen_event_keys_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, db) = make_test_app_with_db(&dir);
+        let meta = json!({ "tokenUsage": { "totalTokens": 100 } });
+        let mut evt = make_event_with_received_at("token_usage", "2025-01-01T14:00:00Z", meta);
+        evt.dedup_key = Some("u-1#usage".to_string());
+        append_event(&app, evt.clone());
+        assert!(app.db_writer.as_ref().unwrap().flush());
+
+        let mut restored = State::default();
+        restore_from_db(&mut restored, &db).unwrap();
+        assert!(!restored.seen_event_keys.insert("u-1#usage"));
+        assert!(restored.seen_event_keys.insert("u-2#usage"));
+    }
+
     // ── session display name tests ──
 
     #[test]
@@ -2435,4 +3182,144 @@ mod tests {
         let session = state.by_session.get("sess-fb").unwrap();
         assert_eq!(session.display_name, "billing-api");
     }
+
+    #[test]
+    fn test_append_event_stores_session_state_and_broadcasts_change() {
+        let app = make_test_app();
+        let (tx, rx) = mpsc::channel::<String>();
+        app.sse_clients.lock().unwrap().push(tx);
+        append_event(
+            &app,
+            make_test_event_with_session("error", "tool_call", "a1", "s1", json!({})),
+        );
+
+        let state = app.state.lock().unwrap();
+        let risk = &state.by_session.get("s1").unwrap().risk;
+        assert_eq!(risk.session_state, "failed");
+        assert_eq!(risk.needs_attention_reasons, vec!["failed"]);
+        drop(state);
+
+        let messages: Vec<serde_json::Value> = rx
+            .try_iter()
+            .filter_map(|m| serde_json::from_str(m.trim_start_matches("data: ").trim()).ok())
+            .collect();
+        let change = messages
+            .iter()
+            .find(|m| m["type"] == "session_state_changed")
+            .expect("session_state_changed broadcast");
+        assert_eq!(change["payload"]["sessionId"], "s1");
+        assert_eq!(change["payload"]["previousState"], serde_json::Value::Null);
+        assert_eq!(change["payload"]["sessionState"], "failed");
+    }
+
+    #[test]
+    fn test_refresh_session_states_reports_time_based_transitions() {
+        let mut state = State::default();
+        let mut session = make_test_session_row("s1", "2025-01-01T00:00:00Z");
+        session.agent_ids = vec!["a1".to_string()];
+        state.by_session.insert("s1".to_string(), session);
+        state.by_agent.insert(
+            "a1".to_string(),
+            AgentRow {
+                agent_id: "a1".to_string(),
+                session_id: "s1".to_string(),
+                last_seen: "2025-01-01T00:00:00Z".to_string(),
+                total: 1,
+                ok: 1,
+                last_event: "tool_call".to_string(),
+                ..AgentRow::default()
+            },
+        );
+
+        let changes = refresh_session_states(&mut state, parse_time("2025-01-01T00:00:10Z"));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].risk.session_state, "active");
+        assert!(refresh_session_states(&mut state, parse_time("2025-01-01T00:00:20Z")).is_empty());
+
+        let changes = refresh_session_states(&mut state, parse_time("2025-01-01T00:03:00Z"));
+        assert_eq!(changes[0].previous_state.as_deref(), Some("active"));
+        assert_eq!(changes[0].risk.session_state, "stuck");
+        assert!(state.by_session["s1"].risk.needs_attention);
+
+        let notice = session_state_alert(&state, &changes[0]).unwrap();
+        assert_eq!(notice.fingerprint, "derived:stuck:s1");
+        assert_eq!(notice.severity, "warning");
+        assert_eq!(notice.status, STATUS_OPEN);
+        assert_eq!(notice.created_at, "2025-01-01T00:03:00Z");
+
+        let changes = refresh_session_states(&mut state, parse_time("2025-01-01T01:00:00Z"));
+        assert_eq!(changes[0].risk.session_state, "completed");
+        assert!(session_state_alert(&state, &changes[0]).is_none());
+    }
+
+    fn state_with_quiet_session(config: serde_json::Value) -> State {
+        let mut state = State {
+            session_state_config: serde_json::from_value(config).unwrap(),
+            ..State::default()
+        };
+        let mut session = make_test_session_row("s1", "2025-01-01T00:00:00Z");
+        session.project_name = "refactor".to_string();
+        session.agent_ids = vec!["a1".to_string()];
+        state.by_session.insert("s1".to_string(), session);
+        state.by_agent.insert(
+            "a1".to_string(),
+            AgentRow {
+                agent_id: "a1".to_string(),
+                last_seen: "2025-01-01T00:00:00Z".to_string(),
+                total: 2,
+                ok: 2,
+                last_event: "tool_call".to_string(),
+                session_id: "s1".to_string(),
+                ..AgentRow::default()
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn test_session_state_uses_project_window_override() {
+        let state = state_with_quiet_session(json!({
+            "projects": { "refactor": { "stuckWindowSecs": 600 } }
+        }));
+        let session = &state.by_session["s1"];
+        assert_eq!(
+            session_state_at(&state, session, parse_time("2025-01-01T00:05:00Z")),
+            "idle"
+        );
+        assert_eq!(
+            session_state_at(&state, session, parse_time("2025-01-01T00:10:00Z")),
+            "stuck"
+        );
+        assert_eq!(
+            workflow_row_at(&state, "a1", parse_time("2025-01-01T00:05:00Z")).status,
+            "idle"
+        );
+    }
+
+    #[test]
+    fn test_session_state_busy_while_tool_call_in_flight() {
+        let mut state = state_with_quiet_session(json!({ "busyWhileToolRunning": true }));
+        state.pending_tool_calls.insert(
+            "toolu_1".to_string(),
+            crate::types::PendingToolCall {
+                tool_name: "Bash".to_string(),
+                agent_id: "a1".to_string(),
+                session_id: "s1".to_string(),
+                started_at: "2025-01-01T00:00:00Z".to_string(),
+                subagent_type: String::new(),
+            },
+        );
+        let now = parse_time("2025-01-01T00:05:00Z");
+        assert_eq!(
+            session_state_at(&state, &state.by_session["s1"], now),
+            "busy"
+        );
+        assert_eq!(workflow_row_at(&state, "a1", now).status, "running");
+
+        state.pending_tool_calls.clear();
+        assert_eq!(
+            session_state_at(&state, &state.by_session["s1"], now),
+            "stuck"
+        );
+    }
 }
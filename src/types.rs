@@ -1,12 +1,17 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 use std::sync::atomic::AtomicU64;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 
+use crate::config::MonitorConfig;
 use crate::db::Db;
+use crate::db_writer::DbWriter;
+use crate::notify::Notifier;
+use crate::rules::RuleSample;
+use crate::session_state::SessionStateConfig;
 
 #[derive(Clone)]
 pub struct App {
@@ -14,10 +19,14 @@ pub struct App {
     pub sse_clients: Arc<Mutex<Vec<Sender<String>>>>,
     pub event_seq: Arc<AtomicU64>,
     pub public_dir: Arc<PathBuf>,
-    pub db: Option<Arc<Mutex<Db>>>,
+    pub db_writer: Option<DbWriter>,
+    pub notifier: Option<Notifier>,
+    /// Read connection for queries that reach past the in-memory window.
+    pub db_reader: Option<Arc<Mutex<Db>>>,
+    pub config: Arc<MonitorConfig>,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Event {
     pub id: String,
@@ -34,9 +43,79 @@ pub struct Event {
     pub session_id: String,
     #[serde(skip)]
     pub cwd: String,
+    /// Stable identity of the source record (`<uuid>#<item>` for session lines),
+    /// used to drop events that are read twice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_key: Option<String>,
 }
 
-#[derive(Clone, Serialize)]
+/// Token usage split by kind, as reported in `message.usage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TokenBreakdown {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub web_search_requests: u64,
+    pub web_fetch_requests: u64,
+    /// cache reads / all prompt tokens (input + cache read + cache creation)
+    pub cache_hit_ratio: f64,
+}
+
+impl TokenBreakdown {
+    /// Reads the `metadata.tokenUsage` object produced by the collector.
+    pub fn from_token_usage(value: Option<&Value>) -> Self {
+        let count = |key: &str| {
+            value
+                .and_then(|v| v.get(key))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+        };
+        let mut usage = Self {
+            input_tokens: count("inputTokens"),
+            output_tokens: count("outputTokens"),
+            cache_read_input_tokens: count("cacheReadInputTokens"),
+            cache_creation_input_tokens: count("cacheCreationInputTokens"),
+            web_search_requests: count("webSearchRequests"),
+            web_fetch_requests: count("webFetchRequests"),
+            cache_hit_ratio: 0.0,
+        };
+        usage.update_ratio();
+        usage
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input_tokens == 0
+            && self.output_tokens == 0
+            && self.cache_read_input_tokens == 0
+            && self.cache_creation_input_tokens == 0
+            && self.web_search_requests == 0
+            && self.web_fetch_requests == 0
+    }
+
+    pub fn add(&mut self, other: &TokenBreakdown) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.web_search_requests += other.web_search_requests;
+        self.web_fetch_requests += other.web_fetch_requests;
+        self.update_ratio();
+    }
+
+    fn update_ratio(&mut self) {
+        let prompt =
+            self.input_tokens + self.cache_read_input_tokens + self.cache_creation_input_tokens;
+        self.cache_hit_ratio = if prompt == 0 {
+            0.0
+        } else {
+            self.cache_read_input_tokens as f64 / prompt as f64
+        };
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentRow {
     pub agent_id: String,
@@ -47,6 +126,7 @@ pub struct AgentRow {
     pub error: u64,
     pub token_total: u64,
     pub cost_usd: f64,
+    pub usage: TokenBreakdown,
     pub last_event: String,
     pub latency_ms: Option<i64>,
     pub model: String,
@@ -56,9 +136,14 @@ pub struct AgentRow {
     pub display_name: String,
     #[serde(skip)]
     pub display_name_from_user: bool,
+    /// The Task tool's `subagent_type` of a subagent, once known.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub subagent_type: String,
 }
 
-#[derive(Clone, Serialize)]
+/// One alert per `fingerprint`; `created_at` is when it first fired and
+/// `last_seen` when it last repeated.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlertRow {
     pub id: String,
@@ -68,6 +153,20 @@ pub struct AlertRow {
     pub event: String,
     pub message: String,
     pub created_at: String,
+    #[serde(default)]
+    pub fingerprint: String,
+    #[serde(default)]
+    pub count: u64,
+    #[serde(default)]
+    pub last_seen: String,
+    /// `open`, `acked`, `snoozed` or `resolved`.
+    #[serde(default)]
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<String>,
+    /// Set on alerts raised by a configured rule rather than by an event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
 }
 
 #[derive(Clone, Serialize)]
@@ -93,23 +192,33 @@ pub struct WorkflowRow {
     pub display_name: String,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionRow {
     pub session_id: String,
     pub last_seen: String,
     pub token_total: u64,
     pub cost_usd: f64,
+    pub usage: TokenBreakdown,
     pub agent_ids: Vec<String>,
     pub display_name: String,
     pub project_name: String,
     pub short_session_id: String,
     #[serde(skip)]
     pub display_name_locked: bool,
+    #[serde(default)]
+    pub by_model: HashMap<String, ModelUsage>,
+    /// State and risk as of the last event or state tick.
+    #[serde(flatten)]
+    pub risk: SessionExportRisk,
+    /// Source of the first event that carried usage; only that feed's
+    /// tokens and cost count toward totals (OTel vs. the local feeds).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_source: Option<String>,
 }
 
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct SessionExportRisk {
     pub session_state: String,
     pub needs_attention: bool,
@@ -118,6 +227,18 @@ pub struct SessionExportRisk {
     pub is_cost_spike: bool,
 }
 
+/// Payload of the `session_state_changed` SSE message.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStateChange {
+    pub session_id: String,
+    /// `None` for a session seen for the first time.
+    pub previous_state: Option<String>,
+    #[serde(flatten)]
+    pub risk: SessionExportRisk,
+    pub changed_at: String,
+}
+
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionExportAlert {
@@ -154,6 +275,131 @@ pub struct HourBucket {
     pub hour_key: String,
     pub token_total: u64,
     pub cost_usd: f64,
+    pub usage: TokenBreakdown,
+}
+
+/// Activity of one project (see `extract_project_name`) since the monitor started.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectRow {
+    pub project_name: String,
+    pub last_seen: String,
+    pub total: u64,
+    pub ok: u64,
+    pub warning: u64,
+    pub error: u64,
+    pub token_total: u64,
+    pub cost_usd: f64,
+    pub usage: TokenBreakdown,
+    /// Sessions and agents first seen under this project.
+    #[serde(default)]
+    pub session_count: u64,
+    #[serde(default)]
+    pub agent_count: u64,
+    pub tool_use_counts: HashMap<String, u64>,
+    #[serde(default)]
+    pub by_model: HashMap<String, ModelUsage>,
+}
+
+/// Per-model totals: globally over the retained history, or within one
+/// session or project.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelUsage {
+    /// Events that reported token usage or cost.
+    pub requests: u64,
+    pub errors: u64,
+    pub token_total: u64,
+    pub cost_usd: f64,
+    pub usage: TokenBreakdown,
+}
+
+impl ModelUsage {
+    pub fn add_event(&mut self, tokens: u64, cost: f64, usage: &TokenBreakdown, is_error: bool) {
+        if tokens > 0 || cost > 0.0 || !usage.is_empty() {
+            self.requests += 1;
+        }
+        if is_error {
+            self.errors += 1;
+        }
+        self.token_total += tokens;
+        self.cost_usd += cost;
+        self.usage.add(usage);
+    }
+}
+
+/// Read position in an append-only JSONL file. `inode` and `size` identify
+/// the file the offset belongs to, so a replaced or truncated file is re-read
+/// from the start instead of being resumed at a stale offset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileCursor {
+    pub offset: u64,
+    pub remainder: String,
+    pub inode: u64,
+    pub size: u64,
+    /// Set once a write read with this cursor was dropped: the cursor is no
+    /// longer persisted this run, so a restart re-reads the lost lines.
+    pub held_back: bool,
+}
+
+/// Last value of one cumulative OTLP sum series.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OtelSeries {
+    pub session_id: String,
+    pub value: f64,
+}
+
+#[derive(Clone)]
+pub struct PendingToolCall {
+    pub tool_name: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub started_at: String,
+    /// For a Task call, the `subagent_type` the subagent runs as.
+    pub subagent_type: String,
+}
+
+/// Cost of the collector's last full scan of `projects/`, reported on `/api/health`.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanStats {
+    pub scanned_at: String,
+    pub scan_ms: u64,
+    pub dirs_listed: u64,
+    pub dirs_skipped: u64,
+    pub files_checked: u64,
+    pub files_changed: u64,
+    pub dormant_skipped: u64,
+}
+
+const MAX_SEEN_EVENT_KEYS: usize = 50_000;
+
+/// Bounded set of recently seen `Event::dedup_key`s; the oldest key is forgotten first.
+#[derive(Default)]
+pub struct SeenKeys {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenKeys {
+    /// Returns `false` when the key was already present.
+    pub fn insert(&mut self, key: &str) -> bool {
+        if self.keys.contains(key) {
+            return false;
+        }
+        self.keys.insert(key.to_string());
+        self.order.push_back(key.to_string());
+        if self.order.len() > MAX_SEEN_EVENT_KEYS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn capacity() -> usize {
+        MAX_SEEN_EVENT_KEYS
+    }
 }
 
 #[derive(Default)]
@@ -166,12 +412,42 @@ pub struct State {
     pub events_by_session: HashMap<String, Vec<Event>>,
     pub token_total: u64,
     pub cost_total_usd: f64,
+    pub usage_total: TokenBreakdown,
     pub tool_use_counts: HashMap<String, u64>,
     pub started_at: String,
     pub hourly_buckets: Vec<HourBucket>,
+    pub pending_tool_calls: HashMap<String, PendingToolCall>,
+    /// Subagent types named by a Task result before the subagent's own
+    /// events arrived, keyed by agent id.
+    pub pending_subagent_types: HashMap<String, String>,
+    pub seen_event_keys: SeenKeys,
+    pub duplicates_skipped: u64,
+    pub collector_scan: ScanStats,
+    /// Last value of each cumulative OTLP sum series, to turn it into deltas.
+    /// Series of sessions that left memory are looked up in the database.
+    pub otel_cumulative: HashMap<String, OtelSeries>,
+    /// Events counted by `(source, event, status)`.
+    pub event_counts: HashMap<(String, String, String), u64>,
+    pub by_model: HashMap<String, ModelUsage>,
+    pub by_project: HashMap<String, ProjectRow>,
+    /// Projects changed since their row was last written to the database.
+    pub dirty_projects: HashSet<String>,
+    /// Hourly tokens and cost per project name and per model (last 31 days).
+    pub project_hourly: HashMap<String, Vec<HourBucket>>,
+    pub model_hourly: HashMap<String, Vec<HourBucket>>,
+    /// Recent per-event figures for windowed alert rules.
+    pub rule_samples: VecDeque<RuleSample>,
+    /// Fingerprints of rule alerts that breached at the last evaluation.
+    pub rules_firing: HashSet<String>,
+    /// Fingerprints of budget alerts already raised in their current period,
+    /// independent of how long the alerts themselves are kept.
+    pub budgets_fired: HashSet<String>,
+    /// The `sessionState` section of `monitor.json`, kept with the state it
+    /// classifies.
+    pub session_state_config: SessionStateConfig,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolCallStat {
     pub name: String,
@@ -198,4 +474,63 @@ pub struct ParsedRequest {
     pub method: String,
     pub path: String,
     pub query: HashMap<String, String>,
+    /// Header names are lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Session and agent rows for tests; override fields with `..session(..)`.
+#[cfg(test)]
+pub mod test_rows {
+    use super::{AgentRow, SessionRow};
+
+    /// A session of `project` with the single agent `agent-<id>`.
+    pub fn session(id: &str, project: &str, last_seen: &str, cost_usd: f64) -> SessionRow {
+        SessionRow {
+            session_id: id.to_string(),
+            last_seen: last_seen.to_string(),
+            cost_usd,
+            agent_ids: vec![format!("agent-{}", id)],
+            project_name: project.to_string(),
+            short_session_id: id.to_string(),
+            ..SessionRow::default()
+        }
+    }
+
+    /// A lead agent of `session_id` whose one event was a tool call.
+    pub fn agent(id: &str, session_id: &str, last_seen: &str) -> AgentRow {
+        AgentRow {
+            agent_id: id.to_string(),
+            last_seen: last_seen.to_string(),
+            total: 1,
+            ok: 1,
+            last_event: "tool_call".to_string(),
+            session_id: session_id.to_string(),
+            ..AgentRow::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_keys_rejects_repeats() {
+        let mut seen = SeenKeys::default();
+        assert!(seen.insert("a#0"));
+        assert!(!seen.insert("a#0"));
+        assert!(seen.insert("a#1"));
+    }
+
+    #[test]
+    fn test_seen_keys_forgets_oldest_beyond_capacity() {
+        let mut seen = SeenKeys::default();
+        for i in 0..=SeenKeys::capacity() {
+            seen.insert(&format!("k{}", i));
+        }
+        assert_eq!(seen.order.len(), SeenKeys::capacity());
+        assert!(seen.insert("k0"));
+        assert!(!seen.insert(&format!("k{}", SeenKeys::capacity())));
+    }
 }
@@ -0,0 +1,275 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Paths reported by one [`FsWatcher::wait`] call. `overflowed` means the
+/// kernel dropped notifications, so the caller should fall back to a full scan.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub paths: Vec<PathBuf>,
+    pub overflowed: bool,
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::FsWatcher;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::collections::HashMap;
+    use std::ffi::{CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    const WATCH_MASK: u32 = libc::IN_CREATE
+        | libc::IN_MODIFY
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_MOVED_TO
+        | libc::IN_DELETE_SELF;
+
+    /// inotify watch over a set of directories. Recursive watches follow
+    /// directories created after the watch was added.
+    pub struct FsWatcher {
+        fd: libc::c_int,
+        dirs: HashMap<libc::c_int, (PathBuf, bool)>,
+    }
+
+    impl FsWatcher {
+        pub fn new() -> io::Result<Self> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                fd,
+                dirs: HashMap::new(),
+            })
+        }
+
+        pub fn is_watching(&self, dir: &Path) -> bool {
+            self.dirs.values().any(|(path, _)| path == dir)
+        }
+
+        /// Watches `dir`, and with `recursive` every directory below it.
+        pub fn watch(&mut self, dir: &Path, recursive: bool) -> io::Result<()> {
+            let c_path = CString::new(dir.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let wd = unsafe { libc::inotify_add_watch(self.fd, c_path.as_ptr(), WATCH_MASK) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.dirs.insert(wd, (dir.to_path_buf(), recursive));
+            if recursive {
+                for entry in std::fs::read_dir(dir)?.flatten() {
+                    let path = entry.path();
+                    if path.is_dir() && !path.is_symlink() {
+                        // a subdirectory vanishing mid-walk is not fatal for the rest of the tree
+                        let _ = self.watch(&path, true);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Waits up to `timeout` for changes and returns the touched file paths.
+        /// Files inside newly created directories are reported as well, since
+        /// they may have been written before the new watch was in place.
+        pub fn wait(&mut self, timeout: Duration) -> io::Result<Changes> {
+            let mut changes = Changes::default();
+            let mut pfd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+            let ready = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    return Ok(changes);
+                }
+                return Err(err);
+            }
+            if ready == 0 {
+                return Ok(changes);
+            }
+
+            let mut buf = [0u8; 64 * 1024];
+            loop {
+                let n = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+                if n < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    }
+                    return Err(err);
+                }
+                if n == 0 {
+                    break;
+                }
+                self.parse_events(&buf[..n as usize], &mut changes);
+            }
+            changes.paths.sort();
+            changes.paths.dedup();
+            Ok(changes)
+        }
+
+        fn parse_events(&mut self, buf: &[u8], changes: &mut Changes) {
+            let header = std::mem::size_of::<libc::inotify_event>();
+            let mut offset = 0;
+            while offset + header <= buf.len() {
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset).cast()) };
+                let name_end = (offset + header + event.len as usize).min(buf.len());
+                let name = buf[offset + header..name_end]
+                    .split(|b| *b == 0)
+                    .next()
+                    .unwrap_or(&[]);
+                offset = name_end;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    changes.overflowed = true;
+                    continue;
+                }
+                if event.mask & libc::IN_IGNORED != 0 {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let Some((dir, recursive)) = self.dirs.get(&event.wd).cloned() else {
+                    continue;
+                };
+                if name.is_empty() {
+                    continue;
+                }
+                let path = dir.join(OsStr::from_bytes(name));
+                if event.mask & libc::IN_ISDIR != 0 {
+                    if recursive && event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                        let _ = self.watch(&path, true);
+                        collect_files(&path, &mut changes.paths);
+                    }
+                    continue;
+                }
+                changes.paths.push(path);
+            }
+        }
+    }
+
+    impl Drop for FsWatcher {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_symlink() {
+                continue;
+            }
+            if path.is_dir() {
+                collect_files(&path, out);
+            } else {
+                out.push(path);
+            }
+        }
+    }
+}
+
+/// Stand-in for platforms without inotify; the collector keeps polling.
+#[cfg(not(target_os = "linux"))]
+pub struct FsWatcher;
+
+#[cfg(not(target_os = "linux"))]
+impl FsWatcher {
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file watching is only implemented on Linux",
+        ))
+    }
+
+    pub fn is_watching(&self, _dir: &Path) -> bool {
+        false
+    }
+
+    pub fn watch(&mut self, _dir: &Path, _recursive: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<Changes> {
+        std::thread::sleep(timeout);
+        Ok(Changes::default())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Instant;
+
+    fn wait_for(watcher: &mut FsWatcher, target: &Path) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            let changes = watcher.wait(Duration::from_millis(100)).unwrap();
+            if changes.paths.iter().any(|p| p == target) {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_watcher_reports_appended_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.jsonl");
+        std::fs::write(&file, "one\n").unwrap();
+        let mut watcher = FsWatcher::new().unwrap();
+        watcher.watch(dir.path(), false).unwrap();
+        let mut f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file)
+            .unwrap();
+        writeln!(f, "two").unwrap();
+        assert!(wait_for(&mut watcher, &file));
+    }
+
+    #[test]
+    fn test_watcher_follows_new_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = FsWatcher::new().unwrap();
+        watcher.watch(dir.path(), true).unwrap();
+        let sub = dir.path().join("proj").join("session");
+        std::fs::create_dir_all(&sub).unwrap();
+        let file = sub.join("s.jsonl");
+        std::fs::write(&file, "one\n").unwrap();
+        assert!(wait_for(&mut watcher, &file));
+        assert!(watcher.is_watching(&sub));
+    }
+
+    #[test]
+    fn test_watcher_non_recursive_ignores_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = FsWatcher::new().unwrap();
+        watcher.watch(dir.path(), false).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let changes = watcher.wait(Duration::from_millis(200)).unwrap();
+        assert!(changes.paths.is_empty());
+        assert!(!watcher.is_watching(&dir.path().join("sub")));
+    }
+
+    #[test]
+    fn test_watcher_times_out_without_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = FsWatcher::new().unwrap();
+        watcher.watch(dir.path(), true).unwrap();
+        let started = Instant::now();
+        let changes = watcher.wait(Duration::from_millis(50)).unwrap();
+        assert!(changes.paths.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+}